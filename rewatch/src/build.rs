@@ -1,29 +1,43 @@
+pub mod affected;
+pub mod artifact_cache;
 pub mod build_types;
 pub mod clean;
 pub mod compile;
+pub mod compile_universe;
 pub mod compiler_info;
 pub mod deps;
+pub mod diagnostics;
+pub mod dry_run;
+pub mod events;
 pub mod logs;
 pub mod namespaces;
 pub mod packages;
 pub mod parse;
 pub mod read_compile_state;
+pub mod saved_state;
+pub mod source_hashes;
+pub mod summary;
+pub mod targets;
+pub mod timings;
 
 use self::parse::parser_args;
 use crate::build::compile::{mark_modules_with_deleted_deps_dirty, mark_modules_with_expired_deps_dirty};
+use crate::build::compile_universe::CompileReason;
 use crate::build::compiler_info::{CompilerCheckResult, verify_compiler_info, write_compiler_info};
+use crate::cli::{MessageFormat, TimingsFormat};
 use crate::config::SourceMapCommand;
 use crate::helpers::emojis::*;
 use crate::helpers::{self};
 use crate::lock::{LockKind, drop_lock, get_lock_or_exit};
 use crate::project_context::ProjectContext;
 use crate::sourcedirs;
+use ahash::AHashMap;
 use anyhow::{Context, Result, anyhow};
 use build_types::*;
 use console::style;
 use indicatif::{ProgressBar, ProgressStyle};
 use log::log_enabled;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::fs::File;
 use std::io::Write;
@@ -58,12 +72,43 @@ pub struct CompilerArgs {
     pub parser_args: Vec<String>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CompilationOutcome {
     Clean,
     Warnings,
 }
 
+/// The sources about to be parsed, which changed or are new since the last build.
+fn changed_sources(build_state: &BuildCommandState) -> Vec<PathBuf> {
+    let mut sources: Vec<PathBuf> = build_state
+        .modules
+        .values()
+        .filter_map(|module| match &module.source_type {
+            SourceType::SourceFile(source_file) => Some((module, source_file)),
+            SourceType::MlMap(_) => None,
+        })
+        .flat_map(|(module, source_file)| {
+            let package_path = build_state
+                .get_package(&module.package_name)
+                .map(|package| package.path.clone())
+                .unwrap_or_default();
+            let implementation = source_file
+                .implementation
+                .parse_dirty
+                .then(|| package_path.join(&source_file.implementation.path));
+            let interface = source_file
+                .interface
+                .as_ref()
+                .filter(|interface| interface.parse_dirty)
+                .map(|interface| package_path.join(&interface.path));
+            implementation.into_iter().chain(interface)
+        })
+        .collect();
+    sources.sort();
+    sources
+}
+
 fn has_output(output: &str) -> bool {
     helpers::contains_ascii_characters(output)
 }
@@ -145,6 +190,7 @@ pub fn get_compiler_args(rescript_file_path: &Path) -> Result<String> {
         None, // No warn_error_override for compiler-args command
         SourceMapCommand::Build,
         &[], // Source dirs not available outside full build; gentype falls back to defaults.
+        false,
     )?;
 
     let result = serde_json::to_string_pretty(&CompilerArgs {
@@ -206,22 +252,45 @@ pub fn initialize_build(
         features,
         source_map_command,
     );
-    packages::parse_packages(&mut build_state)?;
-
-    let compile_assets_state = read_compile_state::read(&mut build_state)?;
+    packages::parse_packages(&mut build_state, true)?;
+
+    // Everything in a cleaned package gets parsed and compiled again; attribute that to the
+    // compiler-info mismatch rather than to the sources.
+    if let CompilerCheckResult::CleanedPackagesDueToCompiler(cleaned_packages) = &compiler_check {
+        let cleaned_modules = build_state
+            .modules
+            .iter()
+            .filter(|(_, module)| cleaned_packages.contains(&module.package_name))
+            .map(|(module_name, _)| module_name.to_owned())
+            .collect::<Vec<String>>();
+        for module_name in cleaned_modules {
+            build_state.record_compile_dirty_reason(&module_name, CompileReason::CompilerInfoMismatch);
+        }
+    }
 
-    let (diff_cleanup, total_cleanup) = clean::cleanup_previous_build(&mut build_state, compile_assets_state);
+    // A package cleaned for a new compiler or config has nothing left to reuse.
+    let saved_state = match compiler_check {
+        CompilerCheckResult::SameCompilerAsLastRun => saved_state::restore(&mut build_state.build_state),
+        CompilerCheckResult::CleanedPackagesDueToCompiler(_) => None,
+    };
+    let (diff_cleanup, total_cleanup) = match saved_state {
+        Some(sources_with_ast) => (0, sources_with_ast),
+        None => {
+            let compile_assets_state = read_compile_state::read(&mut build_state)?;
+            clean::cleanup_previous_build(&mut build_state, compile_assets_state)
+        }
+    };
     let timing_clean_total = timing_clean_start.elapsed();
 
     if show_progress {
         if plain_output {
-            if let CompilerCheckResult::CleanedPackagesDueToCompiler = compiler_check {
+            if let CompilerCheckResult::CleanedPackagesDueToCompiler(_) = compiler_check {
                 // Snapshot-friendly output (no progress prefixes or emojis)
                 println!("Cleaned previous build due to compiler update");
             }
             println!("Cleaned {diff_cleanup}/{total_cleanup}")
         } else {
-            if let CompilerCheckResult::CleanedPackagesDueToCompiler = compiler_check {
+            if let CompilerCheckResult::CleanedPackagesDueToCompiler(_) = compiler_check {
                 println!(
                     "{}{} {}Cleaned previous build due to compiler update",
                     LINE_CLEAR,
@@ -244,6 +313,53 @@ pub fn initialize_build(
     Ok(build_state)
 }
 
+/// Initialize the build state and bring the module dependency graph up to date without
+/// compiling anything. Dirty sources are parsed so their `.ast` files list current imports.
+/// Used by commands that only inspect the graph, like `rescript graph`.
+pub fn load_module_graph(
+    filter: &Option<regex::Regex>,
+    path: &Path,
+    prod: bool,
+    features: Option<Vec<String>>,
+) -> Result<BuildCommandState> {
+    load_module_graph_with(filter, path, prod, features, |_| Ok(()))
+}
+
+/// Like `load_module_graph`, `prepare` gets the build state before the sources are parsed.
+#[instrument(name = "build.load_module_graph", skip_all, fields(working_dir = %path.display()))]
+pub fn load_module_graph_with(
+    filter: &Option<regex::Regex>,
+    path: &Path,
+    prod: bool,
+    features: Option<Vec<String>>,
+    prepare: impl FnOnce(&mut BuildState) -> Result<()>,
+) -> Result<BuildCommandState> {
+    with_build_lock(path, || {
+        let mut build_state = initialize_build(
+            None,
+            filter,
+            false,
+            path,
+            true,
+            None,
+            prod,
+            features,
+            SourceMapCommand::Build,
+        )
+        .with_context(|| "Could not initialize build")?;
+        prepare(&mut build_state.build_state)?;
+
+        logs::initialize(&build_state.packages);
+        let result_asts = parse::generate_asts(&mut build_state, || ());
+        logs::finalize(&build_state.packages);
+        result_asts.map_err(|err| anyhow!("Could not parse source files:\n{err}"))?;
+
+        let deleted_modules = build_state.deleted_modules.clone();
+        deps::get_deps(&mut build_state, &deleted_modules);
+        Ok(build_state)
+    })
+}
+
 fn format_step(current: usize, total: usize) -> console::StyledObject<String> {
     style(format!("[{current}/{total}]")).bold().dim()
 }
@@ -252,6 +368,11 @@ fn format_step(current: usize, total: usize) -> console::StyledObject<String> {
 pub enum IncrementalBuildErrorKind {
     SourceFileParseError,
     CompileError(Option<String>),
+    /// Sources changed while compiling, the build stopped before compiling every module.
+    Cancelled,
+    /// A full rebuild couldn't set up the new build state, e.g. because of an invalid
+    /// `rescript.json`.
+    InitializationError(String),
 }
 
 #[derive(Debug, Clone)]
@@ -284,6 +405,16 @@ impl fmt::Display for IncrementalBuildError {
                     write!(f, "{LINE_CLEAR}  {CROSS}Failed to Compile. See Errors Above",)
                 }
             }
+            IncrementalBuildErrorKind::Cancelled => {
+                write!(f, "{LINE_CLEAR}  Build cancelled because sources changed")
+            }
+            IncrementalBuildErrorKind::InitializationError(e) => {
+                if self.plain_output {
+                    write!(f, "{LINE_CLEAR}  Could not initialize build. Error: {e}",)
+                } else {
+                    write!(f, "{LINE_CLEAR}  {CROSS}Could not initialize build. Error: {e}",)
+                }
+            }
         }
     }
 }
@@ -333,7 +464,16 @@ pub fn incremental_build_without_lock(
     plain_output: bool,
 ) -> Result<CompilationOutcome, IncrementalBuildError> {
     logs::initialize(&build_state.packages);
+    build_state.diagnostics.clear();
+    build_state.timings.start();
+    // Changes that arrived before this point are already part of this build.
+    if let Some(cancel) = &build_state.cancel {
+        cancel.store(false, std::sync::atomic::Ordering::SeqCst);
+    }
+    let json_output = build_state.message_format == MessageFormat::Json;
     let num_dirty_modules = build_state.modules.values().filter(|m| is_dirty(m)).count() as u64;
+    build_state.changed_sources = changed_sources(build_state);
+    build_state.compiled_modules.clear();
     let pb = if !plain_output && show_progress {
         ProgressBar::new(num_dirty_modules)
     } else {
@@ -353,8 +493,14 @@ pub fn incremental_build_without_lock(
     let timing_parse_start = Instant::now();
     let timing_ast = Instant::now();
     let result_asts = parse::generate_asts(build_state, || pb.inc(1));
+    source_hashes::write(build_state);
     let timing_ast_elapsed = timing_ast.elapsed();
 
+    build_state.emit(&events::Event::ParseFinished {
+        parsed: num_dirty_modules,
+        success: result_asts.is_ok(),
+    });
+
     let parse_warnings = match result_asts {
         Ok(warnings) => {
             pb.finish();
@@ -363,6 +509,7 @@ pub fn incremental_build_without_lock(
         Err(err) => {
             let _error_span = info_span!("build.parse_error").entered();
             logs::finalize(&build_state.packages);
+            timings::write(build_state);
 
             if !plain_output && show_progress {
                 eprintln!(
@@ -375,7 +522,11 @@ pub fn incremental_build_without_lock(
                 pb.finish();
             }
 
-            eprintln!("{}", &err);
+            if json_output {
+                print_diagnostics(build_state);
+            } else {
+                eprintln!("{}", &err);
+            }
 
             return Err(IncrementalBuildError {
                 kind: IncrementalBuildErrorKind::SourceFileParseError,
@@ -402,7 +553,7 @@ pub fn incremental_build_without_lock(
         }
     }
     let has_parse_warnings = has_output(&parse_warnings);
-    if has_parse_warnings {
+    if has_parse_warnings && !json_output && !build_state.hide_warnings {
         eprintln!("{}", &parse_warnings);
     }
 
@@ -434,7 +585,7 @@ pub fn incremental_build_without_lock(
         .unwrap(),
     );
 
-    let (compile_errors, compile_warnings, num_compiled_modules) = compile::compile(
+    let (compile_errors, compile_warnings, num_compiled_modules, cancelled) = compile::compile(
         build_state,
         show_progress,
         || pb.inc(1),
@@ -446,12 +597,23 @@ pub fn incremental_build_without_lock(
     })?;
 
     let compile_duration = start_compiling.elapsed();
+    compile_universe::write(build_state);
+    timings::write(build_state);
 
     logs::finalize(&build_state.packages);
     if create_sourcedirs {
         sourcedirs::print(build_state);
     }
     pb.finish();
+    if cancelled {
+        return Err(IncrementalBuildError {
+            kind: IncrementalBuildErrorKind::Cancelled,
+            plain_output,
+        });
+    }
+    if build_state.explain {
+        print_explanation(build_state);
+    }
     if !compile_errors.is_empty() {
         let _error_span = info_span!("build.compile_error").entered();
         if show_progress {
@@ -468,14 +630,16 @@ pub fn incremental_build_without_lock(
                 );
             }
         }
-        if has_output(&compile_warnings) {
+        if has_output(&compile_warnings) && !json_output && !build_state.hide_warnings {
             let _warning_span = info_span!("build.compile_warning").entered();
             eprintln!("{}", &compile_warnings);
         }
         if initial_build {
             log_config_warnings(build_state);
         }
-        if has_output(&compile_errors) {
+        if json_output {
+            print_diagnostics(build_state);
+        } else if has_output(&compile_errors) {
             eprintln!("{}", &compile_errors);
         }
 
@@ -507,7 +671,9 @@ pub fn incremental_build_without_lock(
             }
         }
 
-        if has_compile_warnings {
+        if json_output {
+            print_diagnostics(build_state);
+        } else if has_compile_warnings && !build_state.hide_warnings {
             eprintln!("{}", &compile_warnings);
         }
         if initial_build {
@@ -521,6 +687,49 @@ pub fn incremental_build_without_lock(
     }
 }
 
+fn format_explanation(compile_universe: &AHashMap<String, CompileReason>) -> String {
+    if compile_universe.is_empty() {
+        return "Explain: no modules needed compiling".to_string();
+    }
+    let mut modules = compile_universe.iter().collect::<Vec<_>>();
+    modules.sort_by(|a, b| a.0.cmp(b.0));
+    let mut out = format!("Explain: compiling {} modules", modules.len());
+    for (module_name, reason) in modules {
+        out.push_str(&format!(
+            "\n  {}: {}",
+            helpers::format_namespaced_module_name(module_name),
+            reason.describe()
+        ));
+    }
+    out
+}
+
+// Keep stdout reserved for diagnostics and events when they are emitted as JSON.
+fn print_explanation(build_state: &BuildCommandState) {
+    let explanation = format_explanation(&build_state.compile_universe);
+    if build_state.message_format == MessageFormat::Json
+        || build_state
+            .events
+            .as_deref()
+            .is_some_and(events::EventSink::writes_to_stdout)
+    {
+        eprintln!("{explanation}");
+    } else {
+        println!("{explanation}");
+    }
+}
+
+// With `--message-format json`, stdout carries one diagnostic per line and nothing else, so
+// tools can consume it without scraping the colored bsc output.
+fn print_diagnostics(build_state: &BuildCommandState) {
+    let stdout = std::io::stdout();
+    let mut handle = stdout.lock();
+    for diagnostic in &build_state.diagnostics {
+        let _ = writeln!(handle, "{}", diagnostic.to_json());
+    }
+    let _ = handle.flush();
+}
+
 fn log_config_warnings(build_state: &BuildCommandState) {
     let mut packages: Vec<_> = build_state.packages.values().collect();
     packages.sort_by(|a, b| a.name.cmp(&b.name));
@@ -618,6 +827,14 @@ pub fn write_build_ninja(build_state: &BuildCommandState) {
     }
 }
 
+/// A type-check leaves modules without JS that the saved state would consider compiled. Without
+/// it, the next build finds them by their missing `.cmj`.
+fn write_saved_state(build_state: &BuildCommandState) {
+    if !build_state.check_only {
+        saved_state::write(build_state);
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 #[instrument(name = "rewatch.build", skip_all, fields(working_dir = %path.display()))]
 pub fn build(
@@ -630,7 +847,14 @@ pub fn build(
     warn_error: Option<String>,
     prod: bool,
     features: Option<Vec<String>>,
-) -> Result<BuildCommandState> {
+    message_format: MessageFormat,
+    explain: bool,
+    keep_going: bool,
+    timings_format: Option<TimingsFormat>,
+    check_only: bool,
+    targets: &[String],
+    since: Option<&str>,
+) -> Result<summary::BuildSummary> {
     let default_timing: Option<std::time::Duration> = if no_timing {
         Some(std::time::Duration::new(0.0 as u64, 0.0 as u32))
     } else {
@@ -650,6 +874,17 @@ pub fn build(
             SourceMapCommand::Build,
         )
         .with_context(|| "Could not initialize build")?;
+        build_state.message_format = message_format;
+        build_state.explain = explain;
+        build_state.keep_going = keep_going;
+        build_state.timings_format = timings_format;
+        build_state.check_only = check_only;
+        if !targets.is_empty() {
+            build_state.targets = Some(targets::resolve(targets, &build_state.build_state)?);
+        }
+        if let Some(since) = since {
+            build_state.since_changed = Some(affected::record_changes(&mut build_state.build_state, since)?);
+        }
 
         match incremental_build_without_lock(
             &mut build_state,
@@ -673,11 +908,13 @@ pub fn build(
                     );
                 }
                 clean::cleanup_after_build(&build_state);
+                write_saved_state(&build_state);
                 write_build_ninja(&build_state);
-                Ok(build_state)
+                Ok(summary::BuildSummary::new(&build_state, result))
             }
             Err(e) => {
                 clean::cleanup_after_build(&build_state);
+                write_saved_state(&build_state);
                 write_build_ninja(&build_state);
                 Err(anyhow!("Incremental build failed. Error: {e}"))
             }
@@ -754,6 +991,13 @@ mod tests {
                 None,
                 false,
                 None,
+                MessageFormat::Human,
+                false,
+                false,
+                None,
+                false,
+                &[],
+                None,
             );
             sender.send(result.is_err()).expect("result should be sent");
         });
@@ -772,6 +1016,28 @@ mod tests {
         build_thread.join().expect("build thread should complete");
     }
 
+    #[test]
+    fn explanation_lists_modules_with_reasons() {
+        let compile_universe = AHashMap::from([
+            (
+                "App".to_string(),
+                CompileReason::DirtyDependency {
+                    module: "Button-Ui".to_string(),
+                },
+            ),
+            ("Button-Ui".to_string(), CompileReason::ParseDirty),
+        ]);
+
+        assert_eq!(
+            format_explanation(&compile_universe),
+            "Explain: compiling 2 modules\n  App: depends on Ui.Button\n  Ui.Button: source changed"
+        );
+        assert_eq!(
+            format_explanation(&AHashMap::new()),
+            "Explain: no modules needed compiling"
+        );
+    }
+
     #[test]
     fn formats_successful_completion_message() {
         assert_eq!(
@@ -0,0 +1,324 @@
+//! `rescript build --since` and `rescript affected`: the modules whose sources (or whose package's
+//! `rescript.json`) changed since a git revision, and every module that depends on them.
+
+use super::build_types::{BuildState, Module};
+use super::packages::Package;
+use crate::build;
+use crate::helpers;
+use ahash::{AHashMap, AHashSet};
+use anyhow::{Context, Result, anyhow};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::instrument;
+
+/// Files that differ between `since` and the working tree, as absolute paths, deleted ones
+/// included.
+pub fn changed_files(root: &Path, since: &str) -> Result<Vec<PathBuf>> {
+    let toplevel = git(root, &["rev-parse", "--show-toplevel"])?;
+    let toplevel = PathBuf::from(toplevel.trim());
+    let toplevel = toplevel.canonicalize().unwrap_or(toplevel);
+    // `-z` lists paths as they are, without it git quotes paths with non-ASCII characters.
+    let diff = git(root, &["diff", "--name-only", "-z", since, "--"])?;
+    Ok(diff
+        .split('\0')
+        .filter(|file| !file.is_empty())
+        .map(|file| toplevel.join(file))
+        .collect())
+}
+
+fn git(root: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(root)
+        .output()
+        .with_context(|| "Could not run git")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git {} failed:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The modules changed since `since`. Modules whose source was deleted are added to
+/// `deleted_modules`, so the modules importing them keep them in their deps and are compiled
+/// again, even when no previous build knew about them.
+pub fn record_changes(build_state: &mut BuildState, since: &str) -> Result<AHashSet<String>> {
+    let files = changed_files(build_state.project_context.get_root_path(), since)?;
+    let changed = changed_modules(&files, build_state);
+    let deleted = changed
+        .iter()
+        .filter(|module_name| !build_state.modules.contains_key(*module_name))
+        .cloned()
+        .collect::<Vec<String>>();
+    build_state.deleted_modules.extend(deleted);
+    Ok(changed)
+}
+
+/// Maps changed files to the modules they belong to. A changed `rescript.json` marks every module
+/// of its package, other files are looked up in the source files of the package they're in. A
+/// deleted source maps to the module it was, and to the namespace of its package, which changes
+/// with it.
+pub fn changed_modules(files: &[PathBuf], build_state: &BuildState) -> AHashSet<String> {
+    let mut changed = AHashSet::new();
+    for file in files {
+        let Some(package) = package_for_file(file, build_state.packages.values()) else {
+            continue;
+        };
+        if file.file_name().is_some_and(|name| name == "rescript.json")
+            && file.parent() == Some(&package.path)
+        {
+            changed.extend(
+                build_state
+                    .modules
+                    .iter()
+                    .filter(|(_, module)| module.package_name == package.name)
+                    .map(|(module_name, _)| module_name.clone()),
+            );
+        } else if let Some(module_name) = module_for_source_file(file, package) {
+            changed.insert(module_name);
+        } else if let Some(module_name) = module_for_deleted_file(file, package) {
+            if let Some(namespace) = package.namespace.to_suffix() {
+                changed.insert(namespace);
+            }
+            changed.insert(module_name);
+        }
+    }
+    changed
+}
+
+/// The innermost package containing `file`, packages can live inside the folder of another one.
+fn package_for_file<'a>(file: &Path, packages: impl Iterator<Item = &'a Package>) -> Option<&'a Package> {
+    packages
+        .filter(|package| file.starts_with(&package.path))
+        .max_by_key(|package| package.path.components().count())
+}
+
+fn module_for_source_file(file: &Path, package: &Package) -> Option<String> {
+    if !helpers::is_source_file(file.extension()?.to_str()?) {
+        return None;
+    }
+    let relative = file.strip_prefix(&package.path).ok()?;
+    package
+        .source_files
+        .as_ref()?
+        .contains_key(relative)
+        .then(|| helpers::file_path_to_module_name(relative, &package.namespace))
+}
+
+fn module_for_deleted_file(file: &Path, package: &Package) -> Option<String> {
+    if file.exists() || !helpers::is_source_file(file.extension()?.to_str()?) {
+        return None;
+    }
+    let relative = file.strip_prefix(&package.path).ok()?;
+    Some(helpers::file_path_to_module_name(relative, &package.namespace))
+}
+
+/// The changed modules and every module that depends on them, transitively. A deleted module has
+/// no entry in `modules` anymore, the modules that still import it are found through their deps.
+pub fn dependents_closure(
+    changed: &AHashSet<String>,
+    modules: &AHashMap<String, Module>,
+) -> AHashSet<String> {
+    let mut closure = changed.clone();
+    let mut frontier = changed.iter().cloned().collect::<Vec<String>>();
+    while let Some(module_name) = frontier.pop() {
+        let dependents = match modules.get(&module_name) {
+            Some(module) => module.dependents.iter().cloned().collect::<Vec<String>>(),
+            None => modules
+                .iter()
+                .filter(|(_, module)| module.deps.contains(&module_name))
+                .map(|(dependent, _)| dependent.clone())
+                .collect(),
+        };
+        for dependent in dependents {
+            if closure.insert(dependent.clone()) {
+                frontier.push(dependent);
+            }
+        }
+    }
+    closure
+}
+
+/// Prints the modules affected by the changes since `since`, one per line. Deleted modules are
+/// left out, the modules importing them are listed.
+#[instrument(name = "affected.affected", skip_all, fields(working_dir = %path.display()))]
+pub fn affected(path: &Path, since: &str, prod: bool) -> Result<()> {
+    let mut changed = AHashSet::new();
+    let build_state = build::load_module_graph_with(&None, path, prod, None, |build_state| {
+        changed = record_changes(build_state, since)?;
+        Ok(())
+    })?;
+    let mut affected = dependents_closure(&changed, &build_state.modules)
+        .iter()
+        .filter(|module_name| build_state.modules.contains_key(*module_name))
+        .map(|module_name| helpers::format_namespaced_module_name(module_name))
+        .collect::<Vec<String>>();
+    affected.sort();
+    for module_name in affected {
+        println!("{module_name}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::build_types::{MlMap, SourceType};
+    use super::super::packages::{Namespace, SourceFileMeta};
+    use super::*;
+    use crate::config;
+    use std::time::SystemTime;
+
+    fn package(name: &str, path: &str, files: &[&str], namespace: Namespace) -> Package {
+        let source_file = SourceFileMeta {
+            modified: SystemTime::UNIX_EPOCH,
+            is_type_dev: false,
+        };
+        Package {
+            name: name.to_string(),
+            config: config::tests::create_config(config::tests::CreateConfigArgs {
+                name: name.to_string(),
+                bs_deps: vec![],
+                build_dev_deps: vec![],
+                allowed_dependents: None,
+                path: PathBuf::from(path),
+            }),
+            source_folders: AHashSet::new(),
+            source_files: Some(
+                files
+                    .iter()
+                    .map(|file| (PathBuf::from(file), source_file.clone()))
+                    .collect(),
+            ),
+            namespace,
+            modules: None,
+            path: PathBuf::from(path),
+            dirs: None,
+            gentype_dirs: None,
+            is_local_dep: true,
+            is_root: false,
+        }
+    }
+
+    fn module(dependents: &[&str]) -> Module {
+        Module {
+            source_type: SourceType::MlMap(MlMap { parse_dirty: false }),
+            deps: AHashSet::new(),
+            dependents: dependents.iter().map(|dependent| dependent.to_string()).collect(),
+            package_name: "pkg".to_string(),
+            compile_dirty: false,
+            last_compiled_cmi: None,
+            last_compiled_cmt: None,
+            deps_dirty: false,
+            is_type_dev: false,
+        }
+    }
+
+    #[test]
+    fn closure_contains_the_transitive_dependents() {
+        let modules = AHashMap::from([
+            ("Theme".to_string(), module(&["Layout"])),
+            ("Layout".to_string(), module(&["Home", "Admin"])),
+            ("Home".to_string(), module(&[])),
+            ("Admin".to_string(), module(&[])),
+            ("Api".to_string(), module(&["Home"])),
+        ]);
+        let changed = AHashSet::from(["Theme".to_string()]);
+        let mut closure = dependents_closure(&changed, &modules)
+            .into_iter()
+            .collect::<Vec<_>>();
+        closure.sort();
+        assert_eq!(closure, vec!["Admin", "Home", "Layout", "Theme"]);
+    }
+
+    #[test]
+    fn source_files_map_to_modules_of_the_innermost_package() {
+        let packages = [
+            package("app", "/repo", &["src/App.res"], Namespace::NoNamespace),
+            package(
+                "ui",
+                "/repo/packages/ui",
+                &["src/Button.res", "src/Button.resi"],
+                Namespace::Namespace("Ui".to_string()),
+            ),
+        ];
+        let module_for = |file: &str| {
+            let file = Path::new(file);
+            package_for_file(file, packages.iter()).and_then(|package| module_for_source_file(file, package))
+        };
+
+        assert_eq!(module_for("/repo/src/App.res").as_deref(), Some("App"));
+        assert_eq!(
+            module_for("/repo/packages/ui/src/Button.resi").as_deref(),
+            Some("Button-Ui")
+        );
+        assert_eq!(module_for("/repo/src/Deleted.res"), None);
+        assert_eq!(module_for("/repo/README.md"), None);
+        assert_eq!(module_for("/elsewhere/src/App.res"), None);
+    }
+
+    #[test]
+    fn deleted_sources_map_to_the_module_they_were() {
+        let package = package(
+            "ui",
+            "/nonexistent/packages/ui",
+            &["src/Button.res"],
+            Namespace::Namespace("Ui".to_string()),
+        );
+        let module_for = |file: &str| module_for_deleted_file(Path::new(file), &package);
+
+        assert_eq!(
+            module_for("/nonexistent/packages/ui/src/Icon.res").as_deref(),
+            Some("Icon-Ui")
+        );
+        assert_eq!(module_for("/nonexistent/packages/ui/README.md"), None);
+    }
+
+    #[test]
+    fn importers_of_a_deleted_module_are_affected() {
+        let mut app = module(&["Home"]);
+        app.deps = AHashSet::from(["Icon".to_string()]);
+        let modules = AHashMap::from([
+            ("App".to_string(), app),
+            ("Home".to_string(), module(&[])),
+            ("Main".to_string(), module(&[])),
+        ]);
+        let changed = AHashSet::from(["Icon".to_string()]);
+        let mut closure = dependents_closure(&changed, &modules)
+            .into_iter()
+            .collect::<Vec<_>>();
+        closure.sort();
+        assert_eq!(closure, vec!["App", "Home", "Icon"]);
+    }
+
+    #[test]
+    fn changed_files_keep_non_ascii_paths() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir should be created");
+        let root = temp_dir.path().canonicalize().unwrap();
+        let run = |args: &[&str]| {
+            git(&root, args).expect("git should succeed");
+        };
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/Überblick.res"), "let a = 1\n").unwrap();
+        run(&["init", "-q"]);
+        run(&["add", "-A"]);
+        run(&[
+            "-c",
+            "user.name=test",
+            "-c",
+            "user.email=test@example.com",
+            "commit",
+            "-q",
+            "-m",
+            "init",
+        ]);
+        std::fs::write(root.join("src/Überblick.res"), "let a = 2\n").unwrap();
+
+        assert_eq!(
+            changed_files(&root, "HEAD").unwrap(),
+            vec![root.join("src/Überblick.res")]
+        );
+    }
+}
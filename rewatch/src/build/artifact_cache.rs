@@ -0,0 +1,436 @@
+//! An opt-in, content-addressed cache of compiler output that is shared between builds. It's
+//! stored in a local directory (`RESCRIPT_CACHE_DIR`), on a remote HTTP server (see `remote`), or
+//! both, in which case entries found remotely are kept locally as well.
+//!
+//! Parsing stores the `.ast`/`.iast` of a source, compiling stores the `.cmi`, `.cmj`,
+//! `.cmt`/`.cmti` and the generated JavaScript of a module. An entry is keyed by everything bsc's
+//! output depends on: the compiler (`CompilerInfo.bsc_hash`), the content of the source (and of
+//! its AST when compiling), the arguments bsc is called with and, when compiling, the `.cmi` of
+//! every dependency. The project root is left out: it's replaced by a placeholder in everything
+//! that goes into a key and in the stored artifacts, which bsc writes with absolute paths (see
+//! `relocate`). Entries are shared by every checkout of the project, like the worktrees of a
+//! repository or CI jobs that check out to different directories.
+//!
+//! Only output without warnings is stored, because restoring an entry can't replay what bsc
+//! printed.
+
+mod relocate;
+mod remote;
+
+use super::build_types::*;
+use crate::config::Config;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub use remote::{REMOTE_CACHE_MODE_ENV, REMOTE_CACHE_TOKEN_ENV, REMOTE_CACHE_URL_ENV};
+
+pub const CACHE_DIR_ENV: &str = "RESCRIPT_CACHE_DIR";
+
+/// Bump when what goes into a key, or the layout of an entry, changes.
+const FORMAT_VERSION: &str = "2";
+
+/// The files of a cache entry: the name each artifact is stored under, and its content.
+pub type EntryFiles = Vec<(String, Vec<u8>)>;
+
+/// Where cache entries are kept.
+pub trait Backend: std::fmt::Debug + Send + Sync {
+    /// The files of the entry for `key`, or `None` when there is no such entry.
+    fn get(&self, key: &blake3::Hash) -> Option<EntryFiles>;
+    /// Store an entry. A cache is an optimization, so failing to store one is only logged.
+    fn put(&self, key: &blake3::Hash, files: &EntryFiles);
+}
+
+#[derive(Debug)]
+pub struct ArtifactCache {
+    /// Tried in order. When an entry is found, the backends before it get a copy.
+    backends: Vec<Box<dyn Backend>>,
+    /// The project root as bsc sees it, through its working directory: with symlinks resolved.
+    root: String,
+}
+
+/// A file bsc writes, stored in a cache entry under `name`.
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub name: String,
+    pub path: PathBuf,
+    /// Whether bsc always writes this file. Optional ones (like source maps) are restored when the
+    /// entry has them and removed when it doesn't.
+    pub required: bool,
+}
+
+impl Artifact {
+    pub fn required(name: impl Into<String>, path: PathBuf) -> Self {
+        Self {
+            name: name.into(),
+            path,
+            required: true,
+        }
+    }
+
+    pub fn optional(name: impl Into<String>, path: PathBuf) -> Self {
+        Self {
+            name: name.into(),
+            path,
+            required: false,
+        }
+    }
+}
+
+/// What bsc does with the source, part of the key so a parse and a compile never share an entry.
+#[derive(Debug, Clone, Copy)]
+pub enum Step {
+    Parse,
+    CompileImplementation,
+    CompileInterface,
+}
+
+impl Step {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Step::Parse => "parse",
+            Step::CompileImplementation => "compile-implementation",
+            Step::CompileInterface => "compile-interface",
+        }
+    }
+}
+
+impl ArtifactCache {
+    pub fn new(backends: Vec<Box<dyn Backend>>, root: &Path) -> Self {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        Self {
+            backends,
+            root: root.to_string_lossy().into_owned(),
+        }
+    }
+
+    /// The cache configured for the project: the local directory from `RESCRIPT_CACHE_DIR` and
+    /// the remote cache from the `cache` section of the root `rescript.json` or its environment
+    /// variables. `None` when neither is set.
+    pub fn from_config(root_config: &Config, root: &Path) -> Option<Self> {
+        let mut backends: Vec<Box<dyn Backend>> = Vec::new();
+        if let Some(dir) = std::env::var_os(CACHE_DIR_ENV).filter(|dir| !dir.is_empty()) {
+            backends.push(Box::new(LocalCache::new(PathBuf::from(dir))));
+        }
+        match remote::RemoteCache::from_config(root_config) {
+            Ok(Some(remote)) => backends.push(Box::new(remote)),
+            Ok(None) => (),
+            Err(err) => log::warn!("Not using the remote cache: {err}"),
+        }
+        (!backends.is_empty()).then(|| Self::new(backends, root))
+    }
+
+    /// Copy the artifacts of the entry for `key` into place. Returns `false`, without touching
+    /// anything, when no backend has a complete entry for `key`.
+    pub fn restore(&self, key: &blake3::Hash, artifacts: &[Artifact]) -> bool {
+        for (index, backend) in self.backends.iter().enumerate() {
+            let Some(files) = backend.get(key) else {
+                continue;
+            };
+            if !is_complete(&files, artifacts) {
+                continue;
+            }
+            let Some(relocated) = relocate_files(&files, relocate::ROOT_PLACEHOLDER, &self.root) else {
+                log::debug!("Could not relocate the entry {}", key.to_hex());
+                continue;
+            };
+            if !write_artifacts(&relocated, artifacts) {
+                return false;
+            }
+            for earlier in &self.backends[..index] {
+                earlier.put(key, &files);
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Store the artifacts bsc just wrote under `key`.
+    pub fn store(&self, key: &blake3::Hash, artifacts: &[Artifact]) {
+        let files = match read_artifacts(artifacts) {
+            Ok(files) => files,
+            Err(err) => {
+                log::debug!("Failed to read the artifacts of {}: {}", key.to_hex(), err);
+                return;
+            }
+        };
+        match relocate_files(&files, &self.root, relocate::ROOT_PLACEHOLDER) {
+            Some(files) => self.backends.iter().for_each(|backend| backend.put(key, &files)),
+            None => log::debug!("Could not relocate the artifacts of {}", key.to_hex()),
+        }
+    }
+
+    /// The key of running bsc with `args` on `inputs`, the files it reads. `dependency_cmis`
+    /// lists the name and `.cmi` hash (see `file_hash`) of every module the source depends on,
+    /// and is empty when parsing. Returns `None` when an input can't be read.
+    pub fn key(
+        &self,
+        build_state: &BuildState,
+        step: Step,
+        inputs: &[PathBuf],
+        args: &[String],
+        dependency_cmis: &[(String, Option<blake3::Hash>)],
+    ) -> Option<blake3::Hash> {
+        let mut hasher = blake3::Hasher::new();
+        hash_field(&mut hasher, FORMAT_VERSION.as_bytes());
+        hash_field(&mut hasher, build_state.compiler_info.bsc_hash.as_bytes());
+        hash_field(&mut hasher, step.as_str().as_bytes());
+        hasher.update(&(inputs.len() as u64).to_le_bytes());
+        for input in inputs {
+            hash_field(&mut hasher, &self.without_root(&std::fs::read(input).ok()?)?);
+        }
+        hasher.update(&(args.len() as u64).to_le_bytes());
+        for arg in args {
+            let mut arg_without_root = Vec::new();
+            relocate::replace_paths(
+                arg.as_bytes(),
+                &self.root,
+                relocate::ROOT_PLACEHOLDER,
+                &mut arg_without_root,
+            );
+            hash_field(&mut hasher, &arg_without_root);
+        }
+        hasher.update(&(dependency_cmis.len() as u64).to_le_bytes());
+        for (name, cmi_hash) in dependency_cmis {
+            hash_field(&mut hasher, name.as_bytes());
+            hash_field(
+                &mut hasher,
+                cmi_hash
+                    .as_ref()
+                    .map(|hash| hash.as_bytes().as_slice())
+                    .unwrap_or(&[]),
+            );
+        }
+        Some(hasher.finalize())
+    }
+
+    /// The hash of a file as it's stored in an entry, so it's the same in every checkout. `None`
+    /// when it can't be read.
+    pub fn file_hash(&self, path: &Path) -> Option<blake3::Hash> {
+        Some(blake3::hash(&self.without_root(&std::fs::read(path).ok()?)?))
+    }
+
+    fn without_root(&self, contents: &[u8]) -> Option<Vec<u8>> {
+        relocate::relocate(contents, &self.root, relocate::ROOT_PLACEHOLDER)
+    }
+}
+
+fn relocate_files(files: &EntryFiles, from: &str, to: &str) -> Option<EntryFiles> {
+    files
+        .iter()
+        .map(|(name, contents)| Some((name.clone(), relocate::relocate(contents, from, to)?)))
+        .collect()
+}
+
+fn is_complete(files: &EntryFiles, artifacts: &[Artifact]) -> bool {
+    artifacts
+        .iter()
+        .all(|artifact| !artifact.required || files.iter().any(|(name, _)| *name == artifact.name))
+}
+
+fn read_artifacts(artifacts: &[Artifact]) -> std::io::Result<EntryFiles> {
+    artifacts
+        .iter()
+        .filter(|artifact| artifact.required || artifact.path.is_file())
+        .map(|artifact| Ok((artifact.name.clone(), std::fs::read(&artifact.path)?)))
+        .collect()
+}
+
+fn write_artifacts(files: &EntryFiles, artifacts: &[Artifact]) -> bool {
+    artifacts.iter().all(|artifact| {
+        let Some((_, contents)) = files.iter().find(|(name, _)| *name == artifact.name) else {
+            let _ = std::fs::remove_file(&artifact.path);
+            return true;
+        };
+        if let Some(parent) = artifact.path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        std::fs::write(&artifact.path, contents).is_ok()
+    })
+}
+
+/// Entries as directories of files in a local directory. They are written to a temporary
+/// directory and renamed into place, so a build running at the same time never sees half of an
+/// entry.
+#[derive(Debug)]
+pub struct LocalCache {
+    dir: PathBuf,
+}
+
+static NEXT_TEMP_ID: AtomicU64 = AtomicU64::new(0);
+
+impl LocalCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn entry_path(&self, key: &blake3::Hash) -> PathBuf {
+        let hex = key.to_hex();
+        self.dir.join(&hex[..2]).join(hex.as_str())
+    }
+}
+
+impl Backend for LocalCache {
+    fn get(&self, key: &blake3::Hash) -> Option<EntryFiles> {
+        std::fs::read_dir(self.entry_path(key))
+            .ok()?
+            .map(|file| {
+                let file = file.ok()?;
+                Some((
+                    file.file_name().to_string_lossy().to_string(),
+                    std::fs::read(file.path()).ok()?,
+                ))
+            })
+            .collect()
+    }
+
+    fn put(&self, key: &blake3::Hash, files: &EntryFiles) {
+        let entry = self.entry_path(key);
+        if entry.is_dir() {
+            return;
+        }
+        let temp = self.dir.join("tmp").join(format!(
+            "{}-{}-{}",
+            key.to_hex(),
+            std::process::id(),
+            NEXT_TEMP_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = write_entry(&temp, &entry, files);
+        if let Err(err) = result {
+            let _ = std::fs::remove_dir_all(&temp);
+            log::debug!(
+                "Failed to store {} in the artifact cache: {}",
+                entry.display(),
+                err
+            );
+        }
+    }
+}
+
+fn write_entry(temp: &Path, entry: &Path, files: &EntryFiles) -> anyhow::Result<()> {
+    std::fs::create_dir_all(temp)?;
+    for (name, contents) in files {
+        std::fs::write(temp.join(name), contents)?;
+    }
+    std::fs::create_dir_all(entry.parent().expect("entry has a parent"))?;
+    if std::fs::rename(temp, entry).is_err() {
+        // Another build stored the same entry first.
+        std::fs::remove_dir_all(temp)?;
+    }
+    Ok(())
+}
+
+fn hash_field(hasher: &mut blake3::Hasher, field: &[u8]) {
+    hasher.update(&(field.len() as u64).to_le_bytes());
+    hasher.update(field);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn local_cache(temp_dir: &TempDir, name: &str) -> Box<dyn Backend> {
+        Box::new(LocalCache::new(temp_dir.path().join(name)))
+    }
+
+    #[test]
+    fn restores_what_was_stored_under_the_same_key() {
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        let cache = ArtifactCache::new(vec![local_cache(&temp_dir, "cache")], temp_dir.path());
+        let build = temp_dir.path().join("build");
+        std::fs::create_dir_all(&build).unwrap();
+        std::fs::write(build.join("App.cmj"), "cmj").unwrap();
+        std::fs::write(build.join("App.js.map"), "map").unwrap();
+        let artifacts = vec![
+            Artifact::required("cmj", build.join("App.cmj")),
+            Artifact::optional("js.0.map", build.join("App.js.map")),
+        ];
+        let key = blake3::hash(b"key");
+
+        assert!(!cache.restore(&key, &artifacts));
+        cache.store(&key, &artifacts);
+
+        std::fs::remove_file(build.join("App.cmj")).unwrap();
+        std::fs::remove_file(build.join("App.js.map")).unwrap();
+        assert!(cache.restore(&key, &artifacts));
+        assert_eq!(std::fs::read_to_string(build.join("App.cmj")).unwrap(), "cmj");
+        assert_eq!(std::fs::read_to_string(build.join("App.js.map")).unwrap(), "map");
+
+        assert!(!cache.restore(&blake3::hash(b"other key"), &artifacts));
+        assert!(
+            !temp_dir
+                .path()
+                .join("cache")
+                .join("tmp")
+                .read_dir()
+                .unwrap()
+                .any(|_| true)
+        );
+    }
+
+    #[test]
+    fn optional_artifacts_missing_from_the_entry_are_removed() {
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        let cache = ArtifactCache::new(vec![local_cache(&temp_dir, "cache")], temp_dir.path());
+        let cmj = temp_dir.path().join("App.cmj");
+        let map = temp_dir.path().join("App.js.map");
+        std::fs::write(&cmj, "cmj").unwrap();
+        let artifacts = vec![
+            Artifact::required("cmj", cmj.clone()),
+            Artifact::optional("js.0.map", map.clone()),
+        ];
+        let key = blake3::hash(b"key");
+        cache.store(&key, &artifacts);
+
+        std::fs::write(&map, "stale map").unwrap();
+        assert!(cache.restore(&key, &artifacts));
+        assert!(!map.exists());
+    }
+
+    #[test]
+    fn entries_found_in_a_later_backend_are_copied_to_the_earlier_ones() {
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        let cmj = temp_dir.path().join("App.cmj");
+        std::fs::write(&cmj, "cmj").unwrap();
+        let artifacts = vec![Artifact::required("cmj", cmj.clone())];
+        let key = blake3::hash(b"key");
+        ArtifactCache::new(vec![local_cache(&temp_dir, "shared")], temp_dir.path()).store(&key, &artifacts);
+
+        let cache = ArtifactCache::new(
+            vec![local_cache(&temp_dir, "local"), local_cache(&temp_dir, "shared")],
+            temp_dir.path(),
+        );
+        assert!(cache.restore(&key, &artifacts));
+        assert!(LocalCache::new(temp_dir.path().join("local")).get(&key).is_some());
+    }
+
+    #[test]
+    fn entries_are_moved_to_the_root_of_the_project_they_are_restored_into() {
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        let first = temp_dir.path().join("first");
+        let second = temp_dir.path().join("second");
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+        let first_root = first.canonicalize().unwrap();
+        let second_root = second.canonicalize().unwrap();
+        std::fs::write(
+            first.join("App.ast"),
+            format!("{}/src/App.res", first_root.display()),
+        )
+        .unwrap();
+        let key = blake3::hash(b"key");
+        ArtifactCache::new(vec![local_cache(&temp_dir, "cache")], &first)
+            .store(&key, &[Artifact::required("ast", first.join("App.ast"))]);
+
+        let cache = ArtifactCache::new(vec![local_cache(&temp_dir, "cache")], &second);
+        assert!(cache.restore(&key, &[Artifact::required("ast", second.join("App.ast"))]));
+        assert_eq!(
+            std::fs::read_to_string(second.join("App.ast")).unwrap(),
+            format!("{}/src/App.res", second_root.display())
+        );
+        assert_eq!(
+            cache.file_hash(&second.join("App.ast")),
+            ArtifactCache::new(vec![], &first).file_hash(&first.join("App.ast"))
+        );
+    }
+}
@@ -0,0 +1,368 @@
+//! Moving cache entries between checkouts. bsc records the absolute paths of sources, the build
+//! directory and the load path in `.ast`, `.cmi` and `.cmt` files, as strings in OCaml marshalled
+//! values. Entries are stored with the project root replaced by `ROOT_PLACEHOLDER`, and the
+//! placeholder is replaced by the root of the project they're restored into.
+//!
+//! A marshalled string is prefixed with its length and the header of the value records the total
+//! size, so a path can't be replaced byte by byte. Marshalled values are walked instead, and
+//! written again with the replaced strings and an updated header. Bytes outside of marshalled
+//! values (the dependency list and source path at the start of an `.ast`, JavaScript, source
+//! maps) are plain text.
+
+/// Stands in for the project root in stored entries and in keys.
+pub const ROOT_PLACEHOLDER: &str = "/rescript-project-root";
+
+const MAGIC_SMALL: [u8; 4] = [0x84, 0x95, 0xA6, 0xBE];
+const MAGIC_BIG: [u8; 4] = [0x84, 0x95, 0xA6, 0xBF];
+const MAGIC_COMPRESSED: [u8; 4] = [0x84, 0x95, 0xA6, 0xBD];
+
+const SMALL_HEADER_LEN: usize = 20;
+const BIG_HEADER_LEN: usize = 32;
+
+/// `contents` with every path starting with `from` moved to `to`. `None` when a marshalled value
+/// in it can't be read, e.g. one that is compressed or contains a custom block of an unknown
+/// kind.
+pub fn relocate(contents: &[u8], from: &str, to: &str) -> Option<Vec<u8>> {
+    let mut relocated = Vec::with_capacity(contents.len());
+    let mut plain_start = 0;
+    let mut pos = 0;
+    while pos + MAGIC_SMALL.len() <= contents.len() {
+        let magic = &contents[pos..pos + MAGIC_SMALL.len()];
+        if magic == MAGIC_COMPRESSED {
+            return None;
+        }
+        if magic == MAGIC_SMALL || magic == MAGIC_BIG {
+            replace_paths(&contents[plain_start..pos], from, to, &mut relocated);
+            pos += relocate_value(&contents[pos..], from, to, &mut relocated)?;
+            plain_start = pos;
+        } else {
+            pos += 1;
+        }
+    }
+    replace_paths(&contents[plain_start..], from, to, &mut relocated);
+    Some(relocated)
+}
+
+/// Copies `text` to `out`, with `from` replaced by `to` wherever it's a whole path or the
+/// beginning of one.
+pub fn replace_paths(text: &[u8], from: &str, to: &str, out: &mut Vec<u8>) {
+    let from = from.as_bytes();
+    let mut pos = 0;
+    while pos < text.len() {
+        if text[pos..].starts_with(from)
+            && text
+                .get(pos + from.len())
+                .is_none_or(|next| matches!(next, b'/' | b'\\' | b'"' | b'\'' | b'\n' | b':' | 0))
+        {
+            out.extend_from_slice(to.as_bytes());
+            pos += from.len();
+        } else {
+            out.push(text[pos]);
+            pos += 1;
+        }
+    }
+}
+
+/// Relocates the marshalled value at the start of `data` into `out`, and returns how many bytes
+/// of `data` it took up.
+fn relocate_value(data: &[u8], from: &str, to: &str, out: &mut Vec<u8>) -> Option<usize> {
+    let big = data[..MAGIC_BIG.len()] == MAGIC_BIG;
+    let header_len = if big { BIG_HEADER_LEN } else { SMALL_HEADER_LEN };
+    let header = data.get(..header_len)?;
+    let (data_len, num_objects, size_32, size_64) = if big {
+        (
+            read_u64(header, 8)?,
+            read_u64(header, 16)?,
+            0,
+            read_u64(header, 24)?,
+        )
+    } else {
+        (
+            read_u32(header, 4)?,
+            read_u32(header, 8)?,
+            read_u32(header, 12)?,
+            read_u32(header, 16)?,
+        )
+    };
+    let body = data.get(header_len..header_len.checked_add(usize::try_from(data_len).ok()?)?)?;
+
+    let mut relocated = Relocated {
+        body: Vec::with_capacity(body.len()),
+        words_32: 0,
+        words_64: 0,
+    };
+    let read = relocated.walk(body, from, to)?;
+    if read != body.len() {
+        return None;
+    }
+
+    let new_size_32 = size_32.checked_add_signed(relocated.words_32)?;
+    let new_size_64 = size_64.checked_add_signed(relocated.words_64)?;
+    let new_data_len = relocated.body.len() as u64;
+    if big {
+        out.extend_from_slice(&header[..8]);
+        out.extend_from_slice(&new_data_len.to_be_bytes());
+        out.extend_from_slice(&num_objects.to_be_bytes());
+        out.extend_from_slice(&new_size_64.to_be_bytes());
+    } else {
+        out.extend_from_slice(&MAGIC_SMALL);
+        for field in [new_data_len, num_objects, new_size_32, new_size_64] {
+            out.extend_from_slice(&u32::try_from(field).ok()?.to_be_bytes());
+        }
+    }
+    out.extend_from_slice(&relocated.body);
+    Some(header_len + body.len())
+}
+
+/// The body of a marshalled value being relocated, and by how many heap words the replaced
+/// strings grew (or shrank) on 32-bit and 64-bit platforms.
+struct Relocated {
+    body: Vec<u8>,
+    words_32: i64,
+    words_64: i64,
+}
+
+// The codes of the marshalling format, see `runtime/caml/intext.h` in OCaml.
+const PREFIX_SMALL_BLOCK: u8 = 0x80;
+const PREFIX_SMALL_INT: u8 = 0x40;
+const PREFIX_SMALL_STRING: u8 = 0x20;
+const CODE_INT8: u8 = 0x00;
+const CODE_INT16: u8 = 0x01;
+const CODE_INT32: u8 = 0x02;
+const CODE_INT64: u8 = 0x03;
+const CODE_SHARED8: u8 = 0x04;
+const CODE_SHARED16: u8 = 0x05;
+const CODE_SHARED32: u8 = 0x06;
+const CODE_DOUBLE_ARRAY32_LITTLE: u8 = 0x07;
+const CODE_BLOCK32: u8 = 0x08;
+const CODE_STRING8: u8 = 0x09;
+const CODE_STRING32: u8 = 0x0A;
+const CODE_DOUBLE_BIG: u8 = 0x0B;
+const CODE_DOUBLE_LITTLE: u8 = 0x0C;
+const CODE_DOUBLE_ARRAY8_BIG: u8 = 0x0D;
+const CODE_DOUBLE_ARRAY8_LITTLE: u8 = 0x0E;
+const CODE_DOUBLE_ARRAY32_BIG: u8 = 0x0F;
+const CODE_CUSTOM: u8 = 0x12;
+const CODE_BLOCK64: u8 = 0x13;
+const CODE_SHARED64: u8 = 0x14;
+const CODE_STRING64: u8 = 0x15;
+const CODE_DOUBLE_ARRAY64_BIG: u8 = 0x16;
+const CODE_DOUBLE_ARRAY64_LITTLE: u8 = 0x17;
+const CODE_CUSTOM_LEN: u8 = 0x18;
+const CODE_CUSTOM_FIXED: u8 = 0x19;
+
+impl Relocated {
+    /// Copies the values in `body` and returns how many bytes they took up. Objects are only
+    /// counted by the reader, shared references stay valid when strings change length.
+    fn walk(&mut self, body: &[u8], from: &str, to: &str) -> Option<usize> {
+        let mut pos = 0;
+        // Fields of the blocks read so far that still have to be read.
+        let mut pending: u64 = 1;
+        while pending > 0 {
+            pending -= 1;
+            let code = *body.get(pos)?;
+            let copied = match code {
+                PREFIX_SMALL_BLOCK..=u8::MAX => {
+                    pending += u64::from((code >> 4) & 0x7);
+                    1
+                }
+                PREFIX_SMALL_INT..PREFIX_SMALL_BLOCK => 1,
+                PREFIX_SMALL_STRING..PREFIX_SMALL_INT => {
+                    pos = self.string(body, pos + 1, usize::from(code & 0x1F), from, to)?;
+                    continue;
+                }
+                CODE_INT8 | CODE_SHARED8 => 2,
+                CODE_INT16 | CODE_SHARED16 => 3,
+                CODE_INT32 | CODE_SHARED32 => 5,
+                CODE_INT64 | CODE_SHARED64 | CODE_DOUBLE_BIG | CODE_DOUBLE_LITTLE => 9,
+                CODE_BLOCK32 => {
+                    pending += read_u32(body, pos + 1)? >> 10;
+                    5
+                }
+                CODE_BLOCK64 => {
+                    pending += read_u64(body, pos + 1)? >> 10;
+                    9
+                }
+                CODE_STRING8 => {
+                    let len = usize::from(*body.get(pos + 1)?);
+                    pos = self.string(body, pos + 2, len, from, to)?;
+                    continue;
+                }
+                CODE_STRING32 => {
+                    let len = usize::try_from(read_u32(body, pos + 1)?).ok()?;
+                    pos = self.string(body, pos + 5, len, from, to)?;
+                    continue;
+                }
+                CODE_STRING64 => {
+                    let len = usize::try_from(read_u64(body, pos + 1)?).ok()?;
+                    pos = self.string(body, pos + 9, len, from, to)?;
+                    continue;
+                }
+                CODE_DOUBLE_ARRAY8_BIG | CODE_DOUBLE_ARRAY8_LITTLE => {
+                    2 + 8 * usize::from(*body.get(pos + 1)?)
+                }
+                CODE_DOUBLE_ARRAY32_BIG | CODE_DOUBLE_ARRAY32_LITTLE => {
+                    5 + 8 * usize::try_from(read_u32(body, pos + 1)?).ok()?
+                }
+                CODE_DOUBLE_ARRAY64_BIG | CODE_DOUBLE_ARRAY64_LITTLE => {
+                    9 + 8 * usize::try_from(read_u64(body, pos + 1)?).ok()?
+                }
+                CODE_CUSTOM | CODE_CUSTOM_LEN | CODE_CUSTOM_FIXED => custom_len(body, pos)?,
+                // Code pointers only appear in marshalled closures, which bsc doesn't write.
+                _ => return None,
+            };
+            self.body.extend_from_slice(body.get(pos..pos + copied)?);
+            pos += copied;
+        }
+        Some(pos)
+    }
+
+    /// Copies the string of `len` bytes at `start`, relocated, and returns where it ends.
+    fn string(&mut self, body: &[u8], start: usize, len: usize, from: &str, to: &str) -> Option<usize> {
+        let contents = body.get(start..start.checked_add(len)?)?;
+        let mut relocated = Vec::with_capacity(contents.len());
+        replace_paths(contents, from, to, &mut relocated);
+        self.words_32 += string_words(relocated.len(), 4) - string_words(len, 4);
+        self.words_64 += string_words(relocated.len(), 8) - string_words(len, 8);
+
+        let new_len = relocated.len();
+        if new_len < 0x20 {
+            self.body.push(PREFIX_SMALL_STRING + new_len as u8);
+        } else if new_len < 0x100 {
+            self.body.push(CODE_STRING8);
+            self.body.push(new_len as u8);
+        } else if let Ok(new_len) = u32::try_from(new_len) {
+            self.body.push(CODE_STRING32);
+            self.body.extend_from_slice(&new_len.to_be_bytes());
+        } else {
+            self.body.push(CODE_STRING64);
+            self.body.extend_from_slice(&(new_len as u64).to_be_bytes());
+        }
+        self.body.extend_from_slice(&relocated);
+        Some(start + len)
+    }
+}
+
+/// The heap words of a string of `len` bytes with words of `word_size` bytes, header included.
+fn string_words(len: usize, word_size: usize) -> i64 {
+    (1 + (len + word_size) / word_size) as i64
+}
+
+/// The length of the custom block (`int32`, `int64` or `nativeint`) at `pos`, code included.
+/// The marshalled data of other custom blocks has no known length.
+fn custom_len(body: &[u8], pos: usize) -> Option<usize> {
+    let code = body[pos];
+    let name_start = pos + 1;
+    let name_len = body.get(name_start..)?.iter().position(|byte| *byte == 0)?;
+    let name = &body[name_start..name_start + name_len];
+    let mut data_start = name_start + name_len + 1;
+    if code == CODE_CUSTOM_LEN {
+        // The sizes of the block on 32-bit and 64-bit platforms.
+        data_start += 4 + 8;
+    }
+    let data_len = match name {
+        b"_i" => 4,
+        b"_j" => 8,
+        b"_n" => match body.get(data_start)? {
+            1 => 1 + 4,
+            2 => 1 + 8,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(data_start + data_len - pos)
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u64> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u64::from(u32::from_be_bytes(bytes.try_into().ok()?)))
+}
+
+fn read_u64(data: &[u8], pos: usize) -> Option<u64> {
+    let bytes = data.get(pos..pos + 8)?;
+    Some(u64::from_be_bytes(bytes.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `Marshal.to_string ("/work/app/src/App.res", [1; 2], ("/work/app", 3l)) []`: 47 bytes of
+    // data, 7 objects, 27 words on 32-bit and 23 words on 64-bit platforms.
+    const MARSHALLED: &[u8] = &[
+        0x84, 0x95, 0xA6, 0xBE, 0x00, 0x00, 0x00, 0x2F, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x1B, 0x00,
+        0x00, 0x00, 0x17, 0xB0, 0x35, b'/', b'w', b'o', b'r', b'k', b'/', b'a', b'p', b'p', b'/', b's', b'r',
+        b'c', b'/', b'A', b'p', b'p', b'.', b'r', b'e', b's', 0xA0, 0x41, 0xA0, 0x42, 0x40, 0xA0, 0x29, b'/',
+        b'w', b'o', b'r', b'k', b'/', b'a', b'p', b'p', 0x19, b'_', b'i', 0x00, 0x00, 0x00, 0x00, 0x03,
+    ];
+
+    /// Reads the small header and the first string of `MARSHALLED`-shaped data.
+    fn header_and_first_string(data: &[u8]) -> ([u64; 4], String) {
+        let header = [4, 8, 12, 16].map(|pos| read_u32(data, pos).unwrap());
+        let code = data[SMALL_HEADER_LEN + 1];
+        let (start, len) = match code {
+            PREFIX_SMALL_STRING..PREFIX_SMALL_INT => (SMALL_HEADER_LEN + 2, usize::from(code & 0x1F)),
+            CODE_STRING8 => (SMALL_HEADER_LEN + 3, usize::from(data[SMALL_HEADER_LEN + 2])),
+            _ => panic!("unexpected code {code:#x}"),
+        };
+        (
+            header,
+            String::from_utf8(data[start..start + len].to_vec()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn paths_in_marshalled_strings_are_moved_and_the_header_is_updated() {
+        let relocated = relocate(MARSHALLED, "/work/app", ROOT_PLACEHOLDER).unwrap();
+        let (header, first) = header_and_first_string(&relocated);
+
+        assert_eq!(first, format!("{ROOT_PLACEHOLDER}/src/App.res"));
+        // The first string grew from 21 to 34 bytes, so it's now written with `CODE_STRING8`,
+        // and the second one from 9 to 22 bytes.
+        assert_eq!(header[0], 47 + 14 + 13);
+        assert_eq!(header[1], 7);
+        // 32-bit: 6 to 9 and 3 to 6 words; 64-bit: 3 to 5 and 2 to 3 words.
+        assert_eq!(header[2], 27 + 3 + 3);
+        assert_eq!(header[3], 23 + 2 + 1);
+
+        assert_eq!(
+            relocate(&relocated, ROOT_PLACEHOLDER, "/work/app").unwrap(),
+            MARSHALLED
+        );
+    }
+
+    #[test]
+    fn plain_text_around_marshalled_values_is_moved_too() {
+        let mut ast = b"\nList\n/work/app/src/App.res\nCaml1999M".to_vec();
+        ast.extend_from_slice(MARSHALLED);
+
+        let relocated = relocate(&ast, "/work/app", "/ci/build").unwrap();
+
+        assert!(relocated.starts_with(b"\nList\n/ci/build/src/App.res\nCaml1999M"));
+        let (_, first) =
+            header_and_first_string(&relocated[b"\nList\n/ci/build/src/App.res\nCaml1999M".len()..]);
+        assert_eq!(first, "/ci/build/src/App.res");
+    }
+
+    #[test]
+    fn only_whole_path_components_are_replaced() {
+        let mut out = Vec::new();
+        replace_paths(
+            b"/work/app2/src /work/app/src \"/work/app\"",
+            "/work/app",
+            "/x",
+            &mut out,
+        );
+        assert_eq!(out, b"/work/app2/src /x/src \"/x\"");
+    }
+
+    #[test]
+    fn unreadable_values_are_not_relocated() {
+        let truncated = &MARSHALLED[..MARSHALLED.len() - 3];
+        assert_eq!(relocate(truncated, "/work/app", ROOT_PLACEHOLDER), None);
+
+        let mut compressed = MARSHALLED.to_vec();
+        compressed[3] = 0xBD;
+        assert_eq!(relocate(&compressed, "/work/app", ROOT_PLACEHOLDER), None);
+    }
+}
@@ -0,0 +1,319 @@
+//! A remote artifact cache on an HTTP(S) key-value server, so CI jobs and developers can share
+//! compiler output. An entry is fetched with `GET <url>/<key>` (where a 404 is a miss) and stored
+//! with `PUT <url>/<key>`, its files packed into a single body.
+//!
+//! Configured with the `cache.remote` section of the root `rescript.json`, or with the
+//! environment variables below, which take precedence. In `read-only` mode (the default) entries
+//! are only fetched, so it's safe for developers to point at the cache CI fills in `read-write`
+//! mode.
+
+use super::{Backend, EntryFiles};
+use crate::config::{Config, RemoteCacheMode};
+use anyhow::{Result, anyhow};
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+pub const REMOTE_CACHE_URL_ENV: &str = "RESCRIPT_REMOTE_CACHE_URL";
+pub const REMOTE_CACHE_MODE_ENV: &str = "RESCRIPT_REMOTE_CACHE_MODE";
+/// Sent as a bearer token. Only read from the environment, so it doesn't end up in version control.
+/// Only sent over https://.
+pub const REMOTE_CACHE_TOKEN_ENV: &str = "RESCRIPT_REMOTE_CACHE_TOKEN";
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Marks the start of a packed entry. Bump the version when the layout changes.
+const PACK_MAGIC: &[u8] = b"RESCRIPT-CACHE-1\n";
+
+pub struct RemoteCache {
+    url: String,
+    mode: RemoteCacheMode,
+    token: Option<String>,
+    client: Client,
+    // Set after the first request that didn't reach the server, so an unreachable cache costs a
+    // single timeout per build instead of one per module.
+    unavailable: AtomicBool,
+}
+
+impl std::fmt::Debug for RemoteCache {
+    // Leaves out the token.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteCache")
+            .field("url", &self.url)
+            .field("mode", &self.mode)
+            .finish()
+    }
+}
+
+fn parse_mode(mode: &str) -> Result<RemoteCacheMode> {
+    match mode {
+        "read-only" => Ok(RemoteCacheMode::ReadOnly),
+        "read-write" => Ok(RemoteCacheMode::ReadWrite),
+        other => Err(anyhow!(
+            "{REMOTE_CACHE_MODE_ENV} must be \"read-only\" or \"read-write\", not \"{other}\""
+        )),
+    }
+}
+
+fn non_empty_env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+impl RemoteCache {
+    pub fn new(url: &str, mode: RemoteCacheMode, token: Option<String>) -> Result<Self> {
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(anyhow!("the URL must start with https:// or http://, got {url}"));
+        }
+        if token.is_some() && !url.starts_with("https://") {
+            return Err(anyhow!(
+                "{REMOTE_CACHE_TOKEN_ENV} is only sent over https://, but the URL is {url}"
+            ));
+        }
+        let client = Client::builder().timeout(TIMEOUT).build()?;
+        Ok(Self {
+            url: url.trim_end_matches('/').to_string(),
+            mode,
+            token,
+            client,
+            unavailable: AtomicBool::new(false),
+        })
+    }
+
+    /// `None` when no remote cache is configured.
+    pub fn from_config(root_config: &Config) -> Result<Option<Self>> {
+        let remote_config = root_config.cache.as_ref().and_then(|cache| cache.remote.as_ref());
+        let Some(url) = non_empty_env(REMOTE_CACHE_URL_ENV).or_else(|| remote_config.map(|c| c.url.clone()))
+        else {
+            return Ok(None);
+        };
+        let mode = match non_empty_env(REMOTE_CACHE_MODE_ENV) {
+            Some(mode) => parse_mode(&mode)?,
+            None => remote_config.and_then(|c| c.mode).unwrap_or_default(),
+        };
+        Self::new(&url, mode, non_empty_env(REMOTE_CACHE_TOKEN_ENV)).map(Some)
+    }
+
+    fn send(&self, request: RequestBuilder) -> Option<Response> {
+        if self.unavailable.load(Ordering::Relaxed) {
+            return None;
+        }
+        let request = match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        match request.send() {
+            Ok(response) => Some(response),
+            Err(err) => {
+                if !self.unavailable.swap(true, Ordering::Relaxed) {
+                    log::warn!(
+                        "The remote cache at {} can't be reached, not using it for the rest of the build: {}",
+                        self.url,
+                        err
+                    );
+                }
+                None
+            }
+        }
+    }
+
+    fn entry_url(&self, key: &blake3::Hash) -> String {
+        format!("{}/{}", self.url, key.to_hex())
+    }
+}
+
+impl Backend for RemoteCache {
+    fn get(&self, key: &blake3::Hash) -> Option<EntryFiles> {
+        let response = self.send(self.client.get(self.entry_url(key)))?;
+        match response.status() {
+            StatusCode::OK => unpack(&response.bytes().ok()?),
+            StatusCode::NOT_FOUND => None,
+            status => {
+                log::debug!("GET {} returned {}", self.entry_url(key), status);
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: &blake3::Hash, files: &EntryFiles) {
+        if self.mode == RemoteCacheMode::ReadOnly {
+            return;
+        }
+        let request = self.client.put(self.entry_url(key)).body(pack(files));
+        if let Some(response) = self.send(request)
+            && !response.status().is_success()
+        {
+            log::debug!("PUT {} returned {}", self.entry_url(key), response.status());
+        }
+    }
+}
+
+/// `PACK_MAGIC`, then for every file the length of its name (u32) and its name, and the length
+/// of its content (u64) and its content, all little-endian.
+fn pack(files: &EntryFiles) -> Vec<u8> {
+    let mut packed = PACK_MAGIC.to_vec();
+    for (name, contents) in files {
+        packed.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        packed.extend_from_slice(name.as_bytes());
+        packed.extend_from_slice(&(contents.len() as u64).to_le_bytes());
+        packed.extend_from_slice(contents);
+    }
+    packed
+}
+
+fn unpack(packed: &[u8]) -> Option<EntryFiles> {
+    fn take<'a>(packed: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        let (taken, rest) = packed.split_at_checked(len)?;
+        *packed = rest;
+        Some(taken)
+    }
+
+    let mut packed = packed.strip_prefix(PACK_MAGIC)?;
+    let mut files = Vec::new();
+    while !packed.is_empty() {
+        let name_len = u32::from_le_bytes(take(&mut packed, 4)?.try_into().ok()?);
+        let name = String::from_utf8(take(&mut packed, name_len as usize)?.to_vec()).ok()?;
+        let contents_len = u64::from_le_bytes(take(&mut packed, 8)?.try_into().ok()?);
+        let contents = take(&mut packed, usize::try_from(contents_len).ok()?)?;
+        files.push((name, contents.to_vec()));
+    }
+    Some(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    type Stored = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// A stand-in for a cache server: keeps the bodies of PUT requests in memory and serves them
+    /// to GET requests. Returns its URL and what it stored.
+    fn start_server() -> (String, Stored) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/cache", listener.local_addr().unwrap());
+        let stored = Arc::new(Mutex::new(HashMap::new()));
+        let server_stored = stored.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut parts = request_line.split_whitespace();
+                let (method, path) = (
+                    parts.next().unwrap().to_string(),
+                    parts.next().unwrap().to_string(),
+                );
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let mut stored = server_stored.lock().unwrap();
+                let (status, body) = match (method.as_str(), stored.get(&path)) {
+                    ("PUT", _) => {
+                        stored.insert(path, body);
+                        ("200 OK", Vec::new())
+                    }
+                    ("GET", Some(body)) => ("200 OK", body.clone()),
+                    _ => ("404 Not Found", Vec::new()),
+                };
+                let head = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                stream.write_all(head.as_bytes()).unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+        (url, stored)
+    }
+
+    fn files() -> EntryFiles {
+        vec![
+            ("cmj".to_string(), b"cmj".to_vec()),
+            ("js.0".to_string(), b"console.log(1)".to_vec()),
+        ]
+    }
+
+    #[test]
+    fn packs_and_unpacks_entries() {
+        assert_eq!(unpack(&pack(&files())), Some(files()));
+        assert_eq!(unpack(&pack(&files())[..20]), None);
+        assert_eq!(unpack(b"not a cache entry"), None);
+    }
+
+    #[test]
+    fn stores_and_fetches_entries_in_read_write_mode() {
+        let (url, stored) = start_server();
+        let cache = RemoteCache::new(&url, RemoteCacheMode::ReadWrite, None).unwrap();
+        let key = blake3::hash(b"key");
+
+        assert_eq!(cache.get(&key), None);
+        cache.put(&key, &files());
+        assert_eq!(cache.get(&key), Some(files()));
+        assert!(
+            stored
+                .lock()
+                .unwrap()
+                .contains_key(&format!("/cache/{}", key.to_hex()))
+        );
+    }
+
+    #[test]
+    fn read_only_mode_never_stores() {
+        let (url, stored) = start_server();
+        let cache = RemoteCache::new(&url, RemoteCacheMode::ReadOnly, None).unwrap();
+        cache.put(&blake3::hash(b"key"), &files());
+        assert!(stored.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn stops_using_an_unreachable_server() {
+        // Bind and drop a listener to get a port nothing listens on.
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let cache = RemoteCache::new(
+            &format!("http://127.0.0.1:{port}"),
+            RemoteCacheMode::ReadWrite,
+            None,
+        )
+        .unwrap();
+        assert_eq!(cache.get(&blake3::hash(b"key")), None);
+        assert!(cache.unavailable.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn accepts_https_and_http_urls() {
+        assert!(RemoteCache::new("https://cache.example.com", RemoteCacheMode::ReadOnly, None).is_ok());
+        assert!(RemoteCache::new("http://cache.internal", RemoteCacheMode::ReadOnly, None).is_ok());
+        assert!(RemoteCache::new("ftp://cache.example.com", RemoteCacheMode::ReadOnly, None).is_err());
+    }
+
+    #[test]
+    fn the_token_is_only_sent_over_https() {
+        let token = || Some("secret".to_string());
+        assert!(RemoteCache::new("https://cache.example.com", RemoteCacheMode::ReadOnly, token()).is_ok());
+        let err = RemoteCache::new("http://cache.internal", RemoteCacheMode::ReadOnly, token()).unwrap_err();
+        assert!(err.to_string().contains("only sent over https://"));
+        assert!(!err.to_string().contains("secret"));
+    }
+}
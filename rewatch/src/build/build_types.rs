@@ -1,9 +1,24 @@
+use crate::build::artifact_cache::ArtifactCache;
+use crate::build::compile_universe::CompileReason;
+use crate::build::diagnostics::Diagnostic;
+use crate::build::events::{Event, EventSink};
 use crate::build::packages::{Namespace, Package};
+use crate::build::source_hashes::SourceHash;
+use crate::build::timings::Timings;
+use crate::cli::{MessageFormat, TimingsFormat};
 use crate::config::{Config, SourceMapCommand};
 use crate::project_context::ProjectContext;
+use crate::watcher::settings::WatchSettings;
 use ahash::{AHashMap, AHashSet};
 use blake3::Hash;
-use std::{fmt::Display, ops::Deref, path::PathBuf, time::SystemTime};
+use std::{
+    fmt::Display,
+    ops::Deref,
+    path::PathBuf,
+    sync::Arc,
+    sync::atomic::{AtomicBool, Ordering},
+    time::SystemTime,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ParseState {
@@ -111,6 +126,16 @@ pub struct BuildState {
     pub compiler_info: CompilerInfo,
     pub deps_initialized: bool,
     pub source_map_command: SourceMapCommand,
+    /// Why modules were marked `compile_dirty` since the last compile pass. Only the first
+    /// reason per module is kept.
+    pub compile_dirty_reasons: AHashMap<String, CompileReason>,
+    /// Wall time of the bsc invocations of the current build.
+    pub timings: Timings,
+    /// Content hash of every source, keyed by absolute path, as it was when its current AST was
+    /// parsed. See `source_hashes`.
+    pub source_hashes: AHashMap<PathBuf, SourceHash>,
+    /// The artifact cache configured for the project, if any.
+    pub artifact_cache: Option<ArtifactCache>,
 }
 
 /// Extended build state that includes command-line specific overrides.
@@ -130,6 +155,47 @@ pub struct BuildCommandState {
     // Command-line --features override. `None` means all features are active; `Some(list)`
     // restricts the root package to those features (and whatever they transitively imply).
     pub features: Option<Vec<String>>,
+    // Command-line --message-format. Decides whether diagnostics are printed as bsc's
+    // human-readable output or as one JSON object per line.
+    pub message_format: MessageFormat,
+    // Command-line --explain. Prints why each module in the compile universe is compiled.
+    pub explain: bool,
+    // Command-line --keep-going. After a compile error, keeps compiling every module that
+    // doesn't depend on a failed one instead of stopping the pass.
+    pub keep_going: bool,
+    // Command-line --check of `rescript build`. Modules are only type-checked: bsc writes their
+    // `.cmi` and `.cmt` but no `.cmj` or JS.
+    pub check_only: bool,
+    // Command-line --target of `rescript build`, resolved to module names. Only these modules and
+    // their dependencies are compiled. `None` compiles the whole project.
+    pub targets: Option<AHashSet<String>>,
+    // Command-line --since of `rescript build`: the modules changed since that git revision. Only
+    // they, their dependents and what those depend on are compiled.
+    pub since_changed: Option<AHashSet<String>>,
+    // Command-line --timings. Also writes the build timings in this format.
+    pub timings_format: Option<TimingsFormat>,
+    // Command-line --events/--events-socket of `rescript watch`. Receives structured events as
+    // the build progresses.
+    pub events: Option<Arc<EventSink>>,
+    // Set by the watcher when sources change while a build runs. The compile dispatcher stops
+    // starting new modules once it's set, so the watcher can restart with the new changes.
+    pub cancel: Option<Arc<AtomicBool>>,
+    // Debounce and ignored files of `rescript watch`, read from the config by the watcher.
+    pub watch: WatchSettings,
+    // Toggled with `w` in watch mode. Warnings still count for the outcome, they're just not
+    // printed.
+    pub hide_warnings: bool,
+    // Sources the current build parses because they changed or are new, for `BuildSummary`.
+    pub changed_sources: Vec<PathBuf>,
+    // Modules bsc compiled in the current build, for `BuildSummary`. Dependents skipped because
+    // the `.cmi` of their dependencies didn't change are left out.
+    pub compiled_modules: AHashSet<String>,
+    // Diagnostics parsed from bsc output during the current build cycle. Reset at the start of
+    // every incremental build.
+    pub diagnostics: Vec<Diagnostic>,
+    // Every module compiled (or scheduled to be) in the last compile pass, with the reason it
+    // was included.
+    pub compile_universe: AHashMap<String, CompileReason>,
 }
 
 #[derive(Debug, Clone)]
@@ -154,6 +220,8 @@ impl BuildState {
         compiler: CompilerInfo,
         source_map_command: SourceMapCommand,
     ) -> Self {
+        let artifact_cache =
+            ArtifactCache::from_config(project_context.get_root_config(), project_context.get_root_path());
         Self {
             project_context,
             module_names: AHashSet::new(),
@@ -163,6 +231,10 @@ impl BuildState {
             compiler_info: compiler,
             deps_initialized: false,
             source_map_command,
+            compile_dirty_reasons: AHashMap::new(),
+            timings: Timings::default(),
+            source_hashes: AHashMap::new(),
+            artifact_cache,
         }
     }
 
@@ -174,6 +246,12 @@ impl BuildState {
     pub fn get_root_config(&self) -> &Config {
         self.project_context.get_root_config()
     }
+
+    pub fn record_compile_dirty_reason(&mut self, module_name: &str, reason: CompileReason) {
+        self.compile_dirty_reasons
+            .entry(module_name.to_owned())
+            .or_insert(reason);
+    }
 }
 
 impl BuildCommandState {
@@ -191,6 +269,21 @@ impl BuildCommandState {
             build_state: BuildState::new(project_context, packages, compiler, source_map_command),
             warn_error_override,
             features,
+            message_format: MessageFormat::Human,
+            explain: false,
+            keep_going: false,
+            check_only: false,
+            targets: None,
+            since_changed: None,
+            timings_format: None,
+            events: None,
+            cancel: None,
+            watch: WatchSettings::default(),
+            hide_warnings: false,
+            changed_sources: Vec::new(),
+            compiled_modules: AHashSet::new(),
+            diagnostics: Vec::new(),
+            compile_universe: AHashMap::new(),
         }
     }
 
@@ -198,6 +291,18 @@ impl BuildCommandState {
         self.warn_error_override.clone()
     }
 
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::SeqCst))
+    }
+
+    pub fn emit(&self, event: &Event) {
+        if let Some(events) = &self.events {
+            events.emit(event);
+        }
+    }
+
     pub fn get_features(&self) -> Option<Vec<String>> {
         self.features.clone()
     }
@@ -243,6 +348,10 @@ pub struct CompileAssetsState {
     pub ast_modules: AHashMap<PathBuf, AstModule>,
     pub cmi_modules: AHashMap<String, SystemTime>,
     pub cmt_modules: AHashMap<String, SystemTime>,
+    /// Modules with a `.cmj`. Only their existence counts: bsc doesn't rewrite an unchanged one.
+    pub cmj_modules: AHashSet<String>,
     pub ast_rescript_file_locations: AHashSet<PathBuf>,
     pub rescript_file_locations: AHashSet<PathBuf>,
+    /// The source hashes written by the previous build.
+    pub source_hashes: AHashMap<PathBuf, SourceHash>,
 }
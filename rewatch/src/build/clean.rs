@@ -1,5 +1,6 @@
 use super::build_types::*;
 use super::packages;
+use super::source_hashes;
 use crate::build;
 use crate::build::packages::Package;
 use crate::config::{Config, SourceMapCommand};
@@ -45,7 +46,7 @@ fn remove_mjs_file(source_file: &Path, suffix: &str) {
     let _ = std::fs::remove_file(PathBuf::from(map_file));
 }
 
-fn remove_compile_asset(package: &packages::Package, source_file: &Path, extension: &str) {
+pub fn remove_compile_asset(package: &packages::Package, source_file: &Path, extension: &str) {
     let _ = std::fs::remove_file(helpers::get_compiler_asset(
         package,
         &package.namespace,
@@ -105,20 +106,65 @@ fn clean_source_files(build_state: &BuildState, root_config: &Config) {
         .for_each(|(rescript_file_location, suffix)| remove_mjs_file(rescript_file_location, suffix));
 }
 
-// TODO: change to scan_previous_build => CompileAssetsState
-// and then do cleanup on that state (for instance remove all .mjs files that are not in the state)
+/// The artifacts of a previous build that belong to `source_file`, which no longer exists:
+/// compiler assets, the js output (and its source map) and the parsed ASTs.
+fn get_stale_artifacts(package: &packages::Package, source_file: &Path, suffix: &str) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for extension in &["cmj", "cmi", "cmt", "cmti"] {
+        paths.push(helpers::get_compiler_asset(
+            package,
+            &package.namespace,
+            source_file,
+            extension,
+        ));
+        paths.push(PathBuf::from(helpers::get_bs_compiler_asset(
+            package,
+            &package.namespace,
+            source_file,
+            extension,
+        )));
+    }
+
+    // suffix includes the ., so we need to remove it
+    let js_file = source_file.with_extension(&suffix[1..]);
+    let mut map_file = js_file.clone().into_os_string();
+    map_file.push(".map");
+    paths.push(js_file);
+    paths.push(PathBuf::from(map_file));
+
+    for extension in &["iast", "ast"] {
+        paths.push(helpers::get_compiler_asset(
+            package,
+            &packages::Namespace::NoNamespace,
+            source_file,
+            extension,
+        ));
+    }
+    paths
+}
 
 #[instrument(name = "clean.cleanup_previous_build", skip_all)]
 pub fn cleanup_previous_build(
     build_state: &mut BuildCommandState,
     compile_assets_state: CompileAssetsState,
 ) -> (usize, usize) {
-    // delete the .mjs file which appear in our previous compile assets
-    // but does not exists anymore
-    // delete the compiler assets for which modules we can't find a rescript file
-    // location of rescript file is in the AST
-    // delete the .mjs file for which we DO have a compiler asset, but don't have a
-    // rescript file anymore (path is found in the .ast file)
+    let (stale_artifacts, diff_len, total_len) = scan_previous_build(build_state, compile_assets_state);
+    stale_artifacts.par_iter().for_each(|path| {
+        let _ = std::fs::remove_file(path);
+    });
+    (diff_len, total_len)
+}
+
+/// Compare the artifacts of the previous build with the current sources and update the build
+/// state accordingly (what needs parsing, last compile times, deleted modules), without touching
+/// the disk. Returns the artifacts of deleted source files (candidates, they don't all have to
+/// exist) together with the number of deleted sources and the number of sources with an AST.
+pub fn scan_previous_build(
+    build_state: &mut BuildCommandState,
+    compile_assets_state: CompileAssetsState,
+) -> (Vec<PathBuf>, usize, usize) {
+    // the compiler assets and .mjs files of modules for which we can't find a rescript file
+    // anymore are stale, the location of the rescript file is in the AST
     let diff = compile_assets_state
         .ast_rescript_file_locations
         .difference(&compile_assets_state.rescript_file_locations)
@@ -126,38 +172,29 @@ pub fn cleanup_previous_build(
 
     let diff_len = diff.len();
 
-    let deleted_interfaces = diff
-        .par_iter()
-        .map(|res_file_location| {
-            let AstModule {
-                module_name,
-                package_name,
-                ast_file_path,
-                suffix,
-                ..
-            } = compile_assets_state
-                .ast_modules
-                .get(*res_file_location)
-                .expect("Could not find module name for ast file");
-
-            let package = build_state
-                .packages
-                .get(package_name)
-                .expect("Could not find package");
-            remove_compile_assets(package, res_file_location);
-            remove_mjs_file(res_file_location, suffix);
-            remove_iast(package, res_file_location);
-            remove_ast(package, res_file_location);
-            match helpers::get_extension(ast_file_path).as_str() {
-                "iast" => Some(module_name.to_owned()),
-                "ast" => None,
-                _ => None,
-            }
-        })
-        .collect::<Vec<Option<String>>>()
-        .iter()
-        .filter_map(|module_name| module_name.to_owned())
-        .collect::<AHashSet<String>>();
+    let mut stale_artifacts = Vec::new();
+    let mut deleted_interfaces = AHashSet::new();
+    for res_file_location in diff {
+        let AstModule {
+            module_name,
+            package_name,
+            ast_file_path,
+            suffix,
+            ..
+        } = compile_assets_state
+            .ast_modules
+            .get(res_file_location)
+            .expect("Could not find module name for ast file");
+
+        let package = build_state
+            .packages
+            .get(package_name)
+            .expect("Could not find package");
+        stale_artifacts.extend(get_stale_artifacts(package, res_file_location, suffix));
+        if helpers::get_extension(ast_file_path) == "iast" {
+            deleted_interfaces.insert(module_name.to_owned());
+        }
+    }
 
     compile_assets_state
         .ast_rescript_file_locations
@@ -181,9 +218,12 @@ pub fn cleanup_previous_build(
             // if there is a new AST but it has not been compiled yet, we mark the module as compile dirty
             // we do this by checking if the cmt file is newer than the AST file. We always compile the
             // interface AND implementation. For some reason the CMI file is not always rewritten if it
-            // doesn't have any changes, that's why we just look at the CMT file.
+            // doesn't have any changes, that's why we just look at the CMT file. `build --check`
+            // writes the CMT without the CMJ and removes the outdated CMJ, so a missing CMJ means
+            // the JS still has to be written.
             if let Some(cmt_last_modified) = cmt_last_modified
                 && cmt_last_modified > ast_last_modified
+                && compile_assets_state.cmj_modules.contains(module_name)
                 && !deleted_interfaces.contains(module_name)
             {
                 module.compile_dirty = false;
@@ -199,13 +239,24 @@ pub fn cleanup_previous_build(
                             .expect("Could not find interface for module");
 
                         let source_last_modified = interface.last_modified;
-                        if ast_last_modified > &source_last_modified {
+                        if ast_last_modified > &source_last_modified
+                            || source_hashes::is_unchanged(
+                                &compile_assets_state.source_hashes,
+                                res_file_location,
+                                Some(ast_last_modified),
+                            )
+                        {
                             interface.parse_dirty = false;
                         }
                     } else {
                         let implementation = &mut source_file.implementation;
                         let source_last_modified = implementation.last_modified;
-                        if ast_last_modified > &source_last_modified
+                        if (ast_last_modified > &source_last_modified
+                            || source_hashes::is_unchanged(
+                                &compile_assets_state.source_hashes,
+                                res_file_location,
+                                Some(ast_last_modified),
+                            ))
                             && !deleted_interfaces.contains(module_name)
                         {
                             implementation.parse_dirty = false;
@@ -266,10 +317,18 @@ pub fn cleanup_previous_build(
 
     build_state.deleted_modules = deleted_module_names;
 
-    (diff_len, compile_assets_state.ast_rescript_file_locations.len())
+    let total_len = compile_assets_state.ast_rescript_file_locations.len();
+    // Hashes of sources that no longer exist are dropped.
+    build_state.source_hashes = compile_assets_state
+        .source_hashes
+        .into_iter()
+        .filter(|(path, _)| compile_assets_state.rescript_file_locations.contains(path))
+        .collect();
+
+    (stale_artifacts, diff_len, total_len)
 }
 
-fn has_parse_warnings(module: &Module) -> bool {
+pub fn has_parse_warnings(module: &Module) -> bool {
     matches!(
         &module.source_type,
         SourceType::SourceFile(SourceFile {
@@ -288,7 +347,7 @@ fn has_parse_warnings(module: &Module) -> bool {
     )
 }
 
-fn has_compile_warnings(module: &Module) -> bool {
+pub fn has_compile_warnings(module: &Module) -> bool {
     matches!(
         &module.source_type,
         SourceType::SourceFile(SourceFile {
@@ -377,7 +436,7 @@ pub fn clean(path: &Path, show_progress: bool, plain_output: bool, prod: bool) -
 
     let timing_clean_mjs = Instant::now();
     let mut build_state = BuildState::new(project_context, packages, compiler_info, SourceMapCommand::Build);
-    packages::parse_packages(&mut build_state)?;
+    packages::parse_packages(&mut build_state, true)?;
     let root_config = build_state.get_root_config();
     let suffix_for_print = match root_config.package_specs {
         None => match &root_config.suffix {
@@ -2,9 +2,17 @@
 
 mod dependency_cycle;
 
+use super::affected;
+use super::artifact_cache::{Artifact, ArtifactCache, Step};
 use super::build_types::*;
+use super::clean;
+use super::compile_universe::CompileReason;
+use super::diagnostics::{self, Diagnostic, Severity};
+use super::events::Event;
 use super::logs;
 use super::packages;
+use super::targets;
+use super::timings::Phase;
 use crate::config;
 use crate::config::Config;
 use crate::helpers;
@@ -15,11 +23,11 @@ use anyhow::{Result, anyhow};
 use console::style;
 use log::{debug, info, trace, warn};
 use rayon::prelude::*;
-use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap};
 use std::path::Path;
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Command, ExitStatus, Output};
 use std::sync::OnceLock;
 use std::sync::mpsc;
 use std::time::SystemTime;
@@ -186,6 +194,35 @@ fn compute_critical_path_priorities(
     priorities
 }
 
+/// The longest chain of dependencies in `universe` according to `priorities`, from the module
+/// that has to compile first to the last module that (transitively) depends on it. Ties are
+/// broken by name.
+fn critical_path(
+    universe: &AHashSet<String>,
+    priorities: &AHashMap<String, i64>,
+    build_state: &BuildState,
+) -> Vec<String> {
+    let by_priority_then_name =
+        |a: &&String, b: &&String| priorities[*a].cmp(&priorities[*b]).then_with(|| b.cmp(a));
+    let mut path = Vec::new();
+    let mut current = universe
+        .iter()
+        .filter(|name| priorities[*name] > 0)
+        .max_by(by_priority_then_name);
+    while let Some(name) = current {
+        path.push(name.clone());
+        let next_priority = priorities[name] - 1;
+        current = build_state
+            .get_module(name)
+            .unwrap()
+            .dependents
+            .iter()
+            .filter(|dependent| universe.contains(*dependent) && priorities[*dependent] == next_priority)
+            .max_by(by_priority_then_name);
+    }
+    path
+}
+
 /// Run the short-circuit check or actual `bsc` invocation for a single module.
 /// Invoked from worker threads inside the dispatcher scope; only reads
 /// `BuildState`, never mutates it.
@@ -194,6 +231,7 @@ fn compile_one(
     module_name: &str,
     is_dirty: bool,
     warn_error_override: Option<String>,
+    check_only: bool,
 ) -> CompletionMsg {
     let module = build_state.get_module(module_name).unwrap();
     let package = build_state
@@ -260,19 +298,23 @@ fn compile_one(
                 compile_file(
                     package,
                     &helpers::get_ast_path(&iface.path),
+                    module_name,
                     module,
                     true,
                     build_state,
                     warn_error_override.clone(),
+                    check_only,
                 )
             });
             let result = compile_file(
                 package,
                 &helpers::get_ast_path(&source_file.implementation.path),
+                module_name,
                 module,
                 false,
                 build_state,
                 warn_error_override,
+                check_only,
             );
             let cmi_digest_after = helpers::compute_file_hash(Path::new(&cmi_path));
 
@@ -336,6 +378,7 @@ fn append_compile_warnings(
     build_state: &BuildState,
     mut warning_entries: Vec<CompileWarning>,
     compile_warnings: &mut String,
+    compile_diagnostics: &mut Vec<Diagnostic>,
 ) {
     warning_entries.sort_by(|a, b| a.module_name.cmp(&b.module_name));
     for CompileWarning {
@@ -347,17 +390,119 @@ fn append_compile_warnings(
         if let Some(package) = build_state.get_package(&package_name) {
             logs::append(package, &warning);
         }
+        compile_diagnostics.extend(diagnostics::parse(&warning, &package_name, Severity::Warning));
         compile_warnings.push_str(&warning);
     }
 }
 
+/// The modules of `remaining` that are `roots` or depend on one of them, transitively.
+fn held_back_modules<'a>(
+    roots: impl Iterator<Item = &'a String>,
+    remaining: &AHashSet<String>,
+    modules: &AHashMap<String, Module>,
+) -> AHashSet<String> {
+    let mut frontier = roots.cloned().collect::<Vec<String>>();
+    let mut held_back = frontier
+        .iter()
+        .filter(|module_name| remaining.contains(*module_name))
+        .cloned()
+        .collect::<AHashSet<String>>();
+    while let Some(module_name) = frontier.pop() {
+        let Some(module) = modules.get(&module_name) else {
+            continue;
+        };
+        for dependent in &module.dependents {
+            if remaining.contains(dependent) && held_back.insert(dependent.clone()) {
+                frontier.push(dependent.clone());
+            }
+        }
+    }
+    held_back
+}
+
+/// Expand the compile universe: every dirty module plus everything that transitively depends
+/// on it. Each module maps to the reason it is compiled; dependents remember which module pulled
+/// them in, so `rescript why` can explain it later.
+pub fn compute_compile_universe(build_state: &BuildState) -> AHashMap<String, CompileReason> {
+    let mut compile_reasons: AHashMap<String, CompileReason> = build_state
+        .modules
+        .iter()
+        .filter(|(_, module)| module.compile_dirty)
+        .map(|(module_name, _)| {
+            let reason = build_state
+                .compile_dirty_reasons
+                .get(module_name)
+                .cloned()
+                .unwrap_or(CompileReason::Dirty);
+            (module_name.clone(), reason)
+        })
+        .collect();
+    let mut frontier = compile_reasons.keys().cloned().collect::<AHashSet<String>>();
+    loop {
+        // Walk the frontier in a stable order so the recorded reasons don't change between runs.
+        let mut frontier_names = frontier.iter().collect::<Vec<&String>>();
+        frontier_names.sort();
+        let mut dependents: AHashSet<String> = AHashSet::new();
+        for module_name in frontier_names {
+            for dependent in build_state.get_module(module_name).unwrap().dependents.iter() {
+                if !compile_reasons.contains_key(dependent) && dependents.insert(dependent.clone()) {
+                    compile_reasons.insert(
+                        dependent.clone(),
+                        CompileReason::DirtyDependency {
+                            module: module_name.clone(),
+                        },
+                    );
+                }
+            }
+        }
+        if dependents.is_empty() {
+            break;
+        }
+        frontier = dependents;
+    }
+    compile_reasons
+}
+
+/// The order in which the dispatcher in `compile` would start the modules of `universe` when
+/// running them one at a time: ready modules with the longest critical path first, ties broken
+/// by name. Modules stuck in a dependency cycle are left out.
+pub fn scheduler_order(universe: &AHashSet<String>, build_state: &BuildState) -> Vec<String> {
+    let priorities = compute_critical_path_priorities(universe, build_state);
+    let mut pending_deps: AHashMap<&String, usize> = universe
+        .iter()
+        .map(|name| {
+            let module = build_state.get_module(name).unwrap();
+            (name, module.deps.iter().filter(|d| universe.contains(*d)).count())
+        })
+        .collect();
+    let mut ready_heap = pending_deps
+        .iter()
+        .filter(|(_, count)| **count == 0)
+        .map(|(name, _)| (priorities[*name], Reverse(*name)))
+        .collect::<BinaryHeap<_>>();
+
+    let mut order = Vec::with_capacity(universe.len());
+    while let Some((_, Reverse(name))) = ready_heap.pop() {
+        order.push(name.clone());
+        for dependent in &build_state.get_module(name).unwrap().dependents {
+            if let Some(count) = pending_deps.get_mut(dependent) {
+                *count -= 1;
+                if *count == 0 {
+                    ready_heap.push((priorities[dependent], Reverse(dependent)));
+                }
+            }
+        }
+    }
+    order
+}
+
 #[instrument(name = "build.compile", skip_all)]
 pub fn compile(
     build_state: &mut BuildCommandState,
     show_progress: bool,
     inc: impl Fn() + std::marker::Sync,
     set_length: impl Fn(u64),
-) -> anyhow::Result<(String, String, usize)> {
+) -> anyhow::Result<(String, String, usize, bool)> {
     let dirty_modules = build_state
         .modules
         .iter()
@@ -370,29 +515,41 @@ pub fn compile(
         })
         .collect::<AHashSet<String>>();
 
-    // Expand the compile universe: every dirty module plus everything that
-    // transitively depends on it.
-    let mut compile_universe = dirty_modules.clone();
-    let mut frontier = compile_universe.clone();
-    loop {
-        let mut dependents: AHashSet<String> = AHashSet::new();
-        for module_name in frontier.iter() {
-            dependents.extend(build_state.get_module(module_name).unwrap().dependents.clone());
-        }
-        frontier = dependents
-            .difference(&compile_universe)
-            .cloned()
-            .collect::<AHashSet<String>>();
-        if frontier.is_empty() {
-            break;
+    let mut compile_reasons = compute_compile_universe(&build_state.build_state);
+    // The dependents of the changed modules are only known now that the imports are up to date.
+    let targets = match &build_state.since_changed {
+        Some(changed) => Some(affected::dependents_closure(
+            changed,
+            &build_state.build_state.modules,
+        )),
+        None => build_state.targets.clone(),
+    };
+    if let Some(targets) = &targets {
+        let deferred =
+            targets::restrict_universe(&mut compile_reasons, targets, &build_state.build_state.modules);
+        // Dependents of changed modules aren't dirty on their own, mark them so they're compiled
+        // by the next build.
+        for module_name in deferred {
+            if let Some(module) = build_state.build_state.modules.get_mut(&module_name) {
+                module.compile_dirty = true;
+            }
         }
-        compile_universe.extend(frontier.iter().cloned());
     }
+    let compile_universe = compile_reasons.keys().cloned().collect::<AHashSet<String>>();
+    build_state.compile_universe = compile_reasons;
+    // Modules that stay dirty after this pass (e.g. because of an error) get no stale reason in
+    // the next one.
+    build_state.compile_dirty_reasons.clear();
 
     let compile_universe_count = compile_universe.len();
     set_length(compile_universe_count as u64);
 
     let priorities = compute_critical_path_priorities(&compile_universe, &build_state.build_state);
+    build_state.timings.set_critical_path(critical_path(
+        &compile_universe,
+        &priorities,
+        &build_state.build_state,
+    ));
 
     // Count of not-yet-completed in-universe dependencies for each module.
     // Only touched on the main thread.
@@ -424,6 +581,10 @@ pub fn compile(
     let mut dirty_set: AHashSet<String> = dirty_modules;
 
     let warn_error_override = build_state.get_warn_error_override();
+    // With --keep-going a failure only holds back the modules that depend on the failed one;
+    // otherwise nothing new is dispatched after the first error.
+    let keep_going = build_state.keep_going;
+    let check_only = build_state.check_only;
     let build_state_ref: &BuildState = &build_state.build_state;
     let compile_span = tracing::Span::current();
 
@@ -435,12 +596,12 @@ pub fn compile(
     let mut completed: AHashSet<String> = AHashSet::new();
     let mut results_buffer: Vec<CompletionMsg> = Vec::with_capacity(compile_universe_count);
     let mut has_errors = false;
-    let mut stalled = false;
+    let mut failed_modules: AHashSet<String> = AHashSet::new();
 
     rayon::in_place_scope(|scope| {
         let mut in_flight: usize = 0;
         loop {
-            while in_flight < capacity && !has_errors {
+            while in_flight < capacity && (keep_going || !has_errors) && !build_state.is_cancelled() {
                 let Some(work) = ready_heap.pop() else { break };
                 let module_name = work.module_name.clone();
                 let is_dirty = dirty_set.contains(&module_name);
@@ -451,7 +612,7 @@ pub fn compile(
                 in_flight += 1;
                 scope.spawn(move |_| {
                     let _guard = parent_span.enter();
-                    let msg = compile_one(build_state_ref, &module_name, is_dirty, warn_override);
+                    let msg = compile_one(build_state_ref, &module_name, is_dirty, warn_override, check_only);
                     if show_progress {
                         inc_ref();
                     }
@@ -462,21 +623,29 @@ pub fn compile(
             }
 
             if in_flight == 0 {
-                if !ready_heap.is_empty() {
-                    // Errors suppressed new spawns; nothing left to drain.
-                    break;
-                }
-                if completed.len() < compile_universe_count && !has_errors {
-                    stalled = true;
-                }
+                // Either everything is compiled, errors suppressed new spawns, or the modules left
+                // wait on each other; the latter is told apart below.
                 break;
             }
 
             let Ok(msg) = rx.recv() else { break };
             in_flight -= 1;
 
-            if msg.result.is_err() || msg.interface_result.as_ref().is_some_and(|r| r.is_err()) {
+            let failed = msg.result.is_err() || msg.interface_result.as_ref().is_some_and(|r| r.is_err());
+            if failed {
                 has_errors = true;
+                failed_modules.insert(msg.module_name.clone());
+            }
+
+            let finished_module = build_state.get_module(&msg.module_name).unwrap();
+            if (msg.is_compiled || failed)
+                && let SourceType::SourceFile(_) = finished_module.source_type
+            {
+                build_state.emit(&Event::ModuleCompiled {
+                    module: &msg.module_name,
+                    package: &finished_module.package_name,
+                    success: !failed,
+                });
             }
 
             let is_clean = msg.is_clean;
@@ -489,6 +658,12 @@ pub fn compile(
             // key modules use to refer to the namespace entry.
             let dependents = build_state.get_module(&finished_name).unwrap().dependents.clone();
 
+            // Dependents of a failed module never become ready, which blocks the whole
+            // subtree below it.
+            if failed && keep_going {
+                continue;
+            }
+
             for dep in &dependents {
                 if !compile_universe.contains(dep) {
                     continue;
@@ -511,6 +686,28 @@ pub fn compile(
     // workers don't keep the channel open past the scope.
     drop(tx);
 
+    // Modules left over are either held back by a failed module (or by a ready one that wasn't
+    // dispatched after an error), or wait on each other in a cycle.
+    let remaining = compile_universe
+        .difference(&completed)
+        .cloned()
+        .collect::<AHashSet<String>>();
+    let held_back = held_back_modules(
+        failed_modules
+            .iter()
+            .chain(ready_heap.iter().map(|work| &work.module_name)),
+        &remaining,
+        &build_state.build_state.modules,
+    );
+    let stalled_modules = if build_state.is_cancelled() {
+        AHashSet::new()
+    } else {
+        remaining
+            .difference(&held_back)
+            .cloned()
+            .collect::<AHashSet<String>>()
+    };
+
     trace!(
         "Compiled {} out of {} in the universe",
         completed.len(),
@@ -519,6 +716,7 @@ pub fn compile(
 
     let mut compile_errors = String::new();
     let mut compile_warnings = String::new();
+    let mut compile_diagnostics: Vec<Diagnostic> = Vec::new();
     let mut warning_entries = Vec::new();
     let mut num_compiled_modules = 0;
 
@@ -656,6 +854,7 @@ pub fn compile(
         }
         if let Some(error) = compile_error {
             logs::append(package, &error);
+            compile_diagnostics.extend(diagnostics::parse(&error, &package_name, Severity::Error));
             compile_errors.push_str(&error);
         }
         if let Some(warning) = interface_warning {
@@ -667,13 +866,56 @@ pub fn compile(
         }
         if let Some(error) = interface_error {
             logs::append(package, &error);
+            compile_diagnostics.extend(diagnostics::parse(&error, &package_name, Severity::Error));
             compile_errors.push_str(&error);
         }
     }
 
-    if stalled {
+    if keep_going && has_errors {
+        let mut blocked_modules = held_back
+            .iter()
+            .map(|module_name| {
+                (
+                    helpers::format_namespaced_module_name(module_name),
+                    build_state.get_module(module_name).unwrap().package_name.clone(),
+                )
+            })
+            .collect::<Vec<(String, String)>>();
+        blocked_modules.sort();
+        if !blocked_modules.is_empty() {
+            compile_errors.push_str(&format!(
+                "\nSkipped {} modules that depend on a module with errors: {}\n",
+                blocked_modules.len(),
+                blocked_modules
+                    .iter()
+                    .map(|(module_name, _)| module_name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ));
+            let mut blocked_by_package = BTreeMap::<&str, Vec<&str>>::new();
+            for (module_name, package_name) in &blocked_modules {
+                blocked_by_package
+                    .entry(package_name)
+                    .or_default()
+                    .push(module_name);
+            }
+            for (package_name, module_names) in blocked_by_package {
+                compile_diagnostics.push(Diagnostic::without_location(
+                    package_name,
+                    Severity::Error,
+                    &format!(
+                        "Skipped {} modules that depend on a module with errors: {}",
+                        module_names.len(),
+                        module_names.join(", ")
+                    ),
+                ));
+            }
+        }
+    }
+
+    if !stalled_modules.is_empty() {
         let cycle = dependency_cycle::find(
-            &compile_universe
+            &stalled_modules
                 .iter()
                 .map(|s| (s, build_state.get_module(s).unwrap()))
                 .collect::<Vec<(&String, &Module)>>(),
@@ -697,6 +939,16 @@ pub fn compile(
             }
         }
 
+        if let Some(module) = cycle
+            .first()
+            .and_then(|module_name| build_state.get_module(module_name))
+        {
+            compile_diagnostics.push(Diagnostic::without_location(
+                &module.package_name,
+                Severity::Error,
+                &logs::escape_colours(&message),
+            ));
+        }
         compile_errors.push_str(&message);
     }
 
@@ -706,9 +958,19 @@ pub fn compile(
         &build_state.build_state,
         &recompiled_modules,
     ));
-    append_compile_warnings(&build_state.build_state, warning_entries, &mut compile_warnings);
+    append_compile_warnings(
+        &build_state.build_state,
+        warning_entries,
+        &mut compile_warnings,
+        &mut compile_diagnostics,
+    );
+    build_state.diagnostics.extend(compile_diagnostics);
+    build_state.compiled_modules = recompiled_modules;
 
-    Ok((compile_errors, compile_warnings, num_compiled_modules))
+    // Modules that weren't started keep their dirty flags, the next build picks them up.
+    let cancelled =
+        compile_errors.is_empty() && completed.len() < compile_universe_count && build_state.is_cancelled();
+    Ok((compile_errors, compile_warnings, num_compiled_modules, cancelled))
 }
 
 static RUNTIME_PATH_MEMO: OnceLock<PathBuf> = OnceLock::new();
@@ -763,6 +1025,8 @@ pub fn compiler_args(
     // Pre-expanded source directories for the current package (used by gentype).
     // Pass an empty slice when unavailable (e.g. the compiler-args CLI command).
     current_package_dirs: &[PathBuf],
+    // `build --check`: stop after type checking, without JS output or gentype.
+    check_only: bool,
 ) -> Result<Vec<String>> {
     let bsc_flags = config::flatten_flags(&config.compiler_flags);
     let dependency_paths = get_dependency_paths(config, project_context, packages, is_type_dev);
@@ -812,7 +1076,11 @@ pub fn compiler_args(
     } else {
         Vec::new()
     };
-    let gentype_arg = config.get_gentype_args(current_package_dirs, Some(bsb_project_root), &dep_paths);
+    let gentype_arg = if check_only {
+        vec![]
+    } else {
+        config.get_gentype_args(current_package_dirs, Some(bsb_project_root), &dep_paths)
+    };
     let experimental_args = root_config.get_experimental_features_args();
     let warning_args = config.get_warning_args(is_local_dep, warn_error_override);
 
@@ -833,6 +1101,9 @@ pub fn compiler_args(
     let implementation_args = if is_interface {
         debug!("Compiling interface file: {}", &module_name);
         vec![]
+    } else if check_only {
+        debug!("Type checking file: {}", &module_name);
+        vec!["-bs-cmi-only".to_string()]
     } else {
         debug!("Compiling file: {}", &module_name);
         let specs = root_config.get_package_specs();
@@ -986,10 +1257,12 @@ fn get_dependency_paths(
 fn compile_file(
     package: &packages::Package,
     ast_path: &Path,
+    module_name: &str,
     module: &Module,
     is_interface: bool,
     build_state: &BuildState,
     warn_error_override: Option<String>,
+    check_only: bool,
 ) -> Result<Option<String>> {
     let BuildState {
         packages,
@@ -1029,18 +1302,81 @@ fn compile_file(
         warn_error_override,
         build_state.source_map_command,
         current_package_dirs,
+        check_only,
     )?;
 
-    let to_mjs = Command::new(&compiler_info.bsc_path)
-        .current_dir(
-            build_path_abs
-                .canonicalize()
-                .map(StrippedVerbatimPath::to_stripped_verbatim_path)
-                .ok()
-                .unwrap(),
+    // gentype writes `.gen.tsx` files next to the JS, which the cache doesn't know about. A
+    // type-check doesn't produce all the artifacts, and restoring them would write JS.
+    let cache = build_state
+        .artifact_cache
+        .as_ref()
+        .filter(|_| package.config.gentype_config.is_none() && !check_only);
+    let cache_artifacts = compile_artifacts(
+        package,
+        implementation_file_path,
+        &basename,
+        is_interface,
+        has_interface,
+        root_config,
+    );
+    let cache_key = cache.and_then(|cache| {
+        let (step, source) = if is_interface {
+            (Step::CompileInterface, &module.get_interface().as_ref()?.path)
+        } else {
+            (Step::CompileImplementation, implementation_file_path)
+        };
+        let mut dependency_cmis = dependency_cmi_hashes(module, build_state, cache);
+        if has_interface && !is_interface {
+            // The implementation is checked against the `.cmi` of its interface.
+            let cmi =
+                helpers::get_compiler_asset(package, &package.namespace, implementation_file_path, "cmi");
+            dependency_cmis.push((module_name.to_string(), cache.file_hash(&cmi)));
+        }
+        cache.key(
+            build_state,
+            step,
+            &[package.path.join(source), build_path_abs.join(ast_path)],
+            &to_mjs_args,
+            &dependency_cmis,
         )
-        .args(to_mjs_args)
-        .output();
+    });
+
+    if check_only && !is_interface {
+        // The `.cmj` of the previous compile doesn't match the new `.cmi`. Without it, the next
+        // build knows the module still has to be compiled to JS (see `clean::scan_previous_build`).
+        clean::remove_compile_asset(package, implementation_file_path, "cmj");
+    }
+
+    let to_mjs = build_state
+        .timings
+        .record(module_name, &package.name, Phase::Compile, || {
+            if let (Some(cache), Some(key)) = (cache, &cache_key)
+                && cache.restore(key, &cache_artifacts)
+            {
+                return Ok(Output {
+                    status: ExitStatus::default(),
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                });
+            }
+            let output = Command::new(&compiler_info.bsc_path)
+                .current_dir(
+                    build_path_abs
+                        .canonicalize()
+                        .map(StrippedVerbatimPath::to_stripped_verbatim_path)
+                        .ok()
+                        .unwrap(),
+                )
+                .args(&to_mjs_args)
+                .output();
+            if let (Some(cache), Some(key), Ok(output)) = (cache, &cache_key, &output)
+                && output.status.success()
+                && !helpers::contains_ascii_characters(&compiler_output_to_string(&output.stderr))
+            {
+                cache.store(key, &cache_artifacts);
+            }
+            output
+        });
 
     match to_mjs {
         Ok(x) if !x.status.success() => {
@@ -1068,10 +1404,14 @@ fn compile_file(
                         .join(format!("{basename}.cmi")),
                     ocaml_build_path_abs.join(format!("{basename}.cmi")),
                 );
-                let _ = std::fs::copy(
-                    package.get_build_path().join(dir).join(format!("{basename}.cmj")),
-                    ocaml_build_path_abs.join(format!("{basename}.cmj")),
-                );
+                // `-bs-cmi-only` leaves the `.cmj` of the previous compile in lib/bs, copying it
+                // would bring back the outdated `.cmj` removed above.
+                if !check_only {
+                    let _ = std::fs::copy(
+                        package.get_build_path().join(dir).join(format!("{basename}.cmj")),
+                        ocaml_build_path_abs.join(format!("{basename}.cmj")),
+                    );
+                }
                 let _ = std::fs::copy(
                     package
                         .get_build_path()
@@ -1144,6 +1484,7 @@ fn compile_file(
             // copy js file
             root_config.get_package_specs().iter().for_each(|spec| {
                 if spec.in_source
+                    && !check_only
                     && let SourceType::SourceFile(SourceFile {
                         implementation: Implementation { path, .. },
                         ..
@@ -1181,6 +1522,7 @@ fn compile_file(
             // Execute js-post-build command if configured
             // Only run for implementation files (not interfaces)
             if !is_interface
+                && !check_only
                 && let Some(js_post_build) = &package.config.js_post_build
                 && let SourceType::SourceFile(SourceFile {
                     implementation: Implementation { path, .. },
@@ -1189,28 +1531,16 @@ fn compile_file(
             {
                 // Execute post-build command for each package spec (each output format)
                 for spec in root_config.get_package_specs() {
-                    // Determine the correct JS file path based on in-source setting:
-                    // - in-source: true  -> next to the source file (e.g., src/Foo.js)
-                    // - in-source: false -> in lib/<module>/ directory (e.g., lib/es6/src/Foo.js)
-                    let js_file = if spec.in_source {
-                        helpers::get_source_file_from_rescript_file(
-                            &Path::new(&package.path).join(path),
-                            &root_config.get_suffix(&spec),
-                        )
-                    } else {
-                        helpers::get_source_file_from_rescript_file(
-                            &Path::new(&package.path)
-                                .join("lib")
-                                .join(spec.get_out_of_source_dir())
-                                .join(path),
-                            &root_config.get_suffix(&spec),
-                        )
-                    };
+                    let js_file = js_output_path(package, root_config, &spec, Path::new(path));
 
                     if js_file.exists() {
                         // Fail the build if post-build command fails (matches bsb behavior with &&)
                         // Run in the package's directory (where rescript.json is defined)
-                        execute_post_build_command(&js_post_build.cmd, &js_file, &package.path)?;
+                        build_state
+                            .timings
+                            .record(module_name, &package.name, Phase::JsPostBuild, || {
+                                execute_post_build_command(&js_post_build.cmd, &js_file, &package.path)
+                            })?;
                     }
                 }
             }
@@ -1232,6 +1562,94 @@ fn compile_file(
     }
 }
 
+/// The JS file bsc writes for the implementation at `implementation_file_path`, relative to the
+/// package:
+/// - in-source: true  -> next to the source file (e.g., src/Foo.js)
+/// - in-source: false -> in lib/<module>/ directory (e.g., lib/es6/src/Foo.js)
+pub fn js_output_path(
+    package: &packages::Package,
+    root_config: &Config,
+    spec: &config::PackageSpec,
+    implementation_file_path: &Path,
+) -> PathBuf {
+    let js_file = if spec.in_source {
+        package.path.join(implementation_file_path)
+    } else {
+        package
+            .path
+            .join("lib")
+            .join(spec.get_out_of_source_dir())
+            .join(implementation_file_path)
+    };
+    helpers::get_source_file_from_rescript_file(&js_file, &root_config.get_suffix(spec))
+}
+
+/// The files bsc writes when compiling a module, as stored in the artifact cache.
+fn compile_artifacts(
+    package: &packages::Package,
+    implementation_file_path: &Path,
+    basename: &str,
+    is_interface: bool,
+    has_interface: bool,
+    root_config: &Config,
+) -> Vec<Artifact> {
+    let dir = package
+        .get_build_path()
+        .join(implementation_file_path.parent().unwrap());
+    let asset = |extension: &str| dir.join(format!("{basename}.{extension}"));
+    if is_interface {
+        return vec![
+            Artifact::required("cmi", asset("cmi")),
+            Artifact::required("cmti", asset("cmti")),
+        ];
+    }
+    let mut artifacts = vec![
+        Artifact::required("cmj", asset("cmj")),
+        Artifact::required("cmt", asset("cmt")),
+    ];
+    if !has_interface {
+        artifacts.push(Artifact::required("cmi", asset("cmi")));
+    }
+    for (index, spec) in root_config.get_package_specs().iter().enumerate() {
+        let js_file = js_output_path(package, root_config, spec, implementation_file_path);
+        let mut source_map = js_file.clone().into_os_string();
+        source_map.push(".map");
+        artifacts.push(Artifact::required(format!("js.{index}"), js_file));
+        artifacts.push(Artifact::optional(
+            format!("js.{index}.map"),
+            PathBuf::from(source_map),
+        ));
+    }
+    artifacts
+}
+
+/// The `.cmi` hash of every dependency of `module`, sorted by name, for the artifact cache key.
+fn dependency_cmi_hashes(
+    module: &Module,
+    build_state: &BuildState,
+    cache: &ArtifactCache,
+) -> Vec<(String, Option<blake3::Hash>)> {
+    let mut deps = module.deps.iter().collect::<Vec<&String>>();
+    deps.sort();
+    deps.into_iter()
+        .map(|dep| {
+            let cmi = build_state.get_module(dep).and_then(|dep_module| {
+                let package = build_state.get_package(&dep_module.package_name)?;
+                Some(match &dep_module.source_type {
+                    SourceType::SourceFile(source_file) => helpers::get_compiler_asset(
+                        package,
+                        &package.namespace,
+                        &source_file.implementation.path,
+                        "cmi",
+                    ),
+                    SourceType::MlMap(_) => package.get_mlmap_compile_path(),
+                })
+            });
+            (dep.clone(), cmi.and_then(|cmi| cache.file_hash(&cmi)))
+        })
+        .collect()
+}
+
 /// Filter a bsc stderr capture to the warning blocks the user needs to see
 /// even when they originate in an external dependency.
 ///
@@ -1262,11 +1680,21 @@ pub(super) fn retain_critical_external_warnings(stderr: &str) -> Option<String>
 }
 
 pub fn mark_modules_with_deleted_deps_dirty(build_state: &mut BuildState) {
-    build_state.modules.iter_mut().for_each(|(_, module)| {
-        if !module.deps.is_disjoint(&build_state.deleted_modules) {
+    let mut reasons: Vec<(String, CompileReason)> = Vec::new();
+    build_state.modules.iter_mut().for_each(|(module_name, module)| {
+        if let Some(deleted) = module.deps.intersection(&build_state.deleted_modules).min() {
             module.compile_dirty = true;
+            reasons.push((
+                module_name.to_owned(),
+                CompileReason::DeletedDependency {
+                    module: deleted.to_owned(),
+                },
+            ));
         }
     });
+    for (module_name, reason) in reasons {
+        build_state.record_compile_dirty_reason(&module_name, reason);
+    }
 }
 
 // this happens when a compile is not completed successfully in some way
@@ -1283,8 +1711,9 @@ pub fn mark_modules_with_deleted_deps_dirty(build_state: &mut BuildState) {
 //
 // We could clean up the build after errors. But I think we probably still need
 // to do this, because people can also force quit the watcher of
+#[allow(clippy::collapsible_match)]
 pub fn mark_modules_with_expired_deps_dirty(build_state: &mut BuildCommandState) {
-    let mut modules_with_expired_deps: AHashSet<String> = AHashSet::new();
+    let mut modules_with_expired_deps: AHashMap<String, CompileReason> = AHashMap::new();
     build_state
         .modules
         .iter()
@@ -1300,7 +1729,8 @@ pub fn mark_modules_with_expired_deps_dirty(build_state: &mut BuildCommandState)
                                 //     "🛑 {} is a dependent of {} but has no cmt/cmi",
                                 //     module_name, dependent
                                 // );
-                                modules_with_expired_deps.insert(module_name.to_string());
+                                modules_with_expired_deps
+                                    .insert(module_name.to_string(), CompileReason::MissingOutput);
                             }
                             (Some(_), Some(_)) => (),
                         }
@@ -1316,7 +1746,12 @@ pub fn mark_modules_with_expired_deps_dirty(build_state: &mut BuildCommandState)
                                     //     module_name, dependent, last_compiled_dependent, last_compiled
                                     // );
 
-                                    modules_with_expired_deps.insert(dependent.to_string());
+                                    modules_with_expired_deps.insert(
+                                        dependent.to_string(),
+                                        CompileReason::ExpiredDependency {
+                                            module: module_name.to_string(),
+                                        },
+                                    );
                                 } else {
                                     // println!(
                                     //     "🛑 {} is a dependent of {} ({:?} / {:?})",
@@ -1329,7 +1764,8 @@ pub fn mark_modules_with_expired_deps_dirty(build_state: &mut BuildCommandState)
                                 //     "🛑 {} is a dependent of {} (no last compiled time)",
                                 //     module_name, dependent
                                 // );
-                                modules_with_expired_deps.insert(dependent.to_string());
+                                modules_with_expired_deps
+                                    .insert(dependent.to_string(), CompileReason::MissingOutput);
                             }
                             _ => (),
                         }
@@ -1344,7 +1780,12 @@ pub fn mark_modules_with_expired_deps_dirty(build_state: &mut BuildCommandState)
                                 (dependent_module.last_compiled_cmt, module.last_compiled_cmt)
                                 && last_compiled_dependent < last_compiled
                             {
-                                modules_with_expired_deps.insert(dependent.to_string());
+                                modules_with_expired_deps.insert(
+                                    dependent.to_string(),
+                                    CompileReason::ExpiredDependency {
+                                        module: module_name.to_string(),
+                                    },
+                                );
                             }
                         }
                     }
@@ -1352,10 +1793,13 @@ pub fn mark_modules_with_expired_deps_dirty(build_state: &mut BuildCommandState)
             }
         });
     build_state.modules.iter_mut().for_each(|(module_name, module)| {
-        if modules_with_expired_deps.contains(module_name) {
+        if modules_with_expired_deps.contains_key(module_name) {
             module.compile_dirty = true;
         }
     });
+    for (module_name, reason) in modules_with_expired_deps {
+        build_state.record_compile_dirty_reason(&module_name, reason);
+    }
 }
 
 #[cfg(test)]
@@ -1398,6 +1842,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn check_only_drops_the_js_output() {
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        let project_context = test_project_context(temp_dir.path());
+        // Any runtime path will do, the arguments only mention it.
+        let _ = RUNTIME_PATH_MEMO.set(temp_dir.path().join("runtime"));
+        let args = |check_only| {
+            compiler_args(
+                &project_context.current_config,
+                Path::new("src/App.ast"),
+                Path::new("src/App.res"),
+                false,
+                false,
+                &project_context,
+                &None,
+                false,
+                true,
+                None,
+                config::SourceMapCommand::Build,
+                &[],
+                check_only,
+            )
+            .expect("compiler args should be computed")
+        };
+
+        let build_args = args(false);
+        assert!(build_args.contains(&"-bs-package-output".to_string()));
+        assert!(!build_args.contains(&"-bs-cmi-only".to_string()));
+
+        let check_args = args(true);
+        assert!(!check_args.contains(&"-bs-package-output".to_string()));
+        assert!(check_args.contains(&"-bs-cmi-only".to_string()));
+        assert_eq!(check_args.last(), Some(&"src/App.ast".to_string()));
+    }
+
     fn test_package(name: &str, path: PathBuf) -> Package {
         Package {
             name: name.to_string(),
@@ -1448,6 +1927,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn a_cycle_next_to_a_failed_module_is_not_held_back() {
+        // Cycle <-> Loop wait on each other, Failed <- Blocked <- AlsoBlocked wait on an error.
+        let module = |dependents: &[&str]| {
+            let mut module = test_module("test-package", None);
+            module.dependents = dependents.iter().map(|dependent| dependent.to_string()).collect();
+            module
+        };
+        let modules = AHashMap::from([
+            ("Cycle".to_string(), module(&["Loop"])),
+            ("Loop".to_string(), module(&["Cycle"])),
+            ("Failed".to_string(), module(&["Blocked"])),
+            ("Blocked".to_string(), module(&["AlsoBlocked"])),
+            ("AlsoBlocked".to_string(), module(&[])),
+        ]);
+        let remaining = AHashSet::from(["Cycle", "Loop", "Blocked", "AlsoBlocked"].map(String::from));
+        let failed = ["Failed".to_string()];
+
+        let mut held_back = held_back_modules(failed.iter(), &remaining, &modules)
+            .into_iter()
+            .collect::<Vec<String>>();
+        held_back.sort();
+
+        assert_eq!(held_back, vec!["AlsoBlocked", "Blocked"]);
+    }
+
     fn test_build_state(temp_dir: &TempDir, module_name: &str, module: Module) -> BuildState {
         let package = test_package("test-package", temp_dir.path().to_path_buf());
         fs::create_dir_all(package.get_build_path()).expect("build log directory should be created");
@@ -1472,6 +1977,45 @@ mod tests {
         build_state
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn check_only_does_not_restore_the_outdated_cmj() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        let _ = RUNTIME_PATH_MEMO.set(temp_dir.path().join("runtime"));
+        let build_state = test_build_state(&temp_dir, "ModuleA", test_module("test-package", None));
+        let package = build_state.get_package("test-package").unwrap();
+        let bsc = temp_dir.path().join("bsc");
+        fs::write(&bsc, "#!/bin/sh\nexit 0\n").expect("fake bsc should be written");
+        fs::set_permissions(&bsc, fs::Permissions::from_mode(0o755)).expect("fake bsc should be executable");
+        fs::create_dir_all(temp_dir.path().join("src")).expect("source directory should be created");
+        fs::write(temp_dir.path().join("src/ModuleA.res"), "let a = 1\n").expect("source should be written");
+        fs::create_dir_all(package.get_build_path().join("src")).expect("lib/bs should be created");
+        fs::create_dir_all(package.get_ocaml_build_path()).expect("lib/ocaml should be created");
+        let bs_cmj = package.get_build_path().join("src/ModuleA.cmj");
+        let ocaml_cmj = package.get_ocaml_build_path().join("ModuleA.cmj");
+        fs::write(&bs_cmj, "previous build").expect("lib/bs cmj should be written");
+        fs::write(&ocaml_cmj, "previous build").expect("lib/ocaml cmj should be written");
+
+        compile_file(
+            package,
+            Path::new("src/ModuleA.ast"),
+            "ModuleA",
+            build_state.get_module("ModuleA").unwrap(),
+            false,
+            &build_state,
+            None,
+            true,
+        )
+        .expect("the check should succeed");
+
+        assert!(
+            !ocaml_cmj.exists(),
+            "the outdated .cmj should not be copied back to lib/ocaml"
+        );
+    }
+
     #[test]
     fn retain_critical_external_warnings_returns_none_without_marker() {
         let input = "\n  Warning number 26\n  foo.res:1:1\n\n  unused variable x.\n";
@@ -1541,6 +2085,7 @@ mod tests {
             &build_state,
             collect_stored_warnings_for_modules_not_recompiled(&build_state, &recompiled_modules),
             &mut compile_warnings,
+            &mut Vec::new(),
         );
 
         assert_eq!(compile_warnings, "warning: alpha\nwarning: zed\n");
@@ -1571,6 +2116,7 @@ mod tests {
                 },
             ],
             &mut compile_warnings,
+            &mut Vec::new(),
         );
 
         assert_eq!(compile_warnings, "warning: stored\nwarning: fresh\n");
@@ -1595,4 +2141,64 @@ mod tests {
 
         assert!(compile_warnings.is_empty());
     }
+
+    fn add_dependency(build_state: &mut BuildState, module_name: &str, dependency: &str) {
+        let module = build_state.modules.get_mut(module_name).unwrap();
+        module.deps.insert(dependency.to_string());
+        let dependency = build_state.modules.get_mut(dependency).unwrap();
+        dependency.dependents.insert(module_name.to_string());
+    }
+
+    #[test]
+    fn scheduler_order_follows_critical_path_then_name() {
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        let mut build_state = test_build_state(&temp_dir, "Utils", test_module("test-package", None));
+        for module_name in ["Router", "App", "Logger", "Untouched"] {
+            build_state.insert_module(module_name, test_module("test-package", None));
+        }
+        add_dependency(&mut build_state, "Router", "Utils");
+        add_dependency(&mut build_state, "App", "Router");
+        add_dependency(&mut build_state, "Logger", "Utils");
+        build_state.modules.get_mut("Utils").unwrap().compile_dirty = true;
+        build_state
+            .compile_dirty_reasons
+            .insert("Utils".to_string(), CompileReason::ParseDirty);
+
+        let universe = compute_compile_universe(&build_state);
+        assert_eq!(universe["Utils"], CompileReason::ParseDirty);
+        assert_eq!(
+            universe["App"],
+            CompileReason::DirtyDependency {
+                module: "Router".to_string()
+            }
+        );
+        assert!(!universe.contains_key("Untouched"));
+
+        let universe = universe.into_keys().collect::<AHashSet<String>>();
+        assert_eq!(
+            scheduler_order(&universe, &build_state),
+            vec!["Utils", "Router", "App", "Logger"]
+        );
+    }
+
+    #[test]
+    fn critical_path_follows_the_longest_chain_of_dependents() {
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        let mut build_state = test_build_state(&temp_dir, "Utils", test_module("test-package", None));
+        for module_name in ["Router", "App", "Logger", "Api"] {
+            build_state.insert_module(module_name, test_module("test-package", None));
+        }
+        add_dependency(&mut build_state, "Router", "Utils");
+        add_dependency(&mut build_state, "App", "Router");
+        add_dependency(&mut build_state, "Logger", "Utils");
+        add_dependency(&mut build_state, "Api", "Logger");
+
+        let universe = build_state.module_names.clone();
+        let priorities = compute_critical_path_priorities(&universe, &build_state);
+        assert_eq!(
+            critical_path(&universe, &priorities, &build_state),
+            vec!["Utils", "Logger", "Api"]
+        );
+        assert!(critical_path(&AHashSet::new(), &AHashMap::new(), &build_state).is_empty());
+    }
 }
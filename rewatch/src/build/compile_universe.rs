@@ -0,0 +1,196 @@
+//! The compile universe of the last build, persisted so `rescript why <Module>` can explain
+//! afterwards why a module was recompiled. `--explain` prints the same reasons while building.
+
+use super::build_types::BuildCommandState;
+use super::packages;
+use crate::helpers;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Why a module is part of a build's compile universe.
+///
+/// Reasons for modules that are dirty on their own are recorded where the module gets marked
+/// `compile_dirty` (see `BuildState.compile_dirty_reasons`); the first recorded reason wins.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum CompileReason {
+    /// The module was dirty without a recorded reason, typically left over from an earlier
+    /// build that failed or was interrupted.
+    Dirty,
+    /// The source (or the namespace map) was parsed again because it changed or is new.
+    ParseDirty,
+    /// The package was cleaned because the compiler, runtime or config changed since the last
+    /// build (`verify_compiler_info`).
+    CompilerInfoMismatch,
+    /// The module's own `.cmi`/`.cmt` is missing.
+    MissingOutput,
+    /// The module was compiled before the `.cmi` of `module` was last written.
+    ExpiredDependency { module: String },
+    /// The module depends on `module`, which was deleted.
+    DeletedDependency { module: String },
+    /// The module depends on `module`, which is in the compile universe.
+    DirtyDependency { module: String },
+}
+
+impl CompileReason {
+    pub fn describe(&self) -> String {
+        match self {
+            CompileReason::Dirty => "left dirty by an earlier build".to_string(),
+            CompileReason::ParseDirty => "source changed".to_string(),
+            CompileReason::CompilerInfoMismatch => {
+                "compiler, runtime or package config changed since the last build".to_string()
+            }
+            CompileReason::MissingOutput => "compiled output is missing".to_string(),
+            CompileReason::ExpiredDependency { module } => format!(
+                "compiled before the .cmi of {} changed",
+                helpers::format_namespaced_module_name(module)
+            ),
+            CompileReason::DeletedDependency { module } => format!(
+                "depends on deleted module {}",
+                helpers::format_namespaced_module_name(module)
+            ),
+            CompileReason::DirtyDependency { module } => {
+                format!("depends on {}", helpers::format_namespaced_module_name(module))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompileUniverse {
+    pub modules: BTreeMap<String, CompileReason>,
+}
+
+impl CompileUniverse {
+    /// Follow the recorded reasons from `module` back to the module that was dirty in the
+    /// first place. The result starts with `module`.
+    pub fn chain(&self, module: &str) -> Option<Vec<(&str, &CompileReason)>> {
+        let mut chain = Vec::new();
+        let mut current = module;
+        while let Some((name, reason)) = self.modules.get_key_value(current) {
+            // A chain can never be longer than the universe; bail out on corrupt files.
+            if chain.len() > self.modules.len() {
+                return None;
+            }
+            chain.push((name.as_str(), reason));
+            match reason {
+                CompileReason::DirtyDependency { module } => current = module,
+                _ => return Some(chain),
+            }
+        }
+        None
+    }
+}
+
+pub fn get_path(project_root: &Path) -> PathBuf {
+    packages::get_build_path(project_root).join("compile-universe.json")
+}
+
+pub fn write(build_state: &BuildCommandState) {
+    let universe = CompileUniverse {
+        modules: build_state
+            .compile_universe
+            .iter()
+            .map(|(name, reason)| (name.to_owned(), reason.to_owned()))
+            .collect(),
+    };
+    let path = get_path(build_state.project_context.get_root_path());
+    let result = serde_json::to_string_pretty(&universe)
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| std::fs::write(&path, contents).map_err(anyhow::Error::from));
+    if let Err(err) = result {
+        log::error!("Failed to write {}: {}", path.display(), err);
+    }
+}
+
+pub fn read(project_root: &Path) -> Result<CompileUniverse> {
+    let path = get_path(project_root);
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Could not read {}. Run a build first.", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("Could not parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(module: &str) -> CompileReason {
+        CompileReason::DirtyDependency {
+            module: module.to_string(),
+        }
+    }
+
+    #[test]
+    fn chain_follows_reasons_back_to_the_dirty_module() {
+        let universe = CompileUniverse {
+            modules: BTreeMap::from([
+                ("App".to_string(), dependency("Router")),
+                ("Router".to_string(), dependency("Utils")),
+                ("Utils".to_string(), CompileReason::ParseDirty),
+            ]),
+        };
+
+        assert_eq!(
+            universe.chain("App").unwrap(),
+            vec![
+                ("App", &dependency("Router")),
+                ("Router", &dependency("Utils")),
+                ("Utils", &CompileReason::ParseDirty),
+            ]
+        );
+        assert!(universe.chain("Missing").is_none());
+    }
+
+    #[test]
+    fn chain_stops_on_a_reason_cycle() {
+        let universe = CompileUniverse {
+            modules: BTreeMap::from([
+                ("A".to_string(), dependency("B")),
+                ("B".to_string(), dependency("A")),
+            ]),
+        };
+
+        assert!(universe.chain("A").is_none());
+    }
+
+    #[test]
+    fn serializes_reasons_with_a_tag() {
+        let universe = CompileUniverse {
+            modules: BTreeMap::from([
+                ("A".to_string(), dependency("B")),
+                (
+                    "B".to_string(),
+                    CompileReason::ExpiredDependency {
+                        module: "C-Ns".to_string(),
+                    },
+                ),
+                ("C-Ns".to_string(), CompileReason::CompilerInfoMismatch),
+            ]),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&universe).unwrap(),
+            serde_json::json!({
+                "modules": {
+                    "A": {"reason": "dirty_dependency", "module": "B"},
+                    "B": {"reason": "expired_dependency", "module": "C-Ns"},
+                    "C-Ns": {"reason": "compiler_info_mismatch"},
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn describes_reasons_with_display_names() {
+        assert_eq!(
+            CompileReason::ExpiredDependency {
+                module: "Button-Ui".to_string()
+            }
+            .describe(),
+            "compiled before the .cmi of Ui.Button changed"
+        );
+        assert_eq!(dependency("Button-Ui").describe(), "depends on Ui.Button");
+    }
+}
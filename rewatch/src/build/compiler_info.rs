@@ -25,19 +25,22 @@ struct CompilerInfoFile {
 
 pub enum CompilerCheckResult {
     SameCompilerAsLastRun,
-    CleanedPackagesDueToCompiler,
+    /// Names of the packages that were cleaned.
+    CleanedPackagesDueToCompiler(Vec<String>),
 }
 
 fn get_rescript_config_hash(package: &packages::Package) -> Option<String> {
     helpers::compute_file_hash(&package.config.path).map(|hash| hash.to_hex().to_string())
 }
 
-pub fn verify_compiler_info(
-    packages: &AHashMap<String, packages::Package>,
+/// The packages whose `compiler-info.json` doesn't match the current compiler, runtime, config
+/// or source map args. Only reads; `verify_compiler_info` cleans them.
+pub fn find_mismatched_packages<'a>(
+    packages: &'a AHashMap<String, packages::Package>,
     compiler: &CompilerInfo,
     source_map_args: &[String],
-) -> CompilerCheckResult {
-    let mismatched_packages = packages
+) -> Vec<&'a packages::Package> {
+    packages
         .values()
         .filter(|package| {
             let info_path = package.get_compiler_info_path();
@@ -110,17 +113,29 @@ pub fn verify_compiler_info(
 
             mismatch
         })
-        .collect::<Vec<_>>();
+        .collect::<Vec<_>>()
+}
+
+pub fn verify_compiler_info(
+    packages: &AHashMap<String, packages::Package>,
+    compiler: &CompilerInfo,
+    source_map_args: &[String],
+) -> CompilerCheckResult {
+    let mismatched_packages = find_mismatched_packages(packages, compiler, source_map_args);
 
-    let cleaned_count = mismatched_packages.len();
     mismatched_packages.par_iter().for_each(|package| {
         // suppress progress printing during init to avoid breaking step output
         clean::clean_package(false, true, package);
     });
-    if cleaned_count == 0 {
+    if mismatched_packages.is_empty() {
         CompilerCheckResult::SameCompilerAsLastRun
     } else {
-        CompilerCheckResult::CleanedPackagesDueToCompiler
+        CompilerCheckResult::CleanedPackagesDueToCompiler(
+            mismatched_packages
+                .iter()
+                .map(|package| package.name.to_owned())
+                .collect(),
+        )
     }
 }
 
@@ -325,7 +340,7 @@ mod tests {
 
         assert!(matches!(
             result,
-            CompilerCheckResult::CleanedPackagesDueToCompiler
+            CompilerCheckResult::CleanedPackagesDueToCompiler(ref cleaned) if cleaned == &["dep".to_string()]
         ));
         assert!(!build_path.exists());
     }
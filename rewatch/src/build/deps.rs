@@ -11,6 +11,7 @@ fn get_dep_modules(
     valid_modules: &AHashSet<String>,
     package: &packages::Package,
     build_state: &BuildState,
+    allow_missing_ast: bool,
 ) -> AHashSet<String> {
     let mut deps = AHashSet::new();
     let ast_file = package.get_build_path().join(ast_file);
@@ -29,6 +30,7 @@ fn get_dep_modules(
                 }
             }
         }
+        _ if allow_missing_ast => return deps,
         _ => {
             panic!("Could not read file {}", ast_file.to_string_lossy());
         }
@@ -94,6 +96,16 @@ fn get_dep_modules(
 }
 
 pub fn get_deps(build_state: &mut BuildState, deleted_modules: &AHashSet<String>) {
+    collect_deps(build_state, deleted_modules, false)
+}
+
+/// Like `get_deps`, but reads whatever `.ast` files the previous build left behind without
+/// parsing first. Modules that were never parsed get no dependencies. Used by `build --dry-run`.
+pub fn get_deps_from_previous_build(build_state: &mut BuildState, deleted_modules: &AHashSet<String>) {
+    collect_deps(build_state, deleted_modules, true)
+}
+
+fn collect_deps(build_state: &mut BuildState, deleted_modules: &AHashSet<String>, allow_missing_ast: bool) {
     let all_mod = &build_state.module_names.union(deleted_modules).cloned().collect();
     build_state
         .modules
@@ -113,6 +125,7 @@ pub fn get_deps(build_state: &mut BuildState, deleted_modules: &AHashSet<String>
                         all_mod,
                         package,
                         build_state,
+                        allow_missing_ast,
                     );
 
                     if let Some(interface) = &source_file.interface {
@@ -125,6 +138,7 @@ pub fn get_deps(build_state: &mut BuildState, deleted_modules: &AHashSet<String>
                             all_mod,
                             package,
                             build_state,
+                            allow_missing_ast,
                         ))
                     }
                     match &package.namespace {
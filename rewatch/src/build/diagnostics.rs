@@ -0,0 +1,326 @@
+//! Structured diagnostics extracted from bsc output.
+//!
+//! bsc reports errors and warnings as human-readable blocks:
+//!
+//! ```text
+//!   Warning number 26
+//!   /abs/path/src/Foo.res:3:7-9
+//!
+//!   1 │ let a = 1
+//!   ...
+//!
+//!   unused variable x.
+//! ```
+//!
+//! The build parses these blocks once, right where the output is captured in
+//! `parse::generate_asts` and `compile::compile`, and keeps the records on the
+//! `BuildCommandState` so consumers (e.g. `--message-format json`) don't each have
+//! to scrape the colored text again.
+
+use super::logs;
+use regex::Regex;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Position {
+    /// 1-indexed line.
+    pub line: u32,
+    /// 1-indexed column.
+    pub column: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Range {
+    pub start: Position,
+    /// Inclusive end position, as printed by bsc.
+    pub end: Position,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub file: Option<PathBuf>,
+    pub range: Option<Range>,
+    pub severity: Severity,
+    pub warning_number: Option<u32>,
+    pub package: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// A diagnostic that isn't tied to a source location, such as a dependency cycle or a
+    /// failure to run bsc at all.
+    pub fn without_location(package: &str, severity: Severity, message: &str) -> Self {
+        Self {
+            file: None,
+            range: None,
+            severity,
+            warning_number: None,
+            package: package.to_string(),
+            message: message.trim().to_string(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Diagnostic serialization should not fail")
+    }
+}
+
+fn header_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^(?:(We've found a bug for you!|Syntax error!)|Warning number (\d+)( \(configured as error\))?)\s*$")
+            .expect("Could not create regex")
+    })
+}
+
+fn location_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^(.+?)(?::(\d+):(\d+)(?:-(\d+)(?::(\d+))?)?)?$").expect("Could not create regex")
+    })
+}
+
+// Code frame lines look like `  12 │ let x = 1`, elided lines use a `.` gutter and
+// re-indented frames use `┆` as the separator.
+fn code_frame_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^\s*(?:\d+|\.)?\s*[│┆]").expect("Could not create regex"))
+}
+
+fn parse_location(line: &str) -> (Option<PathBuf>, Option<Range>) {
+    let Some(captures) = location_regex().captures(line) else {
+        return (None, None);
+    };
+    let file = captures.get(1).map(|m| PathBuf::from(m.as_str()));
+    let number = |index: usize| captures.get(index).and_then(|m| m.as_str().parse::<u32>().ok());
+
+    let range = match (number(2), number(3), number(4), number(5)) {
+        // file:line:col-end_line:end_col
+        (Some(line), Some(column), Some(end_line), Some(end_column)) => Some(Range {
+            start: Position { line, column },
+            end: Position {
+                line: end_line,
+                column: end_column,
+            },
+        }),
+        // file:line:col-end_col
+        (Some(line), Some(column), Some(end_column), None) => Some(Range {
+            start: Position { line, column },
+            end: Position {
+                line,
+                column: end_column,
+            },
+        }),
+        // file:line:col
+        (Some(line), Some(column), None, None) => Some(Range {
+            start: Position { line, column },
+            end: Position { line, column },
+        }),
+        _ => None,
+    };
+
+    (file, range)
+}
+
+struct PendingDiagnostic {
+    severity: Severity,
+    warning_number: Option<u32>,
+    location: Option<(Option<PathBuf>, Option<Range>)>,
+    message_lines: Vec<String>,
+}
+
+impl PendingDiagnostic {
+    fn finish(self, package: &str) -> Diagnostic {
+        let (file, range) = self.location.unwrap_or((None, None));
+        Diagnostic {
+            file,
+            range,
+            severity: self.severity,
+            warning_number: self.warning_number,
+            package: package.to_string(),
+            message: self.message_lines.join("\n").trim().to_string(),
+        }
+    }
+}
+
+/// Parse captured bsc output (stderr, possibly with ANSI colors) into diagnostics.
+///
+/// Output that doesn't contain any recognizable error or warning header is still reported
+/// as a single location-less diagnostic with `fallback_severity`, so nothing bsc printed
+/// gets lost.
+pub fn parse(output: &str, package: &str, fallback_severity: Severity) -> Vec<Diagnostic> {
+    let output = logs::escape_colours(output).replace("\r\n", "\n");
+    let mut diagnostics = Vec::new();
+    let mut current: Option<PendingDiagnostic> = None;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+
+        if let Some(captures) = header_regex().captures(trimmed) {
+            if let Some(pending) = current.take() {
+                diagnostics.push(pending.finish(package));
+            }
+            let warning_number = captures.get(2).and_then(|m| m.as_str().parse::<u32>().ok());
+            let severity = if warning_number.is_some() && captures.get(3).is_none() {
+                Severity::Warning
+            } else {
+                Severity::Error
+            };
+            current = Some(PendingDiagnostic {
+                severity,
+                warning_number,
+                location: None,
+                message_lines: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(pending) = current.as_mut() else {
+            continue;
+        };
+
+        if pending.location.is_none() {
+            if !trimmed.is_empty() {
+                pending.location = Some(parse_location(trimmed));
+            }
+            continue;
+        }
+
+        if code_frame_regex().is_match(line) {
+            continue;
+        }
+        pending
+            .message_lines
+            .push(line.strip_prefix("  ").unwrap_or(line).trim_end().to_string());
+    }
+
+    if let Some(pending) = current.take() {
+        diagnostics.push(pending.finish(package));
+    }
+
+    if diagnostics.is_empty() && !output.trim().is_empty() {
+        diagnostics.push(Diagnostic::without_location(package, fallback_severity, &output));
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WARNING: &str = "\n  \u{1b}[1;33mWarning number 26\u{1b}[0m\n  \u{1b}[36m/repo/src/Foo.res\u{1b}[0m:\u{1b}[2m3:7-9\u{1b}[0m\n\n  1 \u{1b}[2m│\u{1b}[0m let a = 1\n  2 \u{1b}[2m│\u{1b}[0m \n  \u{1b}[1;33m3\u{1b}[0m \u{1b}[2m│\u{1b}[0m let \u{1b}[1;33mfoo\u{1b}[0m = 2\n\n  unused variable foo.\n\n";
+
+    const ERROR: &str = "\n  We've found a bug for you!\n  /repo/src/Bar.res:2:10-4:3\n\n  1 │ let x = {\n  . │ ...\n  4 │ }\n\n  This has type: int\n  But it's expected to have type: string\n";
+
+    #[test]
+    fn parses_colored_warning_block() {
+        let diagnostics = parse(WARNING, "my-package", Severity::Warning);
+
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.file, Some(PathBuf::from("/repo/src/Foo.res")));
+        assert_eq!(
+            diagnostic.range,
+            Some(Range {
+                start: Position { line: 3, column: 7 },
+                end: Position { line: 3, column: 9 },
+            })
+        );
+        assert_eq!(diagnostic.severity, Severity::Warning);
+        assert_eq!(diagnostic.warning_number, Some(26));
+        assert_eq!(diagnostic.package, "my-package");
+        assert_eq!(diagnostic.message, "unused variable foo.");
+    }
+
+    #[test]
+    fn parses_multi_line_error_and_skips_code_frame() {
+        let diagnostics = parse(ERROR, "my-package", Severity::Error);
+
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.warning_number, None);
+        assert_eq!(
+            diagnostic.range,
+            Some(Range {
+                start: Position { line: 2, column: 10 },
+                end: Position { line: 4, column: 3 },
+            })
+        );
+        assert_eq!(
+            diagnostic.message,
+            "This has type: int\nBut it's expected to have type: string"
+        );
+    }
+
+    #[test]
+    fn splits_consecutive_blocks() {
+        let output = format!("{WARNING}{ERROR}");
+        let diagnostics = parse(&output, "my-package", Severity::Error);
+
+        assert_eq!(
+            diagnostics.iter().map(|d| d.severity).collect::<Vec<_>>(),
+            vec![Severity::Warning, Severity::Error]
+        );
+    }
+
+    #[test]
+    fn warning_configured_as_error_is_an_error() {
+        let output =
+            "\n  Warning number 27 (configured as error) \n  /repo/src/Foo.res:1:5\n\n  unused variable x.\n";
+        let diagnostics = parse(output, "my-package", Severity::Warning);
+
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].warning_number, Some(27));
+        assert_eq!(
+            diagnostics[0].range,
+            Some(Range {
+                start: Position { line: 1, column: 5 },
+                end: Position { line: 1, column: 5 },
+            })
+        );
+    }
+
+    #[test]
+    fn unrecognized_output_becomes_a_single_diagnostic() {
+        let diagnostics = parse("bsc: command not found\n", "my-package", Severity::Error);
+
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::without_location(
+                "my-package",
+                Severity::Error,
+                "bsc: command not found"
+            )]
+        );
+    }
+
+    #[test]
+    fn serializes_to_a_single_json_line() {
+        let diagnostics = parse(WARNING, "my-package", Severity::Warning);
+        let json = diagnostics[0].to_json();
+
+        assert!(!json.contains('\n'));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!({
+                "file": "/repo/src/Foo.res",
+                "range": {"start": {"line": 3, "column": 7}, "end": {"line": 3, "column": 9}},
+                "severity": "warning",
+                "warning_number": 26,
+                "package": "my-package",
+                "message": "unused variable foo.",
+            })
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! `rescript build --dry-run`: work out what a build would do without doing it.
+//!
+//! This runs the same steps as `build::initialize_build` and the start of an incremental build
+//! (package discovery, reading the previous build, dirtiness marking and expanding the compile
+//! universe), but never invokes bsc and never writes to or deletes from `lib/`. Because nothing
+//! is parsed, the dependencies of modules that would be parsed come from their previous `.ast`
+//! files, so the plan can miss imports that were added since the last build.
+
+use super::build_types::*;
+use super::compile_universe::CompileReason;
+use super::{affected, clean, compile, compiler_info, deps, packages, read_compile_state, targets};
+use crate::config::SourceMapCommand;
+use crate::helpers;
+use crate::project_context::ProjectContext;
+use ahash::AHashSet;
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+use tracing::instrument;
+
+#[derive(Debug, Default)]
+pub struct DryRunReport {
+    pub project_root: PathBuf,
+    /// Packages whose build would be cleaned because the compiler, runtime or config changed.
+    pub cleaned_packages: Vec<String>,
+    /// Source files that would be parsed.
+    pub parse: Vec<PathBuf>,
+    /// Modules that would be compiled, in the order the scheduler would start them.
+    pub compile: Vec<(String, CompileReason)>,
+    /// Modules in the compile universe that can't be scheduled because of a dependency cycle.
+    pub cycle: Vec<String>,
+    /// Artifacts of the previous build that would be deleted.
+    pub stale_artifacts: Vec<PathBuf>,
+}
+
+impl DryRunReport {
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.project_root).unwrap_or(path)
+    }
+
+    pub fn format(&self) -> String {
+        let mut out = String::new();
+        for package in &self.cleaned_packages {
+            out.push_str(&format!(
+                "Would clean package {package}: {}\n",
+                CompileReason::CompilerInfoMismatch.describe()
+            ));
+        }
+
+        out.push_str(&format!("Would parse {} files", self.parse.len()));
+        for path in &self.parse {
+            out.push_str(&format!("\n  {}", self.relative(path).display()));
+        }
+
+        out.push_str(&format!(
+            "\nWould compile {} modules (in scheduler order)",
+            self.compile.len()
+        ));
+        for (module_name, reason) in &self.compile {
+            out.push_str(&format!(
+                "\n  {}: {}",
+                helpers::format_namespaced_module_name(module_name),
+                reason.describe()
+            ));
+        }
+        if !self.cycle.is_empty() {
+            out.push_str(&format!(
+                "\nCould not schedule {} modules because of a dependency cycle",
+                self.cycle.len()
+            ));
+            for module_name in &self.cycle {
+                out.push_str(&format!(
+                    "\n  {}",
+                    helpers::format_namespaced_module_name(module_name)
+                ));
+            }
+        }
+
+        out.push_str(&format!(
+            "\nWould delete {} stale artifacts",
+            self.stale_artifacts.len()
+        ));
+        for path in &self.stale_artifacts {
+            out.push_str(&format!("\n  {}", self.relative(path).display()));
+        }
+        out
+    }
+}
+
+fn source_paths(package: &packages::Package, source_file: &SourceFile) -> Vec<PathBuf> {
+    let mut paths = vec![package.path.join(&source_file.implementation.path)];
+    if let Some(interface) = &source_file.interface {
+        paths.push(package.path.join(&interface.path));
+    }
+    paths
+}
+
+#[instrument(name = "build.dry_run", skip_all, fields(working_dir = %path.display()))]
+pub fn dry_run(
+    filter: &Option<regex::Regex>,
+    path: &Path,
+    warn_error: Option<String>,
+    prod: bool,
+    features: Option<Vec<String>>,
+    targets: &[String],
+    since: Option<&str>,
+) -> Result<DryRunReport> {
+    let project_context = ProjectContext::new(path)?;
+    let compiler = super::get_compiler_info(&project_context)?;
+    let packages = packages::make(filter, &project_context, false, prod, features.as_ref())?;
+
+    let source_map_args = project_context
+        .get_root_config()
+        .get_source_map_args(SourceMapCommand::Build);
+    let mut cleaned_packages =
+        compiler_info::find_mismatched_packages(&packages, &compiler, &source_map_args)
+            .into_iter()
+            .map(|package| package.name.to_owned())
+            .collect::<Vec<String>>();
+    cleaned_packages.sort();
+
+    if !packages::validate_packages_dependencies(&packages) {
+        return Err(anyhow!("Failed to validate package dependencies"));
+    }
+
+    let mut build_state = BuildCommandState::new(
+        path.to_path_buf(),
+        project_context,
+        packages,
+        compiler,
+        warn_error,
+        features,
+        SourceMapCommand::Build,
+    );
+    packages::parse_packages(&mut build_state, false)?;
+
+    let compile_assets_state = read_compile_state::read(&mut build_state)?;
+    let (candidates, _, _) = clean::scan_previous_build(&mut build_state, compile_assets_state);
+
+    // A cleaned package loses its whole build folder, so everything in it starts from scratch.
+    let mut cleaned_build_paths = Vec::new();
+    for package_name in &cleaned_packages {
+        let package = build_state.get_package(package_name).expect("Package not found");
+        cleaned_build_paths.push(package.get_build_path());
+        cleaned_build_paths.push(package.get_ocaml_build_path());
+    }
+    let cleaned_modules = build_state
+        .modules
+        .iter()
+        .filter(|(_, module)| cleaned_packages.contains(&module.package_name))
+        .map(|(module_name, _)| module_name.to_owned())
+        .collect::<Vec<String>>();
+    for module_name in cleaned_modules {
+        build_state.record_compile_dirty_reason(&module_name, CompileReason::CompilerInfoMismatch);
+        let module = build_state.modules.get_mut(&module_name).unwrap();
+        module.last_compiled_cmi = None;
+        module.last_compiled_cmt = None;
+        if let SourceType::SourceFile(source_file) = &mut module.source_type {
+            source_file.implementation.parse_dirty = true;
+            if let Some(interface) = source_file.interface.as_mut() {
+                interface.parse_dirty = true;
+            }
+        }
+    }
+
+    let mut stale_artifacts = cleaned_build_paths
+        .iter()
+        .filter(|path| path.exists())
+        .cloned()
+        .collect::<Vec<PathBuf>>();
+    stale_artifacts.extend(candidates.into_iter().filter(|path| {
+        path.exists()
+            && !cleaned_build_paths
+                .iter()
+                .any(|cleaned| path.starts_with(cleaned))
+    }));
+    stale_artifacts.sort();
+    stale_artifacts.dedup();
+
+    // Everything `parse::generate_asts` would parse becomes compile dirty.
+    let mut parse = Vec::new();
+    let mut parsed_modules = Vec::new();
+    for (module_name, module) in build_state.modules.iter() {
+        if let SourceType::SourceFile(source_file) = &module.source_type
+            && (source_file.implementation.parse_dirty
+                || source_file.interface.as_ref().is_some_and(|i| i.parse_dirty))
+        {
+            let package = build_state
+                .get_package(&module.package_name)
+                .expect("Package not found");
+            parse.extend(source_paths(package, source_file));
+            parsed_modules.push(module_name.to_owned());
+        }
+    }
+    parse.sort();
+    for module_name in parsed_modules {
+        let module = build_state.modules.get_mut(&module_name).unwrap();
+        module.compile_dirty = true;
+        module.deps_dirty = true;
+        build_state.record_compile_dirty_reason(&module_name, CompileReason::ParseDirty);
+    }
+
+    let since_changed = since
+        .map(|since| affected::record_changes(&mut build_state.build_state, since))
+        .transpose()?;
+    let deleted_modules = build_state.deleted_modules.clone();
+    deps::get_deps_from_previous_build(&mut build_state, &deleted_modules);
+    compile::mark_modules_with_expired_deps_dirty(&mut build_state);
+    compile::mark_modules_with_deleted_deps_dirty(&mut build_state.build_state);
+
+    let mut universe = compile::compute_compile_universe(&build_state.build_state);
+    if let Some(changed) = &since_changed {
+        let affected_modules = affected::dependents_closure(changed, &build_state.build_state.modules);
+        targets::restrict_universe(&mut universe, &affected_modules, &build_state.build_state.modules);
+    } else if !targets.is_empty() {
+        let target_modules = targets::resolve(targets, &build_state.build_state)?;
+        targets::restrict_universe(&mut universe, &target_modules, &build_state.build_state.modules);
+    }
+    let universe_names = universe.keys().cloned().collect::<AHashSet<String>>();
+    let order = compile::scheduler_order(&universe_names, &build_state.build_state);
+    let compile = order
+        .into_iter()
+        .map(|module_name| {
+            let reason = universe
+                .remove(&module_name)
+                .expect("Module not in compile universe");
+            (module_name, reason)
+        })
+        .collect();
+    let mut cycle = universe.into_keys().collect::<Vec<String>>();
+    cycle.sort();
+
+    Ok(DryRunReport {
+        project_root: build_state.project_context.get_root_path().to_path_buf(),
+        cleaned_packages,
+        parse,
+        compile,
+        cycle,
+        stale_artifacts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_paths_relative_to_the_project_root() {
+        let report = DryRunReport {
+            project_root: PathBuf::from("/repo"),
+            cleaned_packages: vec![],
+            parse: vec![PathBuf::from("/repo/src/Utils.res")],
+            compile: vec![
+                ("Utils".to_string(), CompileReason::ParseDirty),
+                (
+                    "Button-Ui".to_string(),
+                    CompileReason::DirtyDependency {
+                        module: "Utils".to_string(),
+                    },
+                ),
+            ],
+            cycle: vec![],
+            stale_artifacts: vec![PathBuf::from("/repo/lib/bs/src/Old.cmj")],
+        };
+
+        assert_eq!(
+            report.format(),
+            "Would parse 1 files\n  src/Utils.res\nWould compile 2 modules (in scheduler order)\n  Utils: source changed\n  Ui.Button: depends on Utils\nWould delete 1 stale artifacts\n  lib/bs/src/Old.cmj"
+        );
+    }
+
+    #[test]
+    fn reports_cleaned_packages_and_cycles() {
+        let report = DryRunReport {
+            project_root: PathBuf::from("/repo"),
+            cleaned_packages: vec!["dep".to_string()],
+            cycle: vec!["A".to_string(), "B".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            report.format(),
+            "Would clean package dep: compiler, runtime or package config changed since the last build\nWould parse 0 files\nWould compile 0 modules (in scheduler order)\nCould not schedule 2 modules because of a dependency cycle\n  A\n  B\nWould delete 0 stale artifacts"
+        );
+    }
+}
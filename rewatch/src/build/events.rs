@@ -0,0 +1,262 @@
+//! Structured build events for editors and tooling, enabled with `rescript watch --events
+//! stdout-json` or `--events-socket <path>`.
+//!
+//! Every event is one JSON object on its own line, with an `event` field naming it:
+//!
+//! ```text
+//! {"event":"files-changed","paths":["/abs/src/App.res"]}
+//! {"event":"build-started","kind":"incremental"}
+//! {"event":"parse-finished","parsed":1,"success":true}
+//! {"event":"module-compiled","module":"App","package":"my-app","success":true}
+//! {"event":"diagnostics","diagnostics":[]}
+//! {"event":"build-finished","kind":"incremental","success":true,"cancelled":false,"outcome":"clean","duration_ms":42}
+//! {"event":"after-build-output","stream":"stdout","line":"All tests passed"}
+//! ```
+//!
+//! A `module-compiled` event is sent once the module's JS output is written, `build-finished`
+//! once all output of the build is. The `--after-build` command runs after `build-finished`, and
+//! every line it prints is sent as an `after-build-output` event.
+
+use super::CompilationOutcome;
+use super::diagnostics::Diagnostic;
+use crate::cli::EventsFormat;
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildKind {
+    /// The build when the watcher starts.
+    Initial,
+    /// Only changed sources and their dependents are rebuilt.
+    Incremental,
+    /// Packages and sources are rediscovered, e.g. after a file was added or a config changed.
+    Full,
+}
+
+/// The pipe of the after-build command that a line was written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event<'a> {
+    FilesChanged {
+        paths: &'a [PathBuf],
+    },
+    BuildStarted {
+        kind: BuildKind,
+    },
+    ParseFinished {
+        parsed: u64,
+        success: bool,
+    },
+    ModuleCompiled {
+        module: &'a str,
+        package: &'a str,
+        success: bool,
+    },
+    Diagnostics {
+        diagnostics: &'a [Diagnostic],
+    },
+    BuildFinished {
+        kind: BuildKind,
+        success: bool,
+        /// Sources changed during the build, another build follows.
+        cancelled: bool,
+        /// `None` when the build failed.
+        outcome: Option<CompilationOutcome>,
+        duration_ms: u128,
+    },
+    AfterBuildOutput {
+        stream: OutputStream,
+        line: &'a str,
+    },
+}
+
+#[derive(Debug)]
+pub struct EventSink {
+    stdout: bool,
+    #[cfg(unix)]
+    socket: Option<socket::EventSocket>,
+}
+
+impl EventSink {
+    /// `None` when no event output was requested.
+    pub fn new(format: Option<EventsFormat>, socket_path: Option<&Path>) -> Result<Option<Self>> {
+        if format.is_none() && socket_path.is_none() {
+            return Ok(None);
+        }
+        #[cfg(unix)]
+        let socket = socket_path.map(socket::EventSocket::listen).transpose()?;
+        #[cfg(not(unix))]
+        if socket_path.is_some() {
+            return Err(anyhow::anyhow!("--events-socket is only supported on Unix"));
+        }
+        Ok(Some(Self {
+            stdout: format == Some(EventsFormat::StdoutJson),
+            #[cfg(unix)]
+            socket,
+        }))
+    }
+
+    /// Whether events are printed on stdout, where they can't be mixed with other output.
+    pub fn writes_to_stdout(&self) -> bool {
+        self.stdout
+    }
+
+    pub fn emit(&self, event: &Event) {
+        let mut line = serde_json::to_string(event).expect("Event serialization should not fail");
+        line.push('\n');
+        if self.stdout {
+            let mut stdout = std::io::stdout().lock();
+            let _ = stdout.write_all(line.as_bytes());
+            let _ = stdout.flush();
+        }
+        #[cfg(unix)]
+        if let Some(socket) = &self.socket {
+            socket.broadcast(line.as_bytes());
+        }
+    }
+}
+
+#[cfg(unix)]
+mod socket {
+    use anyhow::{Context, Result, anyhow};
+    use std::io::Write;
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// A client that doesn't read its events for this long is disconnected, so it can't stall
+    /// the build.
+    const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// A Unix domain socket that any number of clients can connect to. Every event goes to all
+    /// clients connected at that time.
+    #[derive(Debug)]
+    pub struct EventSocket {
+        path: PathBuf,
+        pub(super) clients: Arc<Mutex<Vec<UnixStream>>>,
+    }
+
+    impl EventSocket {
+        pub fn listen(path: &Path) -> Result<Self> {
+            // Replace a socket left behind by a watcher that didn't shut down, but nothing else.
+            if let Ok(metadata) = path.symlink_metadata() {
+                if !metadata.file_type().is_socket() {
+                    return Err(anyhow!(
+                        "Could not listen on {}: the file exists and is not a socket",
+                        path.to_string_lossy()
+                    ));
+                }
+                let _ = std::fs::remove_file(path);
+            }
+            let listener = UnixListener::bind(path)
+                .with_context(|| format!("Could not listen on {}", path.to_string_lossy()))?;
+
+            let clients = Arc::new(Mutex::new(Vec::new()));
+            let accepted_clients = clients.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok() {
+                        accepted_clients.lock().unwrap().push(stream);
+                    }
+                }
+            });
+            Ok(Self {
+                path: path.to_path_buf(),
+                clients,
+            })
+        }
+
+        pub fn broadcast(&self, line: &[u8]) {
+            self.clients
+                .lock()
+                .unwrap()
+                .retain_mut(|client| client.write_all(line).is_ok());
+        }
+    }
+
+    impl Drop for EventSocket {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_tagged_json_lines() {
+        let event = Event::BuildFinished {
+            kind: BuildKind::Incremental,
+            success: true,
+            cancelled: false,
+            outcome: Some(CompilationOutcome::Warnings),
+            duration_ms: 12,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"build-finished","kind":"incremental","success":true,"cancelled":false,"outcome":"warnings","duration_ms":12}"#
+        );
+        let event = Event::ModuleCompiled {
+            module: "App",
+            package: "my-app",
+            success: false,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"module-compiled","module":"App","package":"my-app","success":false}"#
+        );
+        let event = Event::AfterBuildOutput {
+            stream: OutputStream::Stderr,
+            line: "1 test failed",
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"after-build-output","stream":"stderr","line":"1 test failed"}"#
+        );
+    }
+
+    #[test]
+    fn no_sink_without_event_options() {
+        assert!(EventSink::new(None, None).unwrap().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn socket_clients_receive_events() {
+        use std::io::{BufRead, BufReader};
+        use std::os::unix::net::UnixStream;
+
+        let temp_dir = tempfile::TempDir::new().expect("temp dir should be created");
+        let path = temp_dir.path().join("events.sock");
+        let sink = EventSink::new(None, Some(&path)).unwrap().unwrap();
+        let client = UnixStream::connect(&path).unwrap();
+        // The listener thread accepts the client asynchronously.
+        while sink.socket.as_ref().unwrap().clients.lock().unwrap().is_empty() {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        sink.emit(&Event::BuildStarted {
+            kind: BuildKind::Full,
+        });
+        let mut line = String::new();
+        BufReader::new(client).read_line(&mut line).unwrap();
+        assert_eq!(line, "{\"event\":\"build-started\",\"kind\":\"full\"}\n");
+
+        drop(sink);
+        assert!(!path.exists(), "the socket should be removed");
+    }
+}
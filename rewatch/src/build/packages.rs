@@ -877,8 +877,11 @@ pub fn make(
     Ok(result)
 }
 
+/// Add the modules of all packages to the build state. With `create_build_files`, the build
+/// folders are created and the namespace `.mlmap` files are (re)generated; without it (for
+/// `build --dry-run`) nothing on disk is touched.
 #[instrument(name = "packages.parse_packages", skip_all)]
-pub fn parse_packages(build_state: &mut BuildState) -> Result<()> {
+pub fn parse_packages(build_state: &mut BuildState, create_build_files: bool) -> Result<()> {
     let packages = build_state.packages.clone();
     for (package_name, package) in packages.iter() {
         debug!("Parsing package: {package_name}");
@@ -887,12 +890,14 @@ pub fn parse_packages(build_state: &mut BuildState) -> Result<()> {
         }
         let build_path_abs = package.get_build_path();
         let bs_build_path = package.get_ocaml_build_path();
-        helpers::create_path(&build_path_abs);
-        helpers::create_path(&bs_build_path);
         let root_config = build_state.get_root_config();
+        if create_build_files {
+            helpers::create_path(&build_path_abs);
+            helpers::create_path(&bs_build_path);
+        }
 
         root_config.get_package_specs().iter().for_each(|spec| {
-            if !spec.in_source {
+            if create_build_files && !spec.in_source {
                 // we don't want to calculate this if we don't have out of source specs
                 // we do this twice, but we almost never have multiple package specs
                 // so this optimization is less important
@@ -949,7 +954,11 @@ pub fn parse_packages(build_state: &mut BuildState) -> Result<()> {
                 .filter(|module_name| helpers::is_non_exotic_module_name(module_name))
                 .collect::<AHashSet<String>>();
 
-            let mlmap = namespaces::gen_mlmap(package, namespace, &depending_modules);
+            let mlmap = if create_build_files {
+                namespaces::gen_mlmap(package, namespace, &depending_modules)
+            } else {
+                package.get_mlmap_path()
+            };
 
             // mlmap will be compiled in the AST generation step
             // compile_mlmap(&package, namespace, &project_root);
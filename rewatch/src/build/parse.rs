@@ -1,7 +1,12 @@
+use super::artifact_cache::{Artifact, Step};
 use super::build_types::*;
 use super::compile::retain_critical_external_warnings;
+use super::compile_universe::CompileReason;
+use super::diagnostics::{self, Diagnostic, Severity};
 use super::logs;
 use super::namespaces;
+use super::source_hashes;
+use super::timings::Phase;
 use crate::build::packages::Package;
 use crate::config;
 use crate::config::{Config, OneOrMore};
@@ -15,12 +20,14 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 use tracing::info_span;
 
+#[allow(clippy::collapsible_match)]
 pub fn generate_asts(
     build_state: &mut BuildCommandState,
     inc: impl Fn() + std::marker::Sync,
 ) -> anyhow::Result<String> {
     let mut has_failure = false;
     let mut stderr = "".to_string();
+    let mut parse_diagnostics: Vec<Diagnostic> = Vec::new();
 
     // Count dirty modules for the span attribute only when a subscriber is
     // listening. Otherwise iterating every module every parse phase is pure
@@ -42,6 +49,8 @@ pub fn generate_asts(
     };
     let _span = parse_span.enter();
 
+    let hashes_before_parse = source_hashes::hash_parse_dirty_sources(build_state);
+
     build_state
         .modules
         .par_iter()
@@ -70,23 +79,34 @@ pub fn generate_asts(
                     {
                         debug!("Generating AST for module: {module_name}");
                         inc();
-                        let ast_result = generate_ast(
-                            package.to_owned(),
-                            &source_file.implementation.path.to_owned(),
-                            build_state,
-                            build_state.get_warn_error_override(),
-                            &parse_span,
-                        )
-                        .map_err(|e| e.to_string());
-
-                        let iast_result = match source_file.interface.as_ref().map(|i| i.path.to_owned()) {
-                            Some(interface_file_path) => {
-                                match generate_ast(
+                        let ast_result = build_state
+                            .timings
+                            .record(module_name, &package.name, Phase::Parse, || {
+                                generate_ast(
                                     package.to_owned(),
-                                    &interface_file_path.to_owned(),
+                                    &source_file.implementation.path.to_owned(),
                                     build_state,
                                     build_state.get_warn_error_override(),
                                     &parse_span,
+                                )
+                            })
+                            .map_err(|e| e.to_string());
+
+                        let iast_result = match source_file.interface.as_ref().map(|i| i.path.to_owned()) {
+                            Some(interface_file_path) => {
+                                match build_state.timings.record(
+                                    module_name,
+                                    &package.name,
+                                    Phase::Parse,
+                                    || {
+                                        generate_ast(
+                                            package.to_owned(),
+                                            &interface_file_path.to_owned(),
+                                            build_state,
+                                            build_state.get_warn_error_override(),
+                                            &parse_span,
+                                        )
+                                    },
                                 ) {
                                     Ok(v) => Ok(Some(v)),
                                     Err(e) => Err(e.to_string()),
@@ -149,8 +169,36 @@ pub fn generate_asts(
                 if is_dirty {
                     module.compile_dirty = true;
                     module.deps_dirty = true;
+                    build_state
+                        .build_state
+                        .compile_dirty_reasons
+                        .entry(module_name.to_owned())
+                        .or_insert(CompileReason::ParseDirty);
                 }
                 if let SourceType::SourceFile(ref mut source_file) = module.source_type {
+                    if is_dirty {
+                        source_hashes::record_parse(
+                            &mut build_state.build_state.source_hashes,
+                            &hashes_before_parse,
+                            package,
+                            &source_file.implementation.path,
+                            ast_result.as_ref().ok().map(|(ast_path, _)| ast_path.as_path()),
+                        );
+                        if let Some(interface) = &source_file.interface {
+                            source_hashes::record_parse(
+                                &mut build_state.build_state.source_hashes,
+                                &hashes_before_parse,
+                                package,
+                                &interface.path,
+                                iast_result
+                                    .as_ref()
+                                    .ok()
+                                    .and_then(Option::as_ref)
+                                    .map(|(ast_path, _)| ast_path.as_path()),
+                            );
+                        }
+                    }
+
                     // We get Err(x) when there is a parse error. When it's Ok(_, Some(
                     // stderr_warnings )), the outputs are warnings
                     match ast_result {
@@ -161,6 +209,11 @@ pub fn generate_asts(
                             source_file.implementation.parse_state = ParseState::Warning;
                             source_file.implementation.parse_dirty = true;
                             logs::append(package, &stderr_warnings);
+                            parse_diagnostics.extend(diagnostics::parse(
+                                &stderr_warnings,
+                                &package.name,
+                                Severity::Warning,
+                            ));
                             stderr.push_str(&stderr_warnings);
                         }
                         Ok((_path, Some(stderr_warnings))) => {
@@ -171,6 +224,11 @@ pub fn generate_asts(
                             // downstream users can report breakage upstream.
                             if let Some(kept) = retain_critical_external_warnings(&stderr_warnings) {
                                 logs::append(package, &kept);
+                                parse_diagnostics.extend(diagnostics::parse(
+                                    &kept,
+                                    &package.name,
+                                    Severity::Warning,
+                                ));
                                 stderr.push_str(&kept);
                             }
                         }
@@ -183,6 +241,11 @@ pub fn generate_asts(
                             source_file.implementation.parse_state = ParseState::ParseError;
                             source_file.implementation.parse_dirty = true;
                             logs::append(package, &err);
+                            parse_diagnostics.extend(diagnostics::parse(
+                                &err,
+                                &package.name,
+                                Severity::Error,
+                            ));
                             has_failure = true;
                             stderr.push_str(&err);
                         }
@@ -200,6 +263,11 @@ pub fn generate_asts(
                                 interface.parse_dirty = true;
                             }
                             logs::append(package, &stderr_warnings);
+                            parse_diagnostics.extend(diagnostics::parse(
+                                &stderr_warnings,
+                                &package.name,
+                                Severity::Warning,
+                            ));
                             stderr.push_str(&stderr_warnings);
                         }
                         Ok(Some((_, Some(stderr_warnings)))) => {
@@ -209,6 +277,11 @@ pub fn generate_asts(
                             }
                             if let Some(kept) = retain_critical_external_warnings(&stderr_warnings) {
                                 logs::append(package, &kept);
+                                parse_diagnostics.extend(diagnostics::parse(
+                                    &kept,
+                                    &package.name,
+                                    Severity::Warning,
+                                ));
                                 stderr.push_str(&kept);
                             }
                         }
@@ -225,6 +298,11 @@ pub fn generate_asts(
                                 interface.parse_dirty = true;
                             }
                             logs::append(package, &err);
+                            parse_diagnostics.extend(diagnostics::parse(
+                                &err,
+                                &package.name,
+                                Severity::Error,
+                            ));
                             has_failure = true;
                             stderr.push_str(&err);
                         }
@@ -262,13 +340,25 @@ pub fn generate_asts(
                         // specific to compiling mlmaps
                         let compile_path = package.get_mlmap_compile_path();
                         let mlmap_hash = helpers::compute_file_hash(Path::new(&compile_path));
-                        if let Err(err) = namespaces::compile_mlmap(
-                            &build_state.build_state.project_context,
-                            package,
+                        if let Err(err) = build_state.build_state.timings.record(
                             &module_name,
-                            &build_state.build_state.compiler_info.bsc_path,
+                            &package_name,
+                            Phase::Mlmap,
+                            || {
+                                namespaces::compile_mlmap(
+                                    &build_state.build_state.project_context,
+                                    package,
+                                    &module_name,
+                                    &build_state.build_state.compiler_info.bsc_path,
+                                )
+                            },
                         ) {
                             has_failure = true;
+                            parse_diagnostics.extend(diagnostics::parse(
+                                &err.to_string(),
+                                &package_name,
+                                Severity::Error,
+                            ));
                             stderr.push_str(&format!("{err}\n"));
                         }
                         let mlmap_hash_after = helpers::compute_file_hash(Path::new(&compile_path));
@@ -307,10 +397,17 @@ pub fn generate_asts(
             };
             if is_dirty {
                 module.compile_dirty = is_dirty;
+                build_state
+                    .build_state
+                    .compile_dirty_reasons
+                    .entry(module_name.to_owned())
+                    .or_insert(CompileReason::ParseDirty);
             }
         }
     }
 
+    build_state.diagnostics.extend(parse_diagnostics);
+
     if has_failure {
         Err(anyhow!(stderr))
     } else {
@@ -437,43 +534,67 @@ fn generate_ast(
     let ast_parent_path = package.get_build_path().join(ast_path.parent().unwrap());
     helpers::create_path(&ast_parent_path);
 
+    let cache_key = build_state.artifact_cache.as_ref().and_then(|cache| {
+        cache.key(
+            build_state,
+            Step::Parse,
+            std::slice::from_ref(&file_path),
+            &parser_args,
+            &[],
+        )
+    });
+    let cache_artifacts = [Artifact::required("ast", build_path_abs.join(&ast_path))];
+    let restored = match (&build_state.artifact_cache, &cache_key) {
+        (Some(cache), Some(key)) => cache.restore(key, &cache_artifacts),
+        _ => false,
+    };
+
     /* Create .ast */
-    let result = match Some(
-        Command::new(&build_state.compiler_info.bsc_path)
-            .current_dir(&build_path_abs)
-            .args(parser_args)
-            .output()
-            .map_err(|e| {
-                anyhow!(
-                    "Error running bsc for parsing {}: {}",
-                    filename.to_string_lossy(),
-                    e
-                )
-            })?,
-    ) {
-        Some(res_to_ast) => {
-            let stderr = String::from_utf8_lossy(&res_to_ast.stderr).to_string();
-
-            if helpers::contains_ascii_characters(&stderr) {
-                if res_to_ast.status.success() {
-                    Ok((ast_path, Some(stderr.to_string())))
+    let result = if restored {
+        Ok((ast_path, None))
+    } else {
+        match Some(
+            Command::new(&build_state.compiler_info.bsc_path)
+                .current_dir(&build_path_abs)
+                .args(parser_args)
+                .output()
+                .map_err(|e| {
+                    anyhow!(
+                        "Error running bsc for parsing {}: {}",
+                        filename.to_string_lossy(),
+                        e
+                    )
+                })?,
+        ) {
+            Some(res_to_ast) => {
+                let stderr = String::from_utf8_lossy(&res_to_ast.stderr).to_string();
+
+                if helpers::contains_ascii_characters(&stderr) {
+                    if res_to_ast.status.success() {
+                        Ok((ast_path, Some(stderr.to_string())))
+                    } else {
+                        Err(anyhow!("Error in {}:\n{}", package.name, stderr))
+                    }
                 } else {
-                    Err(anyhow!("Error in {}:\n{}", package.name, stderr))
+                    Ok((ast_path, None))
                 }
-            } else {
-                Ok((ast_path, None))
             }
-        }
-        _ => {
-            log::info!("Parsing file {}...", filename.display());
-
-            Err(anyhow!(
-                "Could not find canonicalize_string_path for file {} in package {}",
-                filename.display(),
-                package.name
-            ))
+            _ => {
+                log::info!("Parsing file {}...", filename.display());
+
+                Err(anyhow!(
+                    "Could not find canonicalize_string_path for file {} in package {}",
+                    filename.display(),
+                    package.name
+                ))
+            }
         }
     };
+    if !restored
+        && let (Some(cache), Some(key), Ok((_, None))) = (&build_state.artifact_cache, &cache_key, &result)
+    {
+        cache.store(key, &cache_artifacts);
+    }
     if let Ok((ast_path, _)) = &result {
         let _ = std::fs::copy(
             Path::new(&build_path_abs).join(ast_path),
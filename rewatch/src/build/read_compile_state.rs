@@ -1,5 +1,6 @@
 use super::build_types::*;
 use super::packages;
+use super::source_hashes;
 use crate::helpers;
 use ahash::{AHashMap, AHashSet};
 use rayon::prelude::*;
@@ -11,6 +12,7 @@ pub fn read(build_state: &mut BuildCommandState) -> anyhow::Result<CompileAssets
     let mut ast_modules: AHashMap<PathBuf, AstModule> = AHashMap::new();
     let mut cmi_modules: AHashMap<String, SystemTime> = AHashMap::new();
     let mut cmt_modules: AHashMap<String, SystemTime> = AHashMap::new();
+    let mut cmj_modules: AHashSet<String> = AHashSet::new();
     let mut ast_rescript_file_locations = AHashSet::new();
 
     let mut rescript_file_locations = build_state
@@ -45,7 +47,11 @@ pub fn read(build_state: &mut BuildCommandState) -> anyhow::Result<CompileAssets
         .packages
         .par_iter()
         .map(|(_, package)| {
-            let read_dir = fs::read_dir(package.get_ocaml_build_path()).unwrap();
+            // The folder doesn't exist yet when a package was never built (only possible in a
+            // dry run, a real build creates it in `parse_packages`).
+            let Ok(read_dir) = fs::read_dir(package.get_ocaml_build_path()) else {
+                return Vec::new();
+            };
             read_dir
                 .filter_map(|entry| match entry {
                     Ok(entry) => {
@@ -53,7 +59,7 @@ pub fn read(build_state: &mut BuildCommandState) -> anyhow::Result<CompileAssets
                         let extension = path.extension().and_then(|e| e.to_str());
                         match extension {
                             Some(ext) => match ext {
-                                "iast" | "ast" | "cmi" | "cmt" => Some((
+                                "iast" | "ast" | "cmi" | "cmt" | "cmj" => Some((
                                     path.to_owned(),
                                     entry.metadata().unwrap().modified().unwrap(),
                                     ext.to_owned(),
@@ -116,6 +122,12 @@ pub fn read(build_state: &mut BuildCommandState) -> anyhow::Result<CompileAssets
                     );
                     cmt_modules.insert(module_name, last_modified.to_owned());
                 }
+                "cmj" => {
+                    cmj_modules.insert(helpers::file_path_to_module_name(
+                        path,
+                        &packages::Namespace::NoNamespace,
+                    ));
+                }
                 _ => {
                     // println!("other extension: {:?}", other);
                 }
@@ -127,8 +139,10 @@ pub fn read(build_state: &mut BuildCommandState) -> anyhow::Result<CompileAssets
         ast_modules,
         cmi_modules,
         cmt_modules,
+        cmj_modules,
         ast_rescript_file_locations,
         rescript_file_locations,
+        source_hashes: source_hashes::read(build_state.project_context.get_root_path()),
     })
 }
 
@@ -0,0 +1,321 @@
+//! The module graph of the last build, persisted in `lib/bs/build-state.json` so the next build
+//! doesn't have to rediscover it.
+//!
+//! Without it every start lists the `lib/ocaml` folder of every package, reads the header of every
+//! `.ast` to find its source, and reads every `.ast` again to resolve dependencies. With it, the
+//! freshly discovered modules only get the saved dependencies, dirtiness and compile times
+//! overlaid, and sources are checked by the modification time `packages::make` already reads.
+//!
+//! The saved state is only used when the rewatch version, the compiler, every package config,
+//! every `lib/ocaml` folder and the set of modules (with their source paths) are unchanged. It's
+//! deleted as soon as it's read and written again when the build is done, so a build that is
+//! interrupted halfway falls back to a full scan.
+
+use super::build_types::*;
+use super::packages::{self, Package};
+use super::{clean, source_hashes};
+use crate::helpers;
+use ahash::AHashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Bump when the layout of `SavedState` changes.
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SavedState {
+    format_version: u32,
+    rewatch_version: String,
+    bsc_hash: String,
+    deps_initialized: bool,
+    packages: BTreeMap<String, PackageFingerprint>,
+    modules: BTreeMap<String, SavedModule>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct PackageFingerprint {
+    config_hash: Option<String>,
+    ocaml_build_modified: Option<SystemTime>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SavedModule {
+    package_name: String,
+    /// `None` for the namespace map of a package.
+    source: Option<SavedSource>,
+    mlmap_parse_dirty: bool,
+    deps: Vec<String>,
+    dependents: Vec<String>,
+    compile_dirty: bool,
+    deps_dirty: bool,
+    last_compiled_cmi: Option<SystemTime>,
+    last_compiled_cmt: Option<SystemTime>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SavedSource {
+    implementation: SavedFile,
+    interface: Option<SavedFile>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SavedFile {
+    path: PathBuf,
+    last_modified: SystemTime,
+    parse_dirty: bool,
+}
+
+pub fn get_path(project_root: &Path) -> PathBuf {
+    packages::get_build_path(project_root).join("build-state.json")
+}
+
+fn fingerprint(package: &Package) -> PackageFingerprint {
+    PackageFingerprint {
+        config_hash: helpers::compute_file_hash(&package.config.path).map(|hash| hash.to_hex().to_string()),
+        ocaml_build_modified: package
+            .get_ocaml_build_path()
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok(),
+    }
+}
+
+fn sorted(names: &AHashSet<String>) -> Vec<String> {
+    let mut names = names.iter().cloned().collect::<Vec<String>>();
+    names.sort();
+    names
+}
+
+fn save_module(module: &Module) -> SavedModule {
+    // `clean::cleanup_after_build` removed the ASTs of modules with warnings, so that the next
+    // build parses and compiles them again to show the warnings.
+    let ast_removed = clean::has_parse_warnings(module) || clean::has_compile_warnings(module);
+    let save_file = |path: &Path, last_modified: SystemTime, parse_dirty: bool| SavedFile {
+        path: path.to_path_buf(),
+        last_modified,
+        parse_dirty: parse_dirty || ast_removed,
+    };
+    let (source, mlmap_parse_dirty) = match &module.source_type {
+        SourceType::SourceFile(source_file) => (
+            Some(SavedSource {
+                implementation: save_file(
+                    &source_file.implementation.path,
+                    source_file.implementation.last_modified,
+                    source_file.implementation.parse_dirty,
+                ),
+                interface: source_file.interface.as_ref().map(|interface| {
+                    save_file(&interface.path, interface.last_modified, interface.parse_dirty)
+                }),
+            }),
+            false,
+        ),
+        SourceType::MlMap(mlmap) => (None, mlmap.parse_dirty),
+    };
+    SavedModule {
+        package_name: module.package_name.clone(),
+        source,
+        mlmap_parse_dirty,
+        deps: sorted(&module.deps),
+        dependents: sorted(&module.dependents),
+        compile_dirty: module.compile_dirty || ast_removed,
+        deps_dirty: module.deps_dirty,
+        last_compiled_cmi: module.last_compiled_cmi,
+        last_compiled_cmt: module.last_compiled_cmt,
+    }
+}
+
+fn save(build_state: &BuildState) -> SavedState {
+    SavedState {
+        format_version: FORMAT_VERSION,
+        rewatch_version: env!("CARGO_PKG_VERSION").to_string(),
+        bsc_hash: build_state.compiler_info.bsc_hash.to_hex().to_string(),
+        deps_initialized: build_state.deps_initialized,
+        packages: build_state
+            .packages
+            .iter()
+            .map(|(name, package)| (name.clone(), fingerprint(package)))
+            .collect(),
+        modules: build_state
+            .modules
+            .iter()
+            .map(|(name, module)| (name.clone(), save_module(module)))
+            .collect(),
+    }
+}
+
+/// Save the state at the end of a build. Must run after `clean::cleanup_after_build`, which is
+/// the last step that touches `lib/ocaml`.
+pub fn write(build_state: &BuildState) {
+    let path = get_path(build_state.project_context.get_root_path());
+    let result = serde_json::to_vec(&save(build_state))
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| std::fs::write(&path, contents).map_err(anyhow::Error::from));
+    if let Err(err) = result {
+        log::error!("Failed to write {}: {}", path.display(), err);
+    }
+}
+
+fn is_valid(saved: &SavedState, build_state: &BuildState) -> bool {
+    saved.format_version == FORMAT_VERSION
+        && saved.rewatch_version == env!("CARGO_PKG_VERSION")
+        && saved.bsc_hash == build_state.compiler_info.bsc_hash.to_hex().to_string()
+        && saved.packages.len() == build_state.packages.len()
+        && build_state.packages.iter().all(|(name, package)| {
+            saved
+                .packages
+                .get(name)
+                .is_some_and(|saved_package| *saved_package == fingerprint(package))
+        })
+        && saved.modules.len() == build_state.modules.len()
+        && build_state.modules.iter().all(|(name, module)| {
+            saved
+                .modules
+                .get(name)
+                .is_some_and(|saved_module| is_same_module(saved_module, module))
+        })
+}
+
+fn is_same_module(saved: &SavedModule, module: &Module) -> bool {
+    if saved.package_name != module.package_name {
+        return false;
+    }
+    match (&saved.source, &module.source_type) {
+        (None, SourceType::MlMap(_)) => true,
+        (Some(saved_source), SourceType::SourceFile(source_file)) => {
+            saved_source.implementation.path == source_file.implementation.path
+                && saved_source.interface.as_ref().map(|interface| &interface.path)
+                    == source_file.interface.as_ref().map(|interface| &interface.path)
+        }
+        _ => false,
+    }
+}
+
+/// Whether a source has to be parsed again: it was dirty when the state was saved, or it was
+/// modified since and its content changed.
+fn restore_parse_dirty(
+    saved: &SavedFile,
+    last_modified: SystemTime,
+    package: &Package,
+    build_state_source_hashes: &ahash::AHashMap<PathBuf, source_hashes::SourceHash>,
+) -> bool {
+    saved.parse_dirty
+        || (saved.last_modified != last_modified
+            && !source_hashes::is_unchanged(build_state_source_hashes, &package.path.join(&saved.path), None))
+}
+
+/// Overlay the state saved by the previous build on the freshly discovered modules. Returns the
+/// number of sources with an AST from the previous build, or `None` when there is no usable saved
+/// state and the previous build has to be scanned instead.
+pub fn restore(build_state: &mut BuildState) -> Option<usize> {
+    let path = get_path(build_state.project_context.get_root_path());
+    let contents = std::fs::read(&path).ok()?;
+    // Only trust the state once: a build that doesn't finish must not leave it behind.
+    let _ = std::fs::remove_file(&path);
+    let saved = serde_json::from_slice::<SavedState>(&contents).ok()?;
+    if !is_valid(&saved, build_state) {
+        return None;
+    }
+
+    let source_hashes = source_hashes::read(build_state.project_context.get_root_path());
+    let mut sources_with_ast = 0;
+    for (name, saved_module) in saved.modules {
+        let module = build_state.modules.get_mut(&name).expect("Module not found");
+        let package = build_state
+            .packages
+            .get(&module.package_name)
+            .expect("Package not found");
+        match (&mut module.source_type, saved_module.source) {
+            (SourceType::SourceFile(source_file), Some(saved_source)) => {
+                let implementation = &mut source_file.implementation;
+                implementation.parse_dirty = restore_parse_dirty(
+                    &saved_source.implementation,
+                    implementation.last_modified,
+                    package,
+                    &source_hashes,
+                );
+                sources_with_ast += usize::from(!saved_source.implementation.parse_dirty);
+                if let (Some(interface), Some(saved_interface)) =
+                    (source_file.interface.as_mut(), saved_source.interface)
+                {
+                    interface.parse_dirty = restore_parse_dirty(
+                        &saved_interface,
+                        interface.last_modified,
+                        package,
+                        &source_hashes,
+                    );
+                    sources_with_ast += usize::from(!saved_interface.parse_dirty);
+                }
+            }
+            (SourceType::MlMap(mlmap), None) => mlmap.parse_dirty = saved_module.mlmap_parse_dirty,
+            _ => unreachable!("Validated by is_same_module"),
+        }
+        module.deps = saved_module.deps.into_iter().collect();
+        module.dependents = saved_module.dependents.into_iter().collect();
+        module.compile_dirty = saved_module.compile_dirty;
+        module.deps_dirty = saved_module.deps_dirty;
+        module.last_compiled_cmi = saved_module.last_compiled_cmi;
+        module.last_compiled_cmt = saved_module.last_compiled_cmt;
+    }
+    build_state.deps_initialized = saved.deps_initialized;
+    build_state.source_hashes = source_hashes;
+    Some(sources_with_ast)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn source_module(path: &str, parse_dirty: bool) -> Module {
+        Module {
+            source_type: SourceType::SourceFile(SourceFile {
+                implementation: Implementation {
+                    path: PathBuf::from(path),
+                    parse_state: ParseState::Success,
+                    compile_state: CompileState::Success,
+                    last_modified: SystemTime::UNIX_EPOCH + Duration::from_secs(10),
+                    parse_dirty,
+                    compile_warnings: None,
+                },
+                interface: None,
+            }),
+            deps: AHashSet::from(["Utils".to_string()]),
+            dependents: AHashSet::new(),
+            package_name: "pkg".to_string(),
+            compile_dirty: false,
+            last_compiled_cmi: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(20)),
+            last_compiled_cmt: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(20)),
+            deps_dirty: false,
+            is_type_dev: false,
+        }
+    }
+
+    #[test]
+    fn modules_with_warnings_are_saved_as_dirty() {
+        let saved = save_module(&source_module("src/App.res", false));
+        assert!(!saved.compile_dirty);
+        assert!(!saved.source.as_ref().unwrap().implementation.parse_dirty);
+        assert_eq!(saved.deps, vec!["Utils"]);
+
+        let mut module = source_module("src/App.res", false);
+        if let SourceType::SourceFile(source_file) = &mut module.source_type {
+            source_file.implementation.compile_state = CompileState::Warning;
+        }
+        let saved = save_module(&module);
+        assert!(saved.compile_dirty);
+        assert!(saved.source.unwrap().implementation.parse_dirty);
+    }
+
+    #[test]
+    fn a_moved_source_is_not_the_same_module() {
+        let saved = save_module(&source_module("src/App.res", false));
+        assert!(is_same_module(&saved, &source_module("src/App.res", true)));
+        assert!(!is_same_module(&saved, &source_module("src/app/App.res", false)));
+
+        let mut module = source_module("src/App.res", false);
+        module.package_name = "other".to_string();
+        assert!(!is_same_module(&saved, &module));
+    }
+}
@@ -0,0 +1,187 @@
+//! Content hashes of the sources the current `.ast`/`.iast` files were parsed from, persisted in
+//! `lib/bs/source-hashes.json`.
+//!
+//! A source that is newer than its AST is normally parsed again. When its content still hashes to
+//! what the AST was parsed from (a git checkout, a branch switch or a formatter rewriting
+//! identical content), it isn't. Every hash is stored with the modification time of the AST it
+//! belongs to, and only trusted while that AST is unchanged, so a build that was interrupted
+//! after writing an AST but before writing this file can't make a stale hash match.
+
+use super::build_types::*;
+use super::packages::{self, Package};
+use crate::helpers;
+use ahash::AHashMap;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceHash {
+    pub hash: blake3::Hash,
+    /// Modification time of the AST that was parsed from the source with this hash.
+    pub ast_modified: SystemTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    hash: String,
+    ast_modified_ns: u64,
+}
+
+pub fn get_path(project_root: &Path) -> PathBuf {
+    packages::get_build_path(project_root).join("source-hashes.json")
+}
+
+/// The hashes of the last build, keyed by the absolute path of the source. Empty when they were
+/// never written or can't be read, in which case dirtiness falls back to modification times.
+pub fn read(project_root: &Path) -> AHashMap<PathBuf, SourceHash> {
+    let Ok(contents) = std::fs::read_to_string(get_path(project_root)) else {
+        return AHashMap::new();
+    };
+    let Ok(entries) = serde_json::from_str::<BTreeMap<PathBuf, Entry>>(&contents) else {
+        return AHashMap::new();
+    };
+    entries
+        .into_iter()
+        .filter_map(|(path, entry)| {
+            let hash = blake3::Hash::from_hex(&entry.hash).ok()?;
+            let ast_modified = UNIX_EPOCH + Duration::from_nanos(entry.ast_modified_ns);
+            Some((path, SourceHash { hash, ast_modified }))
+        })
+        .collect()
+}
+
+pub fn write(build_state: &BuildCommandState) {
+    let entries = build_state
+        .source_hashes
+        .iter()
+        .filter_map(|(path, source_hash)| {
+            let ast_modified_ns = source_hash
+                .ast_modified
+                .duration_since(UNIX_EPOCH)
+                .ok()?
+                .as_nanos();
+            Some((
+                path,
+                Entry {
+                    hash: source_hash.hash.to_hex().to_string(),
+                    ast_modified_ns: u64::try_from(ast_modified_ns).ok()?,
+                },
+            ))
+        })
+        .collect::<BTreeMap<&PathBuf, Entry>>();
+    let path = get_path(build_state.project_context.get_root_path());
+    let result = serde_json::to_string(&entries)
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| std::fs::write(&path, contents).map_err(anyhow::Error::from));
+    if let Err(err) = result {
+        log::error!("Failed to write {}: {}", path.display(), err);
+    }
+}
+
+/// Whether `source` still has the content its AST was parsed from. With `ast_modified`, the
+/// stored hash is only used if it belongs to that version of the AST.
+pub fn is_unchanged(
+    source_hashes: &AHashMap<PathBuf, SourceHash>,
+    source: &Path,
+    ast_modified: Option<&SystemTime>,
+) -> bool {
+    let Some(stored) = source_hashes.get(source) else {
+        return false;
+    };
+    if ast_modified.is_some_and(|ast_modified| *ast_modified != stored.ast_modified) {
+        return false;
+    }
+    helpers::compute_file_hash(source) == Some(stored.hash)
+}
+
+/// Hash every source that `parse::generate_asts` is about to parse. This has to happen before
+/// bsc reads them, a source that changes while it's being parsed then gets parsed again next time.
+pub fn hash_parse_dirty_sources(build_state: &BuildState) -> AHashMap<PathBuf, Option<blake3::Hash>> {
+    build_state
+        .modules
+        .par_iter()
+        .filter_map(|(_, module)| match &module.source_type {
+            SourceType::SourceFile(source_file)
+                if source_file.implementation.parse_dirty
+                    || source_file.interface.as_ref().is_some_and(|i| i.parse_dirty) =>
+            {
+                let package = build_state.get_package(&module.package_name)?;
+                let mut paths = vec![package.path.join(&source_file.implementation.path)];
+                paths.extend(
+                    source_file
+                        .interface
+                        .as_ref()
+                        .map(|interface| package.path.join(&interface.path)),
+                );
+                Some(paths)
+            }
+            _ => None,
+        })
+        .flatten()
+        .map(|path| {
+            let hash = helpers::compute_file_hash(&path);
+            (path, hash)
+        })
+        .collect::<Vec<(PathBuf, Option<blake3::Hash>)>>()
+        .into_iter()
+        .collect()
+}
+
+/// Remember the hash of a source that was just parsed, or forget it when parsing failed.
+pub fn record_parse(
+    source_hashes: &mut AHashMap<PathBuf, SourceHash>,
+    hashes_before_parse: &AHashMap<PathBuf, Option<blake3::Hash>>,
+    package: &Package,
+    source_path: &Path,
+    ast_path: Option<&Path>,
+) {
+    let source = package.path.join(source_path);
+    let source_hash = ast_path.and_then(|ast_path| {
+        let hash = (*hashes_before_parse.get(&source)?)?;
+        let ocaml_ast_path = package.get_ocaml_build_path().join(ast_path.file_name()?);
+        let ast_modified = ocaml_ast_path.metadata().and_then(|m| m.modified()).ok()?;
+        Some(SourceHash { hash, ast_modified })
+    });
+    match source_hash {
+        Some(source_hash) => source_hashes.insert(source, source_hash),
+        None => source_hashes.remove(&source),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn only_trusts_a_hash_for_the_ast_it_was_recorded_with() {
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        let source = temp_dir.path().join("Main.res");
+        std::fs::write(&source, "let x = 1").unwrap();
+
+        let ast_modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let mut source_hashes = AHashMap::new();
+        source_hashes.insert(
+            source.clone(),
+            SourceHash {
+                hash: blake3::hash(b"let x = 1"),
+                ast_modified,
+            },
+        );
+
+        assert!(is_unchanged(&source_hashes, &source, Some(&ast_modified)));
+        assert!(is_unchanged(&source_hashes, &source, None));
+        assert!(!is_unchanged(
+            &source_hashes,
+            &source,
+            Some(&(ast_modified + Duration::from_secs(1)))
+        ));
+
+        std::fs::write(&source, "let x = 2").unwrap();
+        assert!(!is_unchanged(&source_hashes, &source, Some(&ast_modified)));
+        assert!(!is_unchanged(&AHashMap::new(), &source, Some(&ast_modified)));
+    }
+}
@@ -0,0 +1,214 @@
+//! What a successful build did, passed to `--after-build` commands as environment variables.
+
+use super::CompilationOutcome;
+use super::build_types::{BuildCommandState, SourceType};
+use super::compile;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// One path or module name per line:
+/// - `RESCRIPT_CHANGED_FILES`: the sources that were parsed again because they changed or are new.
+/// - `RESCRIPT_COMPILED_MODULES`: the modules that were compiled, changed ones and the dependents
+///   whose dependencies' interface changed.
+/// - `RESCRIPT_GENERATED_JS`: the JS files written for the compiled modules.
+/// - `RESCRIPT_OUTCOME`: `clean` or `warnings`, and `failed` for `--after-build-failure` commands,
+///   which get no other variables.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildSummary {
+    pub changed_files: Vec<PathBuf>,
+    pub compiled_modules: Vec<String>,
+    pub generated_js: Vec<PathBuf>,
+    pub outcome: CompilationOutcome,
+}
+
+impl BuildSummary {
+    pub fn new(build_state: &BuildCommandState, outcome: CompilationOutcome) -> Self {
+        let mut compiled_modules: Vec<String> = build_state.compiled_modules.iter().cloned().collect();
+        compiled_modules.sort();
+
+        let root_config = build_state.get_root_config();
+        let mut generated_js = Vec::new();
+        for module_name in compiled_modules.iter().filter(|_| !build_state.check_only) {
+            let Some(module) = build_state.get_module(module_name) else {
+                continue;
+            };
+            let (SourceType::SourceFile(source_file), Some(package)) =
+                (&module.source_type, build_state.get_package(&module.package_name))
+            else {
+                continue;
+            };
+            for spec in root_config.get_package_specs() {
+                generated_js.push(compile::js_output_path(
+                    package,
+                    root_config,
+                    &spec,
+                    &source_file.implementation.path,
+                ));
+            }
+        }
+
+        Self {
+            changed_files: build_state.changed_sources.clone(),
+            compiled_modules,
+            generated_js,
+            outcome,
+        }
+    }
+
+    pub fn env_vars(&self) -> Vec<(&'static str, String)> {
+        let lines = |paths: &[PathBuf]| {
+            paths
+                .iter()
+                .map(|path| path.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        vec![
+            ("RESCRIPT_CHANGED_FILES", lines(&self.changed_files)),
+            ("RESCRIPT_COMPILED_MODULES", self.compiled_modules.join("\n")),
+            ("RESCRIPT_GENERATED_JS", lines(&self.generated_js)),
+            (
+                "RESCRIPT_OUTCOME",
+                match self.outcome {
+                    CompilationOutcome::Clean => "clean",
+                    CompilationOutcome::Warnings => "warnings",
+                }
+                .to_string(),
+            ),
+        ]
+    }
+
+    pub fn failure_env_vars() -> Vec<(&'static str, String)> {
+        vec![("RESCRIPT_OUTCOME", "failed".to_string())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::build_types::{
+        CompileState, CompilerInfo, Implementation, Module, ParseState, SourceFile,
+    };
+    use crate::build::compile_universe::CompileReason;
+    use crate::build::packages::{Namespace, Package};
+    use crate::config::{self, SourceMapCommand};
+    use crate::project_context::ProjectContext;
+    use ahash::{AHashMap, AHashSet};
+    use std::sync::RwLock;
+    use std::time::SystemTime;
+
+    const ROOT: &str = "/tmp/rewatch-build-summary";
+
+    fn test_config() -> config::Config {
+        config::tests::create_config(config::tests::CreateConfigArgs {
+            name: "test-package".to_string(),
+            bs_deps: vec![],
+            build_dev_deps: vec![],
+            allowed_dependents: None,
+            path: PathBuf::from(ROOT),
+        })
+    }
+
+    fn test_module(path: &str) -> Module {
+        Module {
+            source_type: SourceType::SourceFile(SourceFile {
+                implementation: Implementation {
+                    path: PathBuf::from(path),
+                    parse_state: ParseState::Success,
+                    compile_state: CompileState::Success,
+                    last_modified: SystemTime::UNIX_EPOCH,
+                    parse_dirty: false,
+                    compile_warnings: None,
+                },
+                interface: None,
+            }),
+            deps: AHashSet::new(),
+            dependents: AHashSet::new(),
+            package_name: "test-package".to_string(),
+            compile_dirty: false,
+            last_compiled_cmi: None,
+            last_compiled_cmt: None,
+            deps_dirty: false,
+            is_type_dev: false,
+        }
+    }
+
+    #[test]
+    fn skipped_dependents_are_not_listed() {
+        let package = Package {
+            name: "test-package".to_string(),
+            config: test_config(),
+            source_folders: AHashSet::new(),
+            source_files: None,
+            namespace: Namespace::NoNamespace,
+            modules: None,
+            path: PathBuf::from(ROOT),
+            dirs: None,
+            gentype_dirs: None,
+            is_local_dep: true,
+            is_root: true,
+        };
+        let mut build_state = BuildCommandState::new(
+            PathBuf::from(ROOT),
+            ProjectContext {
+                current_config: test_config(),
+                monorepo_context: None,
+                node_modules_exist_cache: RwLock::new(AHashMap::new()),
+                packages_cache: RwLock::new(AHashMap::new()),
+            },
+            AHashMap::from([(package.name.clone(), package)]),
+            CompilerInfo {
+                bsc_path: PathBuf::from(ROOT).join("bsc"),
+                bsc_hash: blake3::hash(b"test-bsc"),
+                runtime_path: PathBuf::from(ROOT).join("runtime"),
+            },
+            None,
+            None,
+            SourceMapCommand::Build,
+        );
+        build_state.insert_module("Dep", test_module("src/Dep.res"));
+        build_state.insert_module("App", test_module("src/App.res"));
+        // App was a candidate because it depends on Dep, but the `.cmi` of Dep didn't change.
+        build_state.compile_universe = AHashMap::from([
+            ("Dep".to_string(), CompileReason::ParseDirty),
+            (
+                "App".to_string(),
+                CompileReason::DirtyDependency {
+                    module: "Dep".to_string(),
+                },
+            ),
+        ]);
+        build_state.compiled_modules = AHashSet::from(["Dep".to_string()]);
+
+        let summary = BuildSummary::new(&build_state, CompilationOutcome::Clean);
+        assert_eq!(summary.compiled_modules, vec!["Dep"]);
+        assert_eq!(summary.generated_js.len(), 1);
+        assert!(
+            summary.generated_js[0].ends_with("src/Dep.js"),
+            "{:?}",
+            summary.generated_js
+        );
+    }
+
+    #[test]
+    fn lists_are_passed_one_per_line() {
+        let summary = BuildSummary {
+            changed_files: vec![PathBuf::from("/app/src/A.res"), PathBuf::from("/app/src/B.res")],
+            compiled_modules: vec!["A".to_string(), "B".to_string(), "C".to_string()],
+            generated_js: vec![],
+            outcome: CompilationOutcome::Warnings,
+        };
+        assert_eq!(
+            summary.env_vars(),
+            vec![
+                (
+                    "RESCRIPT_CHANGED_FILES",
+                    "/app/src/A.res\n/app/src/B.res".to_string()
+                ),
+                ("RESCRIPT_COMPILED_MODULES", "A\nB\nC".to_string()),
+                ("RESCRIPT_GENERATED_JS", String::new()),
+                ("RESCRIPT_OUTCOME", "warnings".to_string()),
+            ]
+        );
+    }
+}
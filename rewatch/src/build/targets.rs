@@ -0,0 +1,147 @@
+//! `rescript build --target`: only the given modules and everything they depend on are compiled.
+
+use super::build_types::{BuildState, Module, SourceType};
+use super::compile_universe::CompileReason;
+use ahash::{AHashMap, AHashSet};
+use anyhow::{Result, anyhow};
+use std::path::Path;
+
+/// Resolves every `--target` to a module name. A target is a source file (`.res`/`.resi`),
+/// relative to the working directory, or a module name as `rescript why` prints it.
+pub fn resolve(targets: &[String], build_state: &BuildState) -> Result<AHashSet<String>> {
+    targets
+        .iter()
+        .map(|target| {
+            if build_state.modules.contains_key(target) {
+                return Ok(target.clone());
+            }
+            Path::new(target)
+                .canonicalize()
+                .ok()
+                .and_then(|path| module_for_file(&path, build_state))
+                .ok_or_else(|| anyhow!("Could not find a module for target {target}"))
+        })
+        .collect()
+}
+
+fn module_for_file(path: &Path, build_state: &BuildState) -> Option<String> {
+    build_state.modules.iter().find_map(|(module_name, module)| {
+        let SourceType::SourceFile(source_file) = &module.source_type else {
+            return None;
+        };
+        let package = build_state.get_package(&module.package_name)?;
+        let is_source = package.path.join(&source_file.implementation.path) == path
+            || source_file
+                .interface
+                .as_ref()
+                .is_some_and(|interface| package.path.join(&interface.path) == path);
+        is_source.then(|| module_name.clone())
+    })
+}
+
+/// The targets and the modules they depend on, transitively.
+pub fn dependency_closure(
+    targets: &AHashSet<String>,
+    modules: &AHashMap<String, Module>,
+) -> AHashSet<String> {
+    let mut closure = targets.clone();
+    let mut frontier = targets.iter().cloned().collect::<Vec<String>>();
+    while let Some(module_name) = frontier.pop() {
+        let Some(module) = modules.get(&module_name) else {
+            continue;
+        };
+        for dep in &module.deps {
+            if closure.insert(dep.clone()) {
+                frontier.push(dep.clone());
+            }
+        }
+    }
+    closure
+}
+
+/// Removes the modules the targets don't depend on from `universe` and returns them. They have to
+/// be compiled by a later build.
+pub fn restrict_universe(
+    universe: &mut AHashMap<String, CompileReason>,
+    targets: &AHashSet<String>,
+    modules: &AHashMap<String, Module>,
+) -> Vec<String> {
+    let closure = dependency_closure(targets, modules);
+    let deferred = universe
+        .keys()
+        .filter(|module_name| !closure.contains(*module_name))
+        .cloned()
+        .collect::<Vec<String>>();
+    for module_name in &deferred {
+        universe.remove(module_name);
+    }
+    deferred
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::build_types::{CompileState, Implementation, ParseState, SourceFile};
+    use std::path::PathBuf;
+    use std::time::SystemTime;
+
+    fn module(deps: &[&str]) -> Module {
+        Module {
+            source_type: SourceType::SourceFile(SourceFile {
+                implementation: Implementation {
+                    path: PathBuf::from("src/Module.res"),
+                    parse_state: ParseState::Success,
+                    compile_state: CompileState::Success,
+                    last_modified: SystemTime::UNIX_EPOCH,
+                    parse_dirty: false,
+                    compile_warnings: None,
+                },
+                interface: None,
+            }),
+            deps: deps.iter().map(|dep| dep.to_string()).collect(),
+            dependents: AHashSet::new(),
+            package_name: "pkg".to_string(),
+            compile_dirty: true,
+            last_compiled_cmi: None,
+            last_compiled_cmt: None,
+            deps_dirty: false,
+            is_type_dev: false,
+        }
+    }
+
+    fn modules() -> AHashMap<String, Module> {
+        AHashMap::from([
+            ("Home".to_string(), module(&["Layout", "Api"])),
+            ("Layout".to_string(), module(&["Theme"])),
+            ("Theme".to_string(), module(&[])),
+            ("Api".to_string(), module(&[])),
+            ("Admin".to_string(), module(&["Layout"])),
+        ])
+    }
+
+    #[test]
+    fn closure_contains_the_transitive_dependencies() {
+        let targets = AHashSet::from(["Home".to_string()]);
+        let mut closure = dependency_closure(&targets, &modules())
+            .into_iter()
+            .collect::<Vec<_>>();
+        closure.sort();
+        assert_eq!(closure, vec!["Api", "Home", "Layout", "Theme"]);
+    }
+
+    #[test]
+    fn modules_outside_the_closure_are_deferred() {
+        let mut universe: AHashMap<String, CompileReason> = modules()
+            .into_keys()
+            .map(|module_name| (module_name, CompileReason::ParseDirty))
+            .collect();
+        let targets = AHashSet::from(["Layout".to_string()]);
+
+        let mut deferred = restrict_universe(&mut universe, &targets, &modules());
+        deferred.sort();
+        assert_eq!(deferred, vec!["Admin", "Api", "Home"]);
+        let mut remaining = universe.into_keys().collect::<Vec<_>>();
+        remaining.sort();
+        assert_eq!(remaining, vec!["Layout", "Theme"]);
+    }
+}
@@ -0,0 +1,358 @@
+//! Wall time of every bsc invocation (and js-post-build command) in a build.
+//!
+//! Each compile pass writes a summary to `lib/bs/build-timings.json`: the slowest modules, the
+//! critical path the scheduler prioritized, and how much parallelism was achieved. With
+//! `--timings chrome` the raw events are also written as a Chrome trace-event file
+//! (`lib/bs/build-trace.json`) that can be opened in `chrome://tracing` or Perfetto.
+
+use super::build_types::BuildCommandState;
+use super::packages;
+use crate::cli::TimingsFormat;
+use ahash::AHashMap;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How many modules `slowest_modules` lists.
+const SLOWEST_MODULES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Parse,
+    Compile,
+    Mlmap,
+    JsPostBuild,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingEvent {
+    pub module: String,
+    pub package: String,
+    pub phase: Phase,
+    /// Offset from the start of the build.
+    #[serde(rename = "start_ms", serialize_with = "serialize_ms")]
+    pub start: Duration,
+    #[serde(rename = "duration_ms", serialize_with = "serialize_ms")]
+    pub duration: Duration,
+    /// 0 for the main thread, otherwise the index of the rayon worker plus one.
+    pub thread: usize,
+}
+
+#[derive(Debug, Default)]
+struct Recording {
+    started: Option<Instant>,
+    events: Vec<TimingEvent>,
+    critical_path: Vec<String>,
+}
+
+/// Collects timing events from the worker threads of a build. Reset at the start of every
+/// incremental build.
+#[derive(Debug, Default)]
+pub struct Timings {
+    recording: Mutex<Recording>,
+}
+
+impl Timings {
+    pub fn start(&self) {
+        let mut recording = self.recording.lock().unwrap();
+        *recording = Recording {
+            started: Some(Instant::now()),
+            ..Default::default()
+        };
+    }
+
+    /// Run `f` and record how long it took.
+    pub fn record<T>(&self, module: &str, package: &str, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        let duration = start.elapsed();
+
+        let mut recording = self.recording.lock().unwrap();
+        let started = *recording.started.get_or_insert(start);
+        recording.events.push(TimingEvent {
+            module: module.to_string(),
+            package: package.to_string(),
+            phase,
+            start: start.saturating_duration_since(started),
+            duration,
+            thread: rayon::current_thread_index().map_or(0, |index| index + 1),
+        });
+        result
+    }
+
+    pub fn set_critical_path(&self, critical_path: Vec<String>) {
+        self.recording.lock().unwrap().critical_path = critical_path;
+    }
+
+    pub fn report(&self) -> TimingReport {
+        let recording = self.recording.lock().unwrap();
+        let wall_time = recording
+            .started
+            .map(|started| started.elapsed())
+            .unwrap_or_default();
+        TimingReport::new(wall_time, &recording.events, &recording.critical_path)
+    }
+
+    pub fn chrome_trace(&self) -> serde_json::Value {
+        chrome_trace(&self.recording.lock().unwrap().events)
+    }
+}
+
+fn serialize_ms<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    // Round to microseconds, that's as precise as these numbers get.
+    serializer.serialize_f64((duration.as_secs_f64() * 1_000_000.0).round() / 1000.0)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ModuleTiming {
+    pub module: String,
+    pub package: String,
+    #[serde(rename = "total_ms", serialize_with = "serialize_ms")]
+    pub total: Duration,
+    #[serde(rename = "parse_ms", serialize_with = "serialize_ms")]
+    pub parse: Duration,
+    #[serde(rename = "compile_ms", serialize_with = "serialize_ms")]
+    pub compile: Duration,
+    #[serde(rename = "mlmap_ms", serialize_with = "serialize_ms")]
+    pub mlmap: Duration,
+    #[serde(rename = "js_post_build_ms", serialize_with = "serialize_ms")]
+    pub js_post_build: Duration,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CriticalPath {
+    /// Compile time of the modules on the path, which no amount of parallelism can beat.
+    #[serde(rename = "compile_ms", serialize_with = "serialize_ms")]
+    pub compile: Duration,
+    /// From the first module to compile to the last one that depends on it.
+    pub modules: Vec<ModuleTiming>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimingReport {
+    #[serde(rename = "wall_time_ms", serialize_with = "serialize_ms")]
+    pub wall_time: Duration,
+    /// Sum of the durations of all events.
+    #[serde(rename = "busy_time_ms", serialize_with = "serialize_ms")]
+    pub busy_time: Duration,
+    /// Busy time divided by wall time: the average number of processes running at once.
+    pub parallelism: f64,
+    /// The largest number of processes that were running at the same time.
+    pub max_concurrency: usize,
+    pub slowest_modules: Vec<ModuleTiming>,
+    pub critical_path: CriticalPath,
+    pub events: Vec<TimingEvent>,
+}
+
+impl TimingReport {
+    fn new(wall_time: Duration, events: &[TimingEvent], critical_path: &[String]) -> Self {
+        let mut modules: AHashMap<&str, ModuleTiming> = AHashMap::new();
+        for event in events {
+            let timing = modules.entry(&event.module).or_insert_with(|| ModuleTiming {
+                module: event.module.clone(),
+                package: event.package.clone(),
+                ..Default::default()
+            });
+            timing.total += event.duration;
+            match event.phase {
+                Phase::Parse => timing.parse += event.duration,
+                Phase::Compile => timing.compile += event.duration,
+                Phase::Mlmap => timing.mlmap += event.duration,
+                Phase::JsPostBuild => timing.js_post_build += event.duration,
+            }
+        }
+
+        let critical_path_modules = critical_path
+            .iter()
+            .map(|module| {
+                modules
+                    .get(module.as_str())
+                    .cloned()
+                    .unwrap_or_else(|| ModuleTiming {
+                        module: module.clone(),
+                        ..Default::default()
+                    })
+            })
+            .collect::<Vec<ModuleTiming>>();
+
+        let mut slowest_modules = modules.into_values().collect::<Vec<ModuleTiming>>();
+        slowest_modules.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.module.cmp(&b.module)));
+        slowest_modules.truncate(SLOWEST_MODULES);
+
+        let busy_time = events.iter().map(|event| event.duration).sum::<Duration>();
+        let parallelism = if wall_time.is_zero() {
+            0.0
+        } else {
+            busy_time.as_secs_f64() / wall_time.as_secs_f64()
+        };
+
+        let mut events = events.to_vec();
+        events.sort_by_key(|event| event.start);
+
+        TimingReport {
+            wall_time,
+            busy_time,
+            parallelism,
+            max_concurrency: max_concurrency(&events),
+            slowest_modules,
+            critical_path: CriticalPath {
+                compile: critical_path_modules.iter().map(|module| module.compile).sum(),
+                modules: critical_path_modules,
+            },
+            events,
+        }
+    }
+}
+
+fn max_concurrency(events: &[TimingEvent]) -> usize {
+    // Ends sort before starts at the same instant, so back-to-back events don't overlap.
+    let mut edges = events
+        .iter()
+        .flat_map(|event| [(event.start, 1), (event.start + event.duration, -1)])
+        .collect::<Vec<(Duration, i64)>>();
+    edges.sort();
+    let mut running = 0;
+    let mut max = 0;
+    for (_, change) in edges {
+        running += change;
+        max = max.max(running);
+    }
+    max as usize
+}
+
+fn chrome_trace(events: &[TimingEvent]) -> serde_json::Value {
+    let trace_events = events
+        .iter()
+        .map(|event| {
+            serde_json::json!({
+                "name": event.module,
+                "cat": event.phase,
+                "ph": "X",
+                "ts": event.start.as_micros() as u64,
+                "dur": event.duration.as_micros() as u64,
+                "pid": 1,
+                "tid": event.thread,
+                "args": { "package": event.package },
+            })
+        })
+        .collect::<Vec<serde_json::Value>>();
+    serde_json::json!({ "traceEvents": trace_events, "displayTimeUnit": "ms" })
+}
+
+pub fn get_path(project_root: &Path) -> PathBuf {
+    packages::get_build_path(project_root).join("build-timings.json")
+}
+
+pub fn get_chrome_trace_path(project_root: &Path) -> PathBuf {
+    packages::get_build_path(project_root).join("build-trace.json")
+}
+
+fn write_json(path: &Path, value: &impl Serialize) {
+    let result = serde_json::to_string_pretty(value)
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| std::fs::write(path, contents).map_err(anyhow::Error::from));
+    if let Err(err) = result {
+        log::error!("Failed to write {}: {}", path.display(), err);
+    }
+}
+
+pub fn write(build_state: &BuildCommandState) {
+    let project_root = build_state.project_context.get_root_path();
+    write_json(&get_path(project_root), &build_state.timings.report());
+    if build_state.timings_format == Some(TimingsFormat::Chrome) {
+        write_json(
+            &get_chrome_trace_path(project_root),
+            &build_state.timings.chrome_trace(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(module: &str, phase: Phase, start_ms: u64, duration_ms: u64) -> TimingEvent {
+        TimingEvent {
+            module: module.to_string(),
+            package: "pkg".to_string(),
+            phase,
+            start: Duration::from_millis(start_ms),
+            duration: Duration::from_millis(duration_ms),
+            thread: 1,
+        }
+    }
+
+    #[test]
+    fn report_sums_phases_per_module_and_ranks_the_slowest() {
+        let events = vec![
+            event("A", Phase::Parse, 0, 10),
+            event("B", Phase::Parse, 0, 5),
+            event("A", Phase::Compile, 10, 30),
+            event("B", Phase::Compile, 40, 20),
+            event("B", Phase::JsPostBuild, 60, 20),
+        ];
+        let report = TimingReport::new(
+            Duration::from_millis(80),
+            &events,
+            &["A".to_string(), "B".to_string()],
+        );
+
+        assert_eq!(
+            report
+                .slowest_modules
+                .iter()
+                .map(|module| (module.module.as_str(), module.total.as_millis()))
+                .collect::<Vec<_>>(),
+            vec![("B", 45), ("A", 40)]
+        );
+        assert_eq!(report.critical_path.compile, Duration::from_millis(50));
+        assert_eq!(report.busy_time, Duration::from_millis(85));
+        assert_eq!(report.max_concurrency, 2);
+        assert!((report.parallelism - 85.0 / 80.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn back_to_back_events_do_not_overlap() {
+        let events = vec![
+            event("A", Phase::Compile, 0, 10),
+            event("B", Phase::Compile, 10, 10),
+        ];
+        assert_eq!(max_concurrency(&events), 1);
+    }
+
+    #[test]
+    fn serializes_durations_in_milliseconds() {
+        let value = serde_json::to_value(event("A", Phase::JsPostBuild, 1, 2)).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "module": "A",
+                "package": "pkg",
+                "phase": "js_post_build",
+                "start_ms": 1.0,
+                "duration_ms": 2.0,
+                "thread": 1,
+            })
+        );
+    }
+
+    #[test]
+    fn chrome_trace_uses_complete_events_in_microseconds() {
+        let trace = chrome_trace(&[event("A", Phase::Compile, 1, 2)]);
+        assert_eq!(
+            trace["traceEvents"][0],
+            serde_json::json!({
+                "name": "A",
+                "cat": "compile",
+                "ph": "X",
+                "ts": 1000,
+                "dur": 2000,
+                "pid": 1,
+                "tid": 1,
+                "args": { "package": "pkg" },
+            })
+        );
+    }
+}
@@ -11,11 +11,19 @@
 //
 // However, we may want to revisit the decision to use clap after the v12 release.
 
-use std::{env, ffi::OsString, ops::Deref, path::Path};
+use std::{
+    env,
+    ffi::OsString,
+    num::{NonZeroU64, NonZeroUsize},
+    ops::Deref,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use clap::{Args, CommandFactory, Parser, Subcommand, error::ErrorKind};
 use clap_verbosity_flag::InfoLevel;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 fn parse_regex(s: &str) -> Result<Regex, regex::Error> {
     Regex::new(s)
@@ -192,6 +200,11 @@ pub struct AfterBuildArg {
     /// E.g., play a sound or run a test suite when done compiling.
     #[arg(short, long)]
     pub after_build: Option<String>,
+
+    /// Run an additional command when the build fails.
+    /// E.g., send a notification when compiling breaks.
+    #[arg(long)]
+    pub after_build_failure: Option<String>,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -236,6 +249,146 @@ impl FeaturesArg {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MessageFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimingsFormat {
+    Chrome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EventsFormat {
+    /// One JSON object per event on stdout, instead of the progress output.
+    StdoutJson,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum WatchBackend {
+    /// The file system notifications of the platform (inotify, FSEvents, ReadDirectoryChangesW).
+    #[default]
+    Native,
+    /// Scan the watched folders for changed modification times. Slower, but also works on file
+    /// systems that don't send notifications, like Docker bind mounts, WSL shared folders and NFS.
+    Poll,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz DOT.
+    Dot,
+    /// Mermaid flowchart, e.g. for Markdown docs.
+    Mermaid,
+    /// Nodes and edges as JSON.
+    Json,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct MessageFormatArg {
+    /// How to report compiler errors and warnings.
+    /// `json` prints one JSON object per diagnostic (file, range, severity, warning number,
+    /// package and message) on stdout and disables the progress output.
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = MessageFormat::Human)]
+    pub message_format: MessageFormat,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ExplainArg {
+    /// Print why each module is compiled: changed source, dirty/deleted dependency, expired
+    /// .cmi of a dependency, or a compiler/config change since the last build.
+    #[arg(long, default_value_t = false)]
+    pub explain: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct KeepGoingArg {
+    /// When a module fails to compile, keep compiling every module that doesn't depend on it and
+    /// report all errors at the end, instead of stopping at the first failing dependency wave.
+    #[arg(long, default_value_t = false)]
+    pub keep_going: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct TimingsArg {
+    /// Per-module build timings are always written to `lib/bs/build-timings.json`. With `chrome`,
+    /// a Chrome trace-event file is also written to `lib/bs/build-trace.json`.
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub timings: Option<TimingsFormat>,
+}
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct EventsArg {
+    /// Stream structured build events (files changed, build started, parse finished, module
+    /// compiled, diagnostics, build finished) for editors and tooling.
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub events: Option<EventsFormat>,
+
+    /// Stream the same events as JSON lines to every client connected to a Unix domain socket
+    /// created at this path.
+    #[arg(long, value_name = "PATH")]
+    pub events_socket: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct WatchBackendArg {
+    /// How file changes are detected.
+    #[arg(long, value_enum, default_value_t = WatchBackend::Native)]
+    pub watch_backend: WatchBackend,
+
+    /// Milliseconds between two scans of the watched folders with `--watch-backend poll`.
+    #[arg(long, value_name = "MS", default_value_t = NonZeroU64::new(1000).unwrap())]
+    pub poll_interval: NonZeroU64,
+}
+
+impl Default for WatchBackendArg {
+    fn default() -> Self {
+        Self {
+            watch_backend: WatchBackend::Native,
+            poll_interval: NonZeroU64::new(1000).unwrap(),
+        }
+    }
+}
+
+impl WatchBackendArg {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval.get())
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct JobsArg {
+    /// Maximum number of compiler processes to run at the same time while parsing, compiling
+    /// and formatting. Falls back to the `RESCRIPT_JOBS` environment variable, and to the number
+    /// of CPUs when neither is set.
+    #[arg(short = 'j', long)]
+    pub jobs: Option<NonZeroUsize>,
+}
+
+impl JobsArg {
+    /// The job limit from `--jobs`, or else from `RESCRIPT_JOBS`. `None` means no limit was
+    /// configured.
+    pub fn resolve(&self) -> anyhow::Result<Option<NonZeroUsize>> {
+        resolve_jobs(self.jobs, env::var("RESCRIPT_JOBS").ok().as_deref())
+    }
+}
+
+fn resolve_jobs(jobs: Option<NonZeroUsize>, env_jobs: Option<&str>) -> anyhow::Result<Option<NonZeroUsize>> {
+    match (jobs, env_jobs.map(str::trim)) {
+        (Some(jobs), _) => Ok(Some(jobs)),
+        (None, None | Some("")) => Ok(None),
+        (None, Some(value)) => value
+            .parse::<NonZeroUsize>()
+            .map(Some)
+            .map_err(|_| anyhow::anyhow!("RESCRIPT_JOBS must be a positive number, got '{value}'")),
+    }
+}
+
 #[derive(Args, Debug, Clone)]
 pub struct BuildArgs {
     #[command(flatten)]
@@ -253,10 +406,57 @@ pub struct BuildArgs {
     #[command(flatten)]
     pub features: FeaturesArg,
 
+    #[command(flatten)]
+    pub message_format: MessageFormatArg,
+
+    #[command(flatten)]
+    pub explain: ExplainArg,
+
+    #[command(flatten)]
+    pub keep_going: KeepGoingArg,
+
+    #[command(flatten)]
+    pub timings: TimingsArg,
+
+    #[command(flatten)]
+    pub jobs: JobsArg,
+
+    /// Print the files that would be parsed, the modules that would be compiled (in scheduler
+    /// order) and the stale artifacts that would be deleted, without running the compiler or
+    /// changing anything in `lib/`
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+
+    /// Only type-check: write `.cmi` and `.cmt` files, but no JS, and skip `js-post-build`.
+    /// The next build without `--check` compiles the checked modules again to write their JS
+    #[arg(long, default_value_t = false)]
+    pub check: bool,
+
+    /// Only compile this module and the modules it depends on. A source file, relative to the
+    /// working directory, or a module name. Can be repeated. Other changed modules are compiled
+    /// by the next build without `--target`
+    #[arg(long = "target", value_name = "FILE_OR_MODULE")]
+    pub targets: Vec<String>,
+
+    /// Only compile the modules affected by the changes since this git revision: modules whose
+    /// source or package `rescript.json` differs from it (per `git diff --name-only`) and their
+    /// dependents, plus the modules they depend on. Other changed modules are compiled by the next
+    /// build without `--since`
+    #[arg(long, value_name = "GIT_REF", conflicts_with = "targets")]
+    pub since: Option<String>,
+
+    /// Exit with a non-zero code when the `--after-build` command fails
+    #[arg(long, default_value_t = false)]
+    pub after_build_fail_build: bool,
+
     /// Disable output timing
     #[arg(short, long, default_value_t = false, num_args = 0..=1)]
     pub no_timing: bool,
 
+    /// Build in this process even when a `rescript daemon` is running for the project
+    #[arg(long, default_value_t = false)]
+    pub no_daemon: bool,
+
     /// Skip dev-dependencies and dev sources (type: "dev")
     #[arg(long, default_value_t = false)]
     pub prod: bool,
@@ -420,6 +620,109 @@ mod tests {
         }
     }
 
+    #[test]
+    fn daemon_command_is_parsed() {
+        let cli = parse(&["rescript", "daemon", "--prod", "my-project"]).expect("expected daemon command");
+
+        match cli.command {
+            Command::Daemon(daemon_args) => {
+                assert!(daemon_args.prod);
+                assert_eq!(daemon_args.folder.folder, "my-project");
+            }
+            other => panic!("expected daemon command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn build_no_daemon_flag_is_parsed() {
+        let cli = parse(&["rescript", "--no-daemon"]).expect("expected default build command");
+
+        match cli.command {
+            Command::Build(build_args) => assert!(build_args.no_daemon),
+            other => panic!("expected build command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn watch_events_flags_are_parsed() {
+        let cli = parse(&[
+            "rescript",
+            "watch",
+            "--events",
+            "stdout-json",
+            "--events-socket",
+            "/tmp/rescript-events.sock",
+        ])
+        .expect("expected watch command");
+
+        match cli.command {
+            Command::Watch(watch_args) => {
+                assert_eq!(watch_args.events.events, Some(EventsFormat::StdoutJson));
+                assert_eq!(
+                    watch_args.events.events_socket,
+                    Some(PathBuf::from("/tmp/rescript-events.sock"))
+                );
+            }
+            other => panic!("expected watch command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn watch_backend_defaults_to_native() {
+        let cli = parse(&["rescript", "watch"]).expect("expected watch command");
+        match cli.command {
+            Command::Watch(watch_args) => {
+                assert_eq!(watch_args.watch_backend.watch_backend, WatchBackend::Native);
+                assert_eq!(watch_args.watch_backend.poll_interval(), Duration::from_secs(1));
+            }
+            other => panic!("expected watch command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn watch_poll_backend_is_parsed() {
+        let cli = parse(&[
+            "rescript",
+            "watch",
+            "--watch-backend",
+            "poll",
+            "--poll-interval",
+            "250",
+        ])
+        .expect("expected watch command");
+        match cli.command {
+            Command::Watch(watch_args) => {
+                assert_eq!(watch_args.watch_backend.watch_backend, WatchBackend::Poll);
+                assert_eq!(
+                    watch_args.watch_backend.poll_interval(),
+                    Duration::from_millis(250)
+                );
+            }
+            other => panic!("expected watch command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn watch_rejects_zero_poll_interval() {
+        let err = parse(&[
+            "rescript",
+            "watch",
+            "--watch-backend",
+            "poll",
+            "--poll-interval",
+            "0",
+        ])
+        .expect_err("expected parse failure");
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn build_rejects_events_flag() {
+        let err =
+            parse(&["rescript", "build", "--events", "stdout-json"]).expect_err("expected parse failure");
+        assert_eq!(err.kind(), ErrorKind::UnknownArgument);
+    }
+
     #[test]
     fn watch_clear_screen_flag_is_parsed() {
         let cli = parse(&["rescript", "watch", "--clear-screen"]).expect("expected watch command");
@@ -520,6 +823,337 @@ mod tests {
         }
     }
 
+    // --message-format flag tests.
+    #[test]
+    fn build_message_format_defaults_to_human() {
+        let cli = parse(&["rescript", "build"]).expect("expected build command");
+        match cli.command {
+            Command::Build(build_args) => assert_eq!(*build_args.message_format, MessageFormat::Human),
+            other => panic!("expected build command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn build_message_format_json_is_parsed() {
+        let cli = parse(&["rescript", "build", "--message-format", "json"]).expect("expected build command");
+        match cli.command {
+            Command::Build(build_args) => assert_eq!(*build_args.message_format, MessageFormat::Json),
+            other => panic!("expected build command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn watch_message_format_json_is_parsed() {
+        let cli = parse(&["rescript", "watch", "--message-format", "json"]).expect("expected watch command");
+        match cli.command {
+            Command::Watch(watch_args) => assert_eq!(*watch_args.message_format, MessageFormat::Json),
+            other => panic!("expected watch command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn message_format_rejects_unknown_value() {
+        let err = parse(&["rescript", "build", "--message-format", "xml"])
+            .expect_err("expected unknown --message-format to fail parsing");
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+    }
+
+    // --explain flag tests.
+    #[test]
+    fn explain_flag_is_parsed_for_build_and_watch() {
+        let cli = parse(&["rescript", "build", "--explain"]).expect("expected build command");
+        match cli.command {
+            Command::Build(build_args) => assert!(*build_args.explain),
+            other => panic!("expected build command, got {other:?}"),
+        }
+
+        let cli = parse(&["rescript", "watch", "--explain"]).expect("expected watch command");
+        match cli.command {
+            Command::Watch(watch_args) => assert!(*watch_args.explain),
+            other => panic!("expected watch command, got {other:?}"),
+        }
+    }
+
+    // --dry-run flag tests.
+    #[test]
+    fn dry_run_flag_is_parsed_for_build() {
+        let cli = parse(&["rescript", "build", "--dry-run"]).expect("expected build command");
+        match cli.command {
+            Command::Build(build_args) => assert!(build_args.dry_run),
+            other => panic!("expected build command, got {other:?}"),
+        }
+
+        let cli = parse(&["rescript", "build"]).expect("expected build command");
+        match cli.command {
+            Command::Build(build_args) => assert!(!build_args.dry_run),
+            other => panic!("expected build command, got {other:?}"),
+        }
+    }
+
+    // --after-build-failure and --after-build-fail-build flag tests.
+    #[test]
+    fn after_build_failure_flags_are_parsed_for_build() {
+        let cli = parse(&[
+            "rescript",
+            "build",
+            "--after-build-failure",
+            "notify-send 'build failed'",
+            "--after-build-fail-build",
+        ])
+        .expect("expected build command");
+        match cli.command {
+            Command::Build(build_args) => {
+                assert_eq!(
+                    build_args.after_build.after_build_failure.as_deref(),
+                    Some("notify-send 'build failed'")
+                );
+                assert!(build_args.after_build_fail_build);
+            }
+            other => panic!("expected build command, got {other:?}"),
+        }
+
+        let cli =
+            parse(&["rescript", "watch", "--after-build-failure", "true"]).expect("expected watch command");
+        match cli.command {
+            Command::Watch(watch_args) => {
+                assert_eq!(
+                    watch_args.after_build.after_build_failure.as_deref(),
+                    Some("true")
+                )
+            }
+            other => panic!("expected watch command, got {other:?}"),
+        }
+    }
+
+    // --target flag tests.
+    #[test]
+    fn target_flag_is_repeatable() {
+        let cli = parse(&[
+            "rescript",
+            "build",
+            "--target",
+            "src/pages/Home.res",
+            "--target",
+            "Admin",
+        ])
+        .expect("expected build command");
+        match cli.command {
+            Command::Build(build_args) => assert_eq!(build_args.targets, vec!["src/pages/Home.res", "Admin"]),
+            other => panic!("expected build command, got {other:?}"),
+        }
+    }
+
+    // --since flag tests.
+    #[test]
+    fn since_is_parsed_for_build_and_affected() {
+        let cli = parse(&["rescript", "build", "--since", "main"]).expect("expected build command");
+        match cli.command {
+            Command::Build(build_args) => assert_eq!(build_args.since.as_deref(), Some("main")),
+            other => panic!("expected build command, got {other:?}"),
+        }
+
+        let cli = parse(&["rescript", "affected", "--since", "HEAD~3"]).expect("expected affected command");
+        match cli.command {
+            Command::Affected { since, folder, .. } => {
+                assert_eq!(since, "HEAD~3");
+                assert_eq!(folder, ".");
+            }
+            other => panic!("expected affected command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn since_conflicts_with_target() {
+        let err = parse(&["rescript", "build", "--since", "main", "--target", "App"])
+            .expect_err("expected --since with --target to fail");
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    // --keep-going flag tests.
+    #[test]
+    fn keep_going_flag_is_parsed_for_build_and_watch() {
+        let cli = parse(&["rescript", "build", "--keep-going"]).expect("expected build command");
+        match cli.command {
+            Command::Build(build_args) => assert!(*build_args.keep_going),
+            other => panic!("expected build command, got {other:?}"),
+        }
+
+        let cli = parse(&["rescript", "watch", "--keep-going"]).expect("expected watch command");
+        match cli.command {
+            Command::Watch(watch_args) => assert!(*watch_args.keep_going),
+            other => panic!("expected watch command, got {other:?}"),
+        }
+    }
+
+    // --timings flag tests.
+    #[test]
+    fn timings_flag_is_parsed_for_build_and_watch() {
+        let cli = parse(&["rescript", "build", "--timings", "chrome"]).expect("expected build command");
+        match cli.command {
+            Command::Build(build_args) => assert_eq!(*build_args.timings, Some(TimingsFormat::Chrome)),
+            other => panic!("expected build command, got {other:?}"),
+        }
+
+        let cli = parse(&["rescript", "watch", "--timings=chrome"]).expect("expected watch command");
+        match cli.command {
+            Command::Watch(watch_args) => assert_eq!(*watch_args.timings, Some(TimingsFormat::Chrome)),
+            other => panic!("expected watch command, got {other:?}"),
+        }
+
+        let cli = parse(&["rescript", "build"]).expect("expected build command");
+        match cli.command {
+            Command::Build(build_args) => assert_eq!(*build_args.timings, None),
+            other => panic!("expected build command, got {other:?}"),
+        }
+    }
+
+    // --jobs flag tests.
+    #[test]
+    fn jobs_flag_is_parsed_for_build_watch_and_format() {
+        let cli = parse(&["rescript", "build", "-j", "2"]).expect("expected build command");
+        match cli.command {
+            Command::Build(build_args) => assert_eq!(build_args.jobs.jobs, NonZeroUsize::new(2)),
+            other => panic!("expected build command, got {other:?}"),
+        }
+
+        let cli = parse(&["rescript", "watch", "--jobs", "3"]).expect("expected watch command");
+        match cli.command {
+            Command::Watch(watch_args) => assert_eq!(watch_args.jobs.jobs, NonZeroUsize::new(3)),
+            other => panic!("expected watch command, got {other:?}"),
+        }
+
+        let cli = parse(&["rescript", "format", "-j", "1"]).expect("expected format command");
+        match cli.command {
+            Command::Format { jobs, .. } => assert_eq!(jobs.jobs, NonZeroUsize::new(1)),
+            other => panic!("expected format command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn jobs_flag_is_accepted_without_subcommand() {
+        let cli = parse(&["rescript", "-j", "4"]).expect("expected default build command");
+        match cli.command {
+            Command::Build(build_args) => assert_eq!(build_args.jobs.jobs, NonZeroUsize::new(4)),
+            other => panic!("expected build command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn jobs_flag_rejects_zero() {
+        let err = parse(&["rescript", "build", "--jobs", "0"]).expect_err("expected --jobs 0 to fail");
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn jobs_flag_takes_precedence_over_env() {
+        assert_eq!(
+            resolve_jobs(NonZeroUsize::new(2), Some("8")).unwrap(),
+            NonZeroUsize::new(2)
+        );
+        assert_eq!(resolve_jobs(None, Some(" 8 ")).unwrap(), NonZeroUsize::new(8));
+        assert_eq!(resolve_jobs(None, Some("")).unwrap(), None);
+        assert_eq!(resolve_jobs(None, None).unwrap(), None);
+        assert!(resolve_jobs(None, Some("0")).is_err());
+        assert!(resolve_jobs(None, Some("many")).is_err());
+    }
+
+    // graph command tests.
+    #[test]
+    fn graph_defaults_to_dot_for_current_folder() {
+        let cli = parse(&["rescript", "graph"]).expect("expected graph command");
+        match cli.command {
+            Command::Graph {
+                folder,
+                format,
+                package_level,
+                root,
+                depth,
+                ..
+            } => {
+                assert_eq!(folder.folder, ".");
+                assert_eq!(format, GraphFormat::Dot);
+                assert!(!package_level);
+                assert!(root.is_none());
+                assert!(depth.is_none());
+            }
+            other => panic!("expected graph command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn graph_filters_are_parsed() {
+        let cli = parse(&[
+            "rescript",
+            "graph",
+            "--format",
+            "mermaid",
+            "--package",
+            "@scope/ui",
+            "--root",
+            "App",
+            "--depth",
+            "2",
+        ])
+        .expect("expected graph command");
+        match cli.command {
+            Command::Graph {
+                format,
+                package,
+                root,
+                depth,
+                ..
+            } => {
+                assert_eq!(format, GraphFormat::Mermaid);
+                assert_eq!(package.as_deref(), Some("@scope/ui"));
+                assert_eq!(root.as_deref(), Some("App"));
+                assert_eq!(depth, Some(2));
+            }
+            other => panic!("expected graph command, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn graph_depth_requires_root() {
+        let err = parse(&["rescript", "graph", "--depth", "2"])
+            .expect_err("expected --depth without --root to fail");
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+    }
+
+    // why command tests.
+    #[test]
+    fn why_accepts_one_or_two_modules() {
+        let cli = parse(&["rescript", "why", "App"]).expect("expected why command");
+        match cli.command {
+            Command::Why {
+                module,
+                dependency,
+                folder,
+                ..
+            } => {
+                assert_eq!(module, "App");
+                assert!(dependency.is_none());
+                assert_eq!(folder.folder, ".");
+            }
+            other => panic!("expected why command, got {other:?}"),
+        }
+
+        let cli =
+            parse(&["rescript", "why", "App", "Ui.Button", "packages/app"]).expect("expected why command");
+        match cli.command {
+            Command::Why {
+                module,
+                dependency,
+                folder,
+                ..
+            } => {
+                assert_eq!(module, "App");
+                assert_eq!(dependency.as_deref(), Some("Ui.Button"));
+                assert_eq!(folder.folder, "packages/app");
+            }
+            other => panic!("expected why command, got {other:?}"),
+        }
+    }
+
     #[test]
     fn build_features_flag_strips_whitespace() {
         let cli = parse(&["rescript", "build", "--features", " native , experimental "])
@@ -551,6 +1185,27 @@ pub struct WatchArgs {
     #[command(flatten)]
     pub features: FeaturesArg,
 
+    #[command(flatten)]
+    pub message_format: MessageFormatArg,
+
+    #[command(flatten)]
+    pub explain: ExplainArg,
+
+    #[command(flatten)]
+    pub keep_going: KeepGoingArg,
+
+    #[command(flatten)]
+    pub timings: TimingsArg,
+
+    #[command(flatten)]
+    pub jobs: JobsArg,
+
+    #[command(flatten)]
+    pub events: EventsArg,
+
+    #[command(flatten)]
+    pub watch_backend: WatchBackendArg,
+
     /// Clear terminal screen before each rebuild in interactive watch mode.
     #[arg(long, default_value_t = false)]
     pub clear_screen: bool,
@@ -560,6 +1215,31 @@ pub struct WatchArgs {
     pub prod: bool,
 }
 
+#[derive(Args, Debug, Clone)]
+pub struct DaemonArgs {
+    #[command(flatten)]
+    pub folder: FolderArg,
+
+    #[command(flatten)]
+    pub filter: FilterArg,
+
+    #[command(flatten)]
+    pub warn_error: WarnErrorArg,
+
+    #[command(flatten)]
+    pub features: FeaturesArg,
+
+    #[command(flatten)]
+    pub jobs: JobsArg,
+
+    #[command(flatten)]
+    pub watch_backend: WatchBackendArg,
+
+    /// Skip dev-dependencies and dev sources (type: "dev")
+    #[arg(long, default_value_t = false)]
+    pub prod: bool,
+}
+
 impl From<BuildArgs> for WatchArgs {
     fn from(build_args: BuildArgs) -> Self {
         Self {
@@ -568,6 +1248,13 @@ impl From<BuildArgs> for WatchArgs {
             after_build: build_args.after_build,
             warn_error: build_args.warn_error,
             features: build_args.features,
+            message_format: build_args.message_format,
+            explain: build_args.explain,
+            keep_going: build_args.keep_going,
+            timings: build_args.timings,
+            jobs: build_args.jobs,
+            events: EventsArg::default(),
+            watch_backend: WatchBackendArg::default(),
             clear_screen: false,
             prod: build_args.prod,
         }
@@ -580,6 +1267,12 @@ pub enum Command {
     Build(BuildArgs),
     /// Build, then start a watcher
     Watch(WatchArgs),
+    /// Build, then keep the build state in memory and serve `rescript build` from it
+    ///
+    /// Listens on `lib/daemon.sock`. A `rescript build` of the project with the same --filter,
+    /// --warn-error, --prod and --features hands its build to the daemon, which only rebuilds
+    /// what changed. Stop it with Ctrl-C or by removing `lib/watch.lock`.
+    Daemon(DaemonArgs),
     /// Clean the build artifacts
     Clean {
         #[command(flatten)]
@@ -608,6 +1301,75 @@ pub enum Command {
         /// Files to format. If no files are provided, all files are formatted.
         #[arg(group = "format_input_mode")]
         files: Vec<String>,
+
+        #[command(flatten)]
+        jobs: JobsArg,
+    },
+    /// Export the module dependency graph.
+    ///
+    /// Edges point from a module to the modules it depends on.
+    Graph {
+        #[command(flatten)]
+        folder: FolderArg,
+
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+
+        /// Only include modules of this package.
+        #[arg(long)]
+        package: Option<String>,
+
+        /// Collapse modules into a package-level graph.
+        #[arg(long, default_value_t = false)]
+        package_level: bool,
+
+        /// Only include this module and the modules it depends on.
+        #[arg(long)]
+        root: Option<String>,
+
+        /// Limit how many dependency edges to follow from --root.
+        #[arg(long, requires = "root")]
+        depth: Option<usize>,
+
+        /// Skip dev-dependencies and dev sources (type: "dev")
+        #[arg(long, default_value_t = false)]
+        prod: bool,
+    },
+    /// Explain why a module depends on another one, or why it was compiled in the last build.
+    ///
+    /// With two modules, prints the shortest dependency chain from MODULE to DEPENDENCY.
+    /// With one module, prints the chain of dependencies that pulled it into the last build.
+    Why {
+        /// Module name, e.g. `App` or `MyNamespace.Button`.
+        module: String,
+
+        /// Module that MODULE (transitively) depends on.
+        dependency: Option<String>,
+
+        #[command(flatten)]
+        folder: FolderArg,
+
+        /// Skip dev-dependencies and dev sources (type: "dev")
+        #[arg(long, default_value_t = false)]
+        prod: bool,
+    },
+    /// List the modules affected by the changes since a git revision.
+    ///
+    /// Prints the modules whose source or package `rescript.json` differs from GIT_REF, per
+    /// `git diff --name-only`, and every module that depends on them, one per line.
+    Affected {
+        /// Git revision to compare the working tree with, e.g. `main` or `HEAD~3`.
+        #[arg(long, value_name = "GIT_REF")]
+        since: String,
+
+        /// Path to the project or subproject. This folder must contain a rescript.json file.
+        #[arg(long, default_value = ".")]
+        folder: String,
+
+        /// Skip dev-dependencies and dev sources (type: "dev")
+        #[arg(long, default_value_t = false)]
+        prod: bool,
     },
     /// Print the compiler arguments for a ReScript source file.
     CompilerArgs {
@@ -662,3 +1424,35 @@ impl Deref for FeaturesArg {
         &self.features
     }
 }
+
+impl Deref for KeepGoingArg {
+    type Target = bool;
+
+    fn deref(&self) -> &Self::Target {
+        &self.keep_going
+    }
+}
+
+impl Deref for TimingsArg {
+    type Target = Option<TimingsFormat>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.timings
+    }
+}
+
+impl Deref for ExplainArg {
+    type Target = bool;
+
+    fn deref(&self) -> &Self::Target {
+        &self.explain
+    }
+}
+
+impl Deref for MessageFormatArg {
+    type Target = MessageFormat;
+
+    fn deref(&self) -> &Self::Target {
+        &self.message_format
+    }
+}
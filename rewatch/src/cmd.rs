@@ -1,56 +1,237 @@
+use crate::build::events::{Event, EventSink, OutputStream};
+use crate::build::summary::BuildSummary;
+use crate::cli::AfterBuildArg;
 use crate::helpers::emojis::*;
+use anyhow::{Context, Result, anyhow, bail};
 use console::style;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read};
 use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
 use std::time::Instant;
 
-pub fn run(command_string: String) {
+/// Runs the `--after-build` command after a successful build, with what the build did in its
+/// environment (see `BuildSummary`), or the `--after-build-failure` command after a failed one,
+/// with `RESCRIPT_OUTCOME=failed`. `summary` is `None` when the build failed.
+pub fn run_after_build(
+    after_build: &AfterBuildArg,
+    summary: Option<&BuildSummary>,
+    events: Option<&EventSink>,
+) -> Result<()> {
+    match (
+        summary,
+        &after_build.after_build,
+        &after_build.after_build_failure,
+    ) {
+        (Some(summary), Some(command), _) => run(command, summary.env_vars(), events),
+        (None, _, Some(command)) => run(command, BuildSummary::failure_env_vars(), events),
+        _ => Ok(()),
+    }
+}
+
+/// Runs an after-build command and fails when it can't be started or exits unsuccessfully.
+/// Arguments are split like a POSIX shell does, so they can be quoted. For pipes or variable
+/// expansion, run the command through a shell: `sh -c '...'`.
+///
+/// Both pipes are read at the same time and every line is printed as soon as it arrives, prefixed
+/// with the pipe it came from, and sent to `events`.
+pub fn run(
+    command_string: &str,
+    envs: Vec<(&'static str, String)>,
+    events: Option<&EventSink>,
+) -> Result<()> {
+    let parsed_command = shlex::split(command_string)
+        .filter(|words| !words.is_empty())
+        .ok_or_else(|| anyhow!("Could not parse the after-build command: {command_string}"))?;
     let start_subcommand = Instant::now();
+    // Progress messages go to stdout too, which must only contain events then.
+    let show_progress = !events.is_some_and(EventSink::writes_to_stdout);
 
-    log::info!(
-        "{} {}Running subcommand... \n{}\n",
-        style("[...]").bold().dim(),
-        COMMAND,
-        style("────────"),
-    );
+    if show_progress {
+        log::info!(
+            "{} {}Running subcommand... \n{}\n",
+            style("[...]").bold().dim(),
+            COMMAND,
+            style("────────"),
+        );
+    }
 
-    let parsed_command = command_string.split_whitespace().collect::<Vec<&str>>();
     let (command, params) = parsed_command.split_at(1);
 
-    let mut cmd = Command::new(command[0])
+    let mut cmd = Command::new(&command[0])
         .args(params)
+        .envs(envs)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
-        .expect("failed to execute process");
+        .with_context(|| format!("Could not run the after-build command `{}`", command[0]))?;
 
     {
-        let stdout = cmd.stdout.as_mut().unwrap();
-        let stderr = cmd.stderr.as_mut().unwrap();
+        let (sender, lines) = mpsc::channel();
+        if let Some(stdout) = cmd.stdout.take() {
+            forward_lines(stdout, OutputStream::Stdout, sender.clone());
+        }
+        if let Some(stderr) = cmd.stderr.take() {
+            forward_lines(stderr, OutputStream::Stderr, sender.clone());
+        }
+        drop(sender);
 
-        let stdout_reader = BufReader::new(stdout);
-        let stderr_reader = BufReader::new(stderr);
+        // Ends once the command closed both pipes.
+        for (stream, line) in lines {
+            print_line(stream, &line, events);
+        }
+
+        let subcommand_duration = start_subcommand.elapsed();
+        if show_progress {
+            log::info!(
+                "{}{} {}Ran subcommand in {:.2}s",
+                LINE_CLEAR,
+                style("[...]").bold().dim(),
+                COMMAND,
+                subcommand_duration.as_secs_f64(),
+            );
+        }
+    }
 
-        let stdout_lines = stdout_reader.lines();
-        let std_err = stderr_reader.lines();
+    let status = cmd
+        .wait()
+        .with_context(|| format!("Could not wait for the after-build command `{command_string}`"))?;
+    if !status.success() {
+        bail!("The after-build command `{command_string}` failed with {status}");
+    }
+    Ok(())
+}
 
-        for line in stdout_lines {
-            println!("{}", line.unwrap());
+/// Sends the lines written to `pipe` from a thread of their own, so that a command which fills
+/// one pipe while nobody reads it can't block.
+fn forward_lines(
+    pipe: impl Read + Send + 'static,
+    stream: OutputStream,
+    sender: Sender<(OutputStream, String)>,
+) {
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).split(b'\n') {
+            let Ok(line) = line else {
+                return;
+            };
+            let line = String::from_utf8_lossy(&line);
+            if sender
+                .send((stream, line.trim_end_matches('\r').to_string()))
+                .is_err()
+            {
+                return;
+            }
         }
+    });
+}
 
-        for line in std_err {
-            eprintln!("{}", line.unwrap());
+fn print_line(stream: OutputStream, line: &str, events: Option<&EventSink>) {
+    if let Some(events) = events {
+        events.emit(&Event::AfterBuildOutput { stream, line });
+    }
+    match stream {
+        // The line is already part of the event stream on stdout.
+        OutputStream::Stdout if events.is_some_and(EventSink::writes_to_stdout) => (),
+        OutputStream::Stdout => println!("{} {line}", style("[out]").dim()),
+        OutputStream::Stderr => eprintln!("{} {line}", style("[err]").dim()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn reports_the_exit_status_of_the_command() {
+        assert!(run("true", Vec::new(), None).is_ok());
+        let err = run("sh -c 'exit 3'", Vec::new(), None).expect_err("the command should fail");
+        assert!(err.to_string().contains("exit status: 3"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_both_pipes_at_the_same_time() {
+        // Writes more to stderr than a pipe buffers before closing stdout.
+        let command = "sh -c 'i=0; while [ $i -lt 20000 ]; do echo err >&2; i=$((i+1)); done; echo out'";
+        assert!(run(command, Vec::new(), None).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn forwards_output_to_the_event_stream() {
+        use std::os::unix::net::UnixStream;
+
+        let temp_dir = tempfile::TempDir::new().expect("temp dir should be created");
+        let path = temp_dir.path().join("events.sock");
+        let sink = EventSink::new(None, Some(&path)).unwrap().unwrap();
+        let client = UnixStream::connect(&path).unwrap();
+        client
+            .set_read_timeout(Some(std::time::Duration::from_millis(50)))
+            .unwrap();
+        // The listener thread accepts the client asynchronously, probe until an event arrives.
+        let mut reader = BufReader::new(client);
+        let mut line = String::new();
+        while line.is_empty() {
+            sink.emit(&Event::BuildStarted {
+                kind: crate::build::events::BuildKind::Full,
+            });
+            let _ = reader.read_line(&mut line);
         }
+        reader
+            .get_ref()
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
 
-        let subcommand_duration = start_subcommand.elapsed();
-        log::info!(
-            "{}{} {}Ran subcommand in {:.2}s",
-            LINE_CLEAR,
-            style("[...]").bold().dim(),
-            COMMAND,
-            subcommand_duration.as_secs_f64(),
+        run("sh -c 'echo hello; echo oops >&2'", Vec::new(), Some(&sink)).unwrap();
+        let mut lines = Vec::new();
+        while lines.len() < 2 {
+            let mut line = String::new();
+            reader
+                .read_line(&mut line)
+                .expect("the output should be forwarded");
+            if line.contains("after-build-output") {
+                lines.push(line);
+            }
+        }
+        assert!(lines.contains(
+            &"{\"event\":\"after-build-output\",\"stream\":\"stdout\",\"line\":\"hello\"}\n".to_string()
+        ));
+        assert!(lines.contains(
+            &"{\"event\":\"after-build-output\",\"stream\":\"stderr\",\"line\":\"oops\"}\n".to_string()
+        ));
+    }
+
+    #[test]
+    fn reports_a_missing_command_instead_of_panicking() {
+        let err =
+            run("rescript-no-such-command --flag", Vec::new(), None).expect_err("the command should fail");
+        assert!(
+            format!("{err:#}")
+                .starts_with("Could not run the after-build command `rescript-no-such-command`"),
+            "{err:#}"
         );
     }
 
-    cmd.wait().unwrap();
+    #[test]
+    fn rejects_unparseable_commands() {
+        assert!(run("echo 'unterminated", Vec::new(), None).is_err());
+        assert!(run("  ", Vec::new(), None).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_the_failure_command_only_when_the_build_failed() {
+        let after_build = AfterBuildArg {
+            after_build: Some("sh -c 'exit 1'".to_string()),
+            after_build_failure: Some("sh -c 'test \"$RESCRIPT_OUTCOME\" = failed'".to_string()),
+        };
+        assert!(run_after_build(&after_build, None, None).is_ok());
+
+        let no_hooks = AfterBuildArg {
+            after_build: None,
+            after_build_failure: None,
+        };
+        assert!(run_after_build(&no_hooks, None, None).is_ok());
+    }
 }
@@ -491,6 +491,36 @@ pub struct GenTypeConfig {
     pub debug: HashMap<String, bool>,
 }
 
+/// The `cache` section of the root `rescript.json`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CacheConfig {
+    pub remote: Option<RemoteCacheConfig>,
+}
+
+/// A remote artifact cache, see `build::artifact_cache`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RemoteCacheConfig {
+    pub url: String,
+    pub mode: Option<RemoteCacheMode>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum RemoteCacheMode {
+    #[default]
+    ReadOnly,
+    ReadWrite,
+}
+
+/// The `watch` section of the root `rescript.json`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct WatchConfig {
+    /// Milliseconds without further file changes before a rebuild starts.
+    pub debounce: Option<u64>,
+    /// Gitignore-style patterns, relative to the project root, of files the watcher ignores.
+    pub ignore: Option<Vec<String>>,
+}
+
 /// Configuration for running a command after each JavaScript file is compiled.
 /// Note: Unlike bsb, rewatch passes absolute paths to the command for clarity.
 #[derive(Deserialize, Debug, Clone)]
@@ -591,6 +621,10 @@ pub struct Config {
     // this is a new feature of rewatch, and it's not part of the rescript.json spec
     #[serde(rename = "allowed-dependents")]
     pub allowed_dependents: Option<Vec<String>>,
+    // Shared cache of compiler output. Only read from the root config.
+    pub cache: Option<CacheConfig>,
+    // Settings of `rescript watch`. Only read from the root config.
+    pub watch: Option<WatchConfig>,
 
     // Holds all deprecation warnings for the config struct
     #[serde(skip)]
@@ -1423,6 +1457,8 @@ pub mod tests {
             deprecation_warnings: vec![],
             experimental_features: None,
             allowed_dependents: args.allowed_dependents,
+            cache: None,
+            watch: None,
             unknown_fields: vec![],
             path: args.path,
         }
@@ -2153,6 +2189,54 @@ pub mod tests {
         assert!(config.get_unknown_fields().is_empty());
     }
 
+    #[test]
+    fn test_cache_config() {
+        let json = r#"
+        {
+            "name": "testrepo",
+            "sources": "src",
+            "cache": {
+                "remote": {
+                    "url": "http://cache.internal:8080/rescript",
+                    "mode": "read-write"
+                }
+            }
+        }
+        "#;
+
+        let config = Config::new_from_json_string(json).expect("a valid json string");
+        assert!(config.get_unknown_fields().is_empty());
+        let remote = config.cache.and_then(|cache| cache.remote).unwrap();
+        assert_eq!(remote.url, "http://cache.internal:8080/rescript");
+        assert_eq!(remote.mode, Some(RemoteCacheMode::ReadWrite));
+    }
+
+    #[test]
+    fn test_watch_config() {
+        let json = r#"
+        {
+            "name": "testrepo",
+            "sources": "src",
+            "watch": {
+                "debounce": 300,
+                "ignore": ["src/generated/**", "!src/generated/Keep.res"]
+            }
+        }
+        "#;
+
+        let config = Config::new_from_json_string(json).expect("a valid json string");
+        assert!(config.get_unknown_fields().is_empty());
+        let watch = config.watch.unwrap();
+        assert_eq!(watch.debounce, Some(300));
+        assert_eq!(
+            watch.ignore,
+            Some(vec![
+                "src/generated/**".to_string(),
+                "!src/generated/Keep.res".to_string()
+            ])
+        );
+    }
+
     #[test]
     fn test_compiler_flags() {
         let json = r#"
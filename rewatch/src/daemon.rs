@@ -0,0 +1,650 @@
+//! `rescript daemon` keeps the build state of a project in memory and listens on a Unix domain
+//! socket in `lib/`. `rescript build` hands its build to a running daemon, which only has to
+//! look at what changed since its previous build instead of rediscovering packages, reading
+//! `.ast`/`.cmi` state from disk and checking every source file.
+//!
+//! A request is a single JSON line describing the build. The daemon answers with JSON lines: the
+//! build output as it's printed, then the result. Builds whose package selection differs from the
+//! daemon's (`--filter`, `--warn-error`, `--prod`, `--features`) are declined, and the client
+//! builds in-process instead.
+//!
+//! The daemon holds `watch.lock` like `rescript watch`, so only one of them runs per project, and
+//! every build runs under `build.lock`. Removing `watch.lock` stops the daemon.
+
+use crate::build::summary::BuildSummary;
+use crate::cli::{MessageFormat, TimingsFormat};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const SOCKET_FILE_NAME: &str = "daemon.sock";
+
+fn socket_path(folder: &Path) -> PathBuf {
+    folder.join("lib").join(SOCKET_FILE_NAME)
+}
+
+/// A `rescript build` invocation, as sent to the daemon.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildRequest {
+    pub version: String,
+    pub filter: Option<String>,
+    pub warn_error: Option<String>,
+    pub prod: bool,
+    pub features: Option<Vec<String>>,
+    pub message_format: MessageFormat,
+    pub explain: bool,
+    pub keep_going: bool,
+    pub timings_format: Option<TimingsFormat>,
+    pub show_progress: bool,
+    pub no_timing: bool,
+    pub plain_output: bool,
+    pub stdout_colors: bool,
+    pub stderr_colors: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Response {
+    Stdout {
+        text: String,
+    },
+    Stderr {
+        text: String,
+    },
+    /// The daemon can't serve this build, the client should build by itself.
+    Declined {
+        reason: String,
+    },
+    /// The summary of a successful build, or the error it failed with.
+    Finished {
+        result: Result<BuildSummary, String>,
+    },
+}
+
+/// The package selection of a daemon. Builds have to match it to be served.
+#[derive(Debug, Clone, PartialEq)]
+struct Selection {
+    filter: Option<String>,
+    warn_error: Option<String>,
+    prod: bool,
+    features: Option<Vec<String>>,
+}
+
+impl Selection {
+    fn decline_reason(&self, request: &BuildRequest) -> Option<String> {
+        if request.version != env!("CARGO_PKG_VERSION") {
+            return Some(format!(
+                "the daemon runs version {}, not {}",
+                env!("CARGO_PKG_VERSION"),
+                request.version
+            ));
+        }
+        let requested = Selection {
+            filter: request.filter.clone(),
+            warn_error: request.warn_error.clone(),
+            prod: request.prod,
+            features: request.features.clone(),
+        };
+        (requested != *self).then(|| {
+            "the daemon was started with a different --filter, --warn-error, --prod or --features".to_string()
+        })
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{build, start};
+
+#[cfg(not(unix))]
+#[allow(clippy::too_many_arguments)]
+pub fn start(
+    _filter: &Option<regex::Regex>,
+    _show_progress: bool,
+    _folder: &str,
+    _plain_output: bool,
+    _warn_error: Option<String>,
+    _prod: bool,
+    _features: Option<Vec<String>>,
+) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!("rescript daemon is only supported on Unix"))
+}
+
+/// Never finds a daemon on this platform.
+#[cfg(not(unix))]
+pub fn build(_folder: &Path, _request: &BuildRequest) -> Option<anyhow::Result<BuildSummary>> {
+    None
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::{BuildRequest, BuildSummary, Response, Selection, socket_path};
+    use crate::build;
+    use crate::build::build_types::{BuildCommandState, SourceType};
+    use crate::config::SourceMapCommand;
+    use crate::queue::FifoQueue;
+    use crate::queue::*;
+    use crate::watcher::settings::WatchSettings;
+    use crate::watcher::{self, CompileType, WatchPaths};
+    use anyhow::{Context, Result, anyhow};
+    use notify::{Event, Watcher};
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::os::fd::{AsRawFd, RawFd};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread::{self, JoinHandle};
+    use std::time::{Duration, Instant};
+
+    /// Sends the request to the daemon of the project in `folder` and prints the build output it
+    /// streams back. `None` when no daemon is running or it declined the build.
+    pub fn build(folder: &Path, request: &BuildRequest) -> Option<Result<BuildSummary>> {
+        let mut stream = UnixStream::connect(socket_path(folder)).ok()?;
+        let mut line = serde_json::to_string(request).expect("a build request can be serialized");
+        line.push('\n');
+        if let Err(err) = stream.write_all(line.as_bytes()) {
+            log::info!("Could not send the build to the daemon: {err}");
+            return None;
+        }
+
+        for line in BufReader::new(stream).lines() {
+            let response = match line.map(|line| serde_json::from_str::<Response>(&line)) {
+                Ok(Ok(response)) => response,
+                Ok(Err(err)) => return Some(Err(anyhow!("Could not read the daemon's response: {err}"))),
+                Err(err) => return Some(Err(anyhow!("Lost the connection to the daemon: {err}"))),
+            };
+            match response {
+                Response::Stdout { text } => {
+                    let mut stdout = io::stdout().lock();
+                    let _ = stdout.write_all(text.as_bytes());
+                    let _ = stdout.flush();
+                }
+                Response::Stderr { text } => {
+                    let _ = io::stderr().write_all(text.as_bytes());
+                }
+                Response::Declined { reason } => {
+                    log::info!("Building without the daemon: {reason}");
+                    return None;
+                }
+                Response::Finished { result } => return Some(result.map_err(|error| anyhow!(error))),
+            }
+        }
+        Some(Err(anyhow!("The daemon stopped before the build finished")))
+    }
+
+    /// Clients send their request right after connecting. One that doesn't (a hung or killed
+    /// `rescript build`, a stray `nc`) is dropped, so it can't block the daemon.
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+    pub(super) fn read_request(stream: &UnixStream, timeout: Duration) -> Result<BuildRequest> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(timeout))?;
+        let mut line = String::new();
+        BufReader::new(stream)
+            .read_line(&mut line)
+            .with_context(|| "No build request received")?;
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    struct Daemon<'a> {
+        path: &'a Path,
+        filter: &'a Option<regex::Regex>,
+        selection: Selection,
+        build_state: BuildCommandState,
+        watcher: Box<dyn Watcher + Send>,
+        watch_paths: WatchPaths,
+        queue: Arc<FifoQueue<Result<Event, notify::Error>>>,
+        needs_compile_type: CompileType,
+    }
+
+    pub fn start(
+        filter: &Option<regex::Regex>,
+        show_progress: bool,
+        folder: &str,
+        plain_output: bool,
+        warn_error: Option<String>,
+        prod: bool,
+        features: Option<Vec<String>>,
+    ) -> Result<()> {
+        let path = Path::new(folder);
+        let queue = Arc::new(FifoQueue::<Result<Event, notify::Error>>::new());
+        let producer = queue.clone();
+        let mut watcher =
+            watcher::new_watcher(move |res| producer.push(res)).expect("Could not create watcher");
+
+        let ctrlc_pressed = Arc::new(AtomicBool::new(false));
+        let ctrlc_pressed_for_handler = Arc::clone(&ctrlc_pressed);
+        ctrlc::set_handler(move || {
+            ctrlc_pressed_for_handler.store(true, Ordering::SeqCst);
+        })
+        .expect("Error setting Ctrl-C handler");
+
+        let (build_state, watch_paths) = build::with_build_lock(path, || {
+            let mut build_state = build::initialize_build(
+                None,
+                filter,
+                show_progress,
+                path,
+                plain_output,
+                warn_error.clone(),
+                prod,
+                features.clone(),
+                SourceMapCommand::Build,
+            )
+            .with_context(|| "Could not initialize build")?;
+            build_state.watch = WatchSettings::new(&build_state);
+
+            let watch_paths = watcher::compute_watch_paths(&build_state, path);
+            watcher::register_watches(watcher.as_mut(), &watch_paths);
+
+            let timing_total = Instant::now();
+            // A failed initial build leaves its modules dirty, the first request compiles them again.
+            if let Ok(result) = build::incremental_build_without_lock(
+                &mut build_state,
+                None,
+                true,
+                show_progress,
+                false,
+                true,
+                plain_output,
+            ) && show_progress
+                && !plain_output
+            {
+                println!(
+                    "\n{}",
+                    build::format_finished_compilation_message(
+                        Some("initial"),
+                        result,
+                        timing_total.elapsed()
+                    )
+                );
+            }
+            build::clean::cleanup_after_build(&build_state);
+            build::saved_state::write(&build_state);
+            build::write_build_ninja(&build_state);
+            Ok::<_, anyhow::Error>((build_state, watch_paths))
+        })?;
+
+        // We hold watch.lock, so a socket that's left over is from a daemon that didn't shut down.
+        let socket = socket_path(path);
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket)
+            .with_context(|| format!("Could not listen on {}", socket.to_string_lossy()))?;
+        listener.set_nonblocking(true)?;
+        if show_progress {
+            println!("Daemon listening on {}", socket.to_string_lossy());
+        }
+
+        let mut daemon = Daemon {
+            path,
+            filter,
+            selection: Selection {
+                filter: filter.as_ref().map(|re| re.as_str().to_string()),
+                warn_error,
+                prod,
+                features,
+            },
+            build_state,
+            watcher,
+            watch_paths,
+            queue,
+            needs_compile_type: CompileType::None,
+        };
+
+        let exit_message = loop {
+            if ctrlc_pressed.load(Ordering::SeqCst) {
+                break "\nExiting...";
+            }
+            if !daemon.apply_events() {
+                break "\nExiting... (lockfile removed)";
+            }
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(err) = daemon.serve(stream) {
+                        log::warn!("Could not serve a build: {err}");
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                Err(err) => return Err(err.into()),
+            }
+        };
+
+        let _ = std::fs::remove_file(&socket);
+        watcher::cleanup_before_watch_exit(path, &daemon.build_state, show_progress, exit_message);
+        Ok(())
+    }
+
+    impl Daemon<'_> {
+        /// Records the file system events received so far. `false` when `watch.lock` was removed
+        /// and the daemon should stop.
+        fn apply_events(&mut self) -> bool {
+            while !self.queue.is_empty() {
+                if let Ok(event) = self.queue.pop() {
+                    if watcher::removes_watch_lock(&event) {
+                        return false;
+                    }
+                    watcher::apply_event(
+                        &event,
+                        &mut self.build_state,
+                        self.filter,
+                        &mut self.needs_compile_type,
+                    );
+                }
+            }
+            true
+        }
+
+        /// Catches up on changes the watcher hasn't reported yet, e.g. a file saved right before
+        /// the build was requested.
+        fn check_modification_times(&mut self) {
+            if self.needs_compile_type == CompileType::Full {
+                return;
+            }
+            let mut changed = Vec::new();
+            for module in self.build_state.modules.values() {
+                let SourceType::SourceFile(source_file) = &module.source_type else {
+                    continue;
+                };
+                let package = self
+                    .build_state
+                    .get_package(&module.package_name)
+                    .expect("Package not found");
+                let sources = std::iter::once((
+                    &source_file.implementation.path,
+                    source_file.implementation.last_modified,
+                ))
+                .chain(source_file.interface.as_ref().map(|i| (&i.path, i.last_modified)));
+                for (source, last_modified) in sources {
+                    let source_path = package.path.join(source);
+                    match source_path.metadata().and_then(|m| m.modified()) {
+                        Ok(modified) if modified == last_modified => (),
+                        Ok(_) => changed.push(source_path),
+                        // The source was removed or renamed.
+                        Err(_) => {
+                            self.needs_compile_type = CompileType::Full;
+                            return;
+                        }
+                    }
+                }
+            }
+            for source_path in changed {
+                watcher::mark_source_changed(&mut self.build_state, &source_path);
+                self.needs_compile_type = CompileType::Incremental;
+            }
+        }
+
+        fn serve(&mut self, stream: UnixStream) -> Result<()> {
+            let request = read_request(&stream, REQUEST_TIMEOUT)?;
+            let stream = Arc::new(Mutex::new(stream));
+
+            if let Some(reason) = self.selection.decline_reason(&request) {
+                send(&stream, &Response::Declined { reason });
+                return Ok(());
+            }
+
+            // Give the watcher a moment to report changes that were just made.
+            thread::sleep(Duration::from_millis(50));
+            if !self.apply_events() {
+                send(
+                    &stream,
+                    &Response::Declined {
+                        reason: "the daemon is shutting down".to_string(),
+                    },
+                );
+                return Ok(());
+            }
+            self.check_modification_times();
+
+            self.build_state.message_format = request.message_format;
+            self.build_state.explain = request.explain;
+            self.build_state.keep_going = request.keep_going;
+            self.build_state.timings_format = request.timings_format;
+
+            let colors = (console::colors_enabled(), console::colors_enabled_stderr());
+            console::set_colors_enabled(request.stdout_colors);
+            console::set_colors_enabled_stderr(request.stderr_colors);
+            let captured = CapturedOutput::start(&stream)?;
+            let result = self.run_build(&request);
+            captured.stop();
+            console::set_colors_enabled(colors.0);
+            console::set_colors_enabled_stderr(colors.1);
+
+            send(
+                &stream,
+                &Response::Finished {
+                    result: result.map_err(|e| e.to_string()),
+                },
+            );
+            Ok(())
+        }
+
+        fn run_build(&mut self, request: &BuildRequest) -> Result<BuildSummary> {
+            let default_timing = request.no_timing.then_some(Duration::ZERO);
+            let timing_total = Instant::now();
+            let compile_type = std::mem::replace(&mut self.needs_compile_type, CompileType::None);
+            build::with_build_lock(self.path, || {
+                let result = match compile_type {
+                    CompileType::Full => watcher::full_rebuild_without_lock(
+                        &mut self.build_state,
+                        self.watcher.as_mut(),
+                        &mut self.watch_paths,
+                        self.path,
+                        self.filter,
+                        request.show_progress,
+                        request.plain_output,
+                        self.selection.prod,
+                        self.selection.features.clone(),
+                        true,
+                        SourceMapCommand::Build,
+                    ),
+                    CompileType::Incremental | CompileType::None => build::incremental_build_without_lock(
+                        &mut self.build_state,
+                        default_timing,
+                        false,
+                        request.show_progress,
+                        true,
+                        true,
+                        request.plain_output,
+                    ),
+                };
+                if let Err(build::IncrementalBuildError {
+                    kind: build::IncrementalBuildErrorKind::InitializationError(_),
+                    ..
+                }) = &result
+                {
+                    // The previous state is kept, the next build tries again, e.g. once the config
+                    // is fixed.
+                    self.needs_compile_type = CompileType::Full;
+                }
+                build::clean::cleanup_after_build(&self.build_state);
+                build::saved_state::write(&self.build_state);
+                build::write_build_ninja(&self.build_state);
+                match result {
+                    Ok(result) => {
+                        if !request.plain_output && request.show_progress {
+                            println!(
+                                "\n{}",
+                                build::format_finished_compilation_message(
+                                    None,
+                                    result,
+                                    default_timing.unwrap_or(timing_total.elapsed()),
+                                )
+                            );
+                        }
+                        Ok(BuildSummary::new(&self.build_state, result))
+                    }
+                    Err(e) => Err(anyhow!("Incremental build failed. Error: {e}")),
+                }
+            })
+        }
+    }
+
+    fn send(stream: &Mutex<UnixStream>, response: &Response) {
+        let mut line = serde_json::to_string(response).expect("a response can be serialized");
+        line.push('\n');
+        // The client may be gone, the build goes on regardless.
+        let _ = stream.lock().unwrap().write_all(line.as_bytes());
+    }
+
+    /// Redirects this process's stdout and stderr to the client while a build runs. Everything
+    /// the build prints, also from other threads, ends up in the redirected file descriptors.
+    struct CapturedOutput {
+        // The redirected file descriptor and a duplicate of what it was before.
+        saved: Vec<(RawFd, RawFd)>,
+        relays: Vec<JoinHandle<()>>,
+    }
+
+    impl CapturedOutput {
+        fn start(stream: &Arc<Mutex<UnixStream>>) -> io::Result<Self> {
+            let _ = io::stdout().flush();
+            let mut captured = CapturedOutput {
+                saved: Vec::new(),
+                relays: Vec::new(),
+            };
+            for fd in [libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+                let (reader, writer) = io::pipe()?;
+                // SAFETY: only duplicates file descriptors, which are valid for the whole process.
+                let saved_fd = unsafe { libc::dup(fd) };
+                if saved_fd < 0 || unsafe { libc::dup2(writer.as_raw_fd(), fd) } < 0 {
+                    let err = io::Error::last_os_error();
+                    captured.stop();
+                    return Err(err);
+                }
+                // `fd` now refers to the pipe, the relay sees the end of it once `fd` is restored.
+                drop(writer);
+                captured.saved.push((fd, saved_fd));
+
+                let stream = stream.clone();
+                captured.relays.push(thread::spawn(move || {
+                    let mut reader = BufReader::new(reader);
+                    let mut chunk = Vec::new();
+                    while let Ok(read) = reader.read_until(b'\n', &mut chunk)
+                        && read > 0
+                    {
+                        let text = String::from_utf8_lossy(&chunk).into_owned();
+                        let response = if fd == libc::STDOUT_FILENO {
+                            Response::Stdout { text }
+                        } else {
+                            Response::Stderr { text }
+                        };
+                        send(&stream, &response);
+                        chunk.clear();
+                    }
+                }));
+            }
+            Ok(captured)
+        }
+
+        fn stop(self) {
+            let _ = io::stdout().flush();
+            for (fd, saved_fd) in &self.saved {
+                // SAFETY: `saved_fd` was returned by `dup` and isn't used after this.
+                unsafe {
+                    libc::dup2(*saved_fd, *fd);
+                    libc::close(*saved_fd);
+                }
+            }
+            for relay in self.relays {
+                let _ = relay.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> BuildRequest {
+        BuildRequest {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            filter: None,
+            warn_error: None,
+            prod: false,
+            features: None,
+            message_format: MessageFormat::Human,
+            explain: false,
+            keep_going: false,
+            timings_format: None,
+            show_progress: true,
+            no_timing: false,
+            plain_output: true,
+            stdout_colors: false,
+            stderr_colors: false,
+        }
+    }
+
+    fn selection() -> Selection {
+        Selection {
+            filter: None,
+            warn_error: None,
+            prod: false,
+            features: None,
+        }
+    }
+
+    #[test]
+    fn serves_builds_with_the_same_selection() {
+        let request = BuildRequest {
+            explain: true,
+            message_format: MessageFormat::Json,
+            ..request()
+        };
+        assert_eq!(selection().decline_reason(&request), None);
+    }
+
+    #[test]
+    fn declines_builds_with_another_selection() {
+        for request in [
+            BuildRequest {
+                prod: true,
+                ..request()
+            },
+            BuildRequest {
+                filter: Some("Test".to_string()),
+                ..request()
+            },
+            BuildRequest {
+                features: Some(vec!["native".to_string()]),
+                ..request()
+            },
+            BuildRequest {
+                version: "0.0.0".to_string(),
+                ..request()
+            },
+        ] {
+            assert!(selection().decline_reason(&request).is_some(), "{request:?}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_client_that_sends_nothing_does_not_block_the_next_request() {
+        use std::io::Write;
+        use std::os::unix::net::{UnixListener, UnixStream};
+        use std::time::Duration;
+
+        let temp_dir = tempfile::TempDir::new().expect("temp dir should be created");
+        let socket = temp_dir.path().join(SOCKET_FILE_NAME);
+        let listener = UnixListener::bind(&socket).expect("socket should be bound");
+
+        let _silent = UnixStream::connect(&socket).expect("silent client should connect");
+        let mut client = UnixStream::connect(&socket).expect("client should connect");
+        let mut line = serde_json::to_string(&request()).unwrap();
+        line.push('\n');
+        client.write_all(line.as_bytes()).unwrap();
+
+        let (silent, _) = listener.accept().unwrap();
+        assert!(unix::read_request(&silent, Duration::from_millis(100)).is_err());
+        let (served, _) = listener.accept().unwrap();
+        let served = unix::read_request(&served, Duration::from_millis(100)).expect("request should be read");
+        assert_eq!(served.version, env!("CARGO_PKG_VERSION"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn build_finds_no_daemon_without_a_socket() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir should be created");
+        assert!(build(temp_dir.path(), &request()).is_none());
+    }
+}
@@ -0,0 +1,574 @@
+//! `rescript graph`: export the module dependency graph that every build computes anyway.
+//!
+//! Edges point from a module to the modules it depends on (`Module.deps`), so the direction
+//! matches the import statements in the source.
+
+use crate::build::{self, build_types::*};
+use crate::cli::GraphFormat;
+use crate::helpers;
+use ahash::AHashMap;
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::Path;
+use tracing::instrument;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Node {
+    pub package: String,
+    /// Source file relative to its package root. `None` for namespace maps and packages.
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleGraph {
+    pub nodes: BTreeMap<String, Node>,
+    /// `(module, dependency)` pairs.
+    pub edges: BTreeSet<(String, String)>,
+    /// Nodes are packages rather than modules.
+    pub package_level: bool,
+}
+
+#[derive(Serialize)]
+struct JsonNode<'a> {
+    id: &'a str,
+    name: String,
+    package: &'a str,
+    path: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct JsonEdge<'a> {
+    from: &'a str,
+    to: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonGraph<'a> {
+    package_level: bool,
+    nodes: Vec<JsonNode<'a>>,
+    edges: Vec<JsonEdge<'a>>,
+}
+
+impl ModuleGraph {
+    pub fn from_modules(modules: &AHashMap<String, Module>) -> Self {
+        let nodes = modules
+            .iter()
+            .map(|(name, module)| {
+                let path = match &module.source_type {
+                    SourceType::SourceFile(source_file) => {
+                        Some(source_file.implementation.path.to_string_lossy().to_string())
+                    }
+                    SourceType::MlMap(_) => None,
+                };
+                (
+                    name.to_owned(),
+                    Node {
+                        package: module.package_name.to_owned(),
+                        path,
+                    },
+                )
+            })
+            .collect();
+
+        let edges = modules
+            .iter()
+            .flat_map(|(name, module)| {
+                module
+                    .deps
+                    .iter()
+                    .filter(|dep| modules.contains_key(*dep))
+                    .map(move |dep| (name.to_owned(), dep.to_owned()))
+            })
+            .collect();
+
+        Self {
+            nodes,
+            edges,
+            package_level: false,
+        }
+    }
+
+    pub fn deps_of<'a>(&'a self, module: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        self.edges
+            .range((module.to_string(), String::new())..)
+            .take_while(move |(from, _)| from == module)
+            .map(|(_, to)| to)
+    }
+
+    pub fn resolve_module(&self, name: &str) -> Result<String> {
+        resolve_module_name(self.nodes.keys(), name)
+    }
+
+    /// Shortest chain of dependency edges from `from` to `to`, both included.
+    pub fn shortest_path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        let mut parents: BTreeMap<&str, Option<&str>> = BTreeMap::new();
+        let mut queue = VecDeque::new();
+        parents.insert(from, None);
+        queue.push_back(from);
+
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                let mut path = vec![current.to_string()];
+                let mut node = current;
+                while let Some(Some(parent)) = parents.get(node) {
+                    path.push(parent.to_string());
+                    node = parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for dep in self.deps_of(current) {
+                if !parents.contains_key(dep.as_str()) {
+                    parents.insert(dep, Some(current));
+                    queue.push_back(dep);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Keep `root` and the modules reachable from it through at most `depth` dependency edges.
+    pub fn from_root(&self, root: &str, depth: Option<usize>) -> Self {
+        let mut distances: BTreeMap<String, usize> = BTreeMap::new();
+        let mut queue = VecDeque::new();
+        distances.insert(root.to_string(), 0);
+        queue.push_back(root.to_string());
+
+        while let Some(current) = queue.pop_front() {
+            let distance = distances[&current];
+            if depth.is_some_and(|depth| distance >= depth) {
+                continue;
+            }
+            for dep in self.deps_of(&current) {
+                if !distances.contains_key(dep) {
+                    distances.insert(dep.to_owned(), distance + 1);
+                    queue.push_back(dep.to_owned());
+                }
+            }
+        }
+
+        Self {
+            nodes: self
+                .nodes
+                .iter()
+                .filter(|(id, _)| distances.contains_key(*id))
+                .map(|(id, node)| (id.to_owned(), node.to_owned()))
+                .collect(),
+            // Only keep edges that were walked, so modules on the depth boundary don't drag in
+            // the edges between each other.
+            edges: self
+                .edges
+                .iter()
+                .filter(|(from, to)| match (distances.get(from), distances.get(to)) {
+                    (Some(from_distance), Some(_)) => depth.is_none_or(|depth| *from_distance < depth),
+                    _ => false,
+                })
+                .cloned()
+                .collect(),
+            package_level: self.package_level,
+        }
+    }
+
+    pub fn restrict_to_package(&self, package: &str) -> Self {
+        let nodes: BTreeMap<String, Node> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.package == package)
+            .map(|(id, node)| (id.to_owned(), node.to_owned()))
+            .collect();
+        let edges = self
+            .edges
+            .iter()
+            .filter(|(from, to)| nodes.contains_key(from) && nodes.contains_key(to))
+            .cloned()
+            .collect();
+
+        Self {
+            nodes,
+            edges,
+            package_level: self.package_level,
+        }
+    }
+
+    /// Merge all modules of a package into one node. Edges inside a package are dropped.
+    pub fn collapse_to_packages(&self) -> Self {
+        let nodes = self
+            .nodes
+            .values()
+            .map(|node| {
+                (
+                    node.package.to_owned(),
+                    Node {
+                        package: node.package.to_owned(),
+                        path: None,
+                    },
+                )
+            })
+            .collect();
+        let edges = self
+            .edges
+            .iter()
+            .filter_map(|(from, to)| {
+                let from_package = &self.nodes.get(from)?.package;
+                let to_package = &self.nodes.get(to)?.package;
+                (from_package != to_package).then(|| (from_package.to_owned(), to_package.to_owned()))
+            })
+            .collect();
+
+        Self {
+            nodes,
+            edges,
+            package_level: true,
+        }
+    }
+
+    fn label(&self, id: &str) -> String {
+        if self.package_level {
+            id.to_string()
+        } else {
+            helpers::format_namespaced_module_name(id)
+        }
+    }
+
+    fn nodes_by_package(&self) -> BTreeMap<&str, Vec<&str>> {
+        let mut packages: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (id, node) in &self.nodes {
+            packages
+                .entry(node.package.as_str())
+                .or_default()
+                .push(id.as_str());
+        }
+        packages
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph rescript {\n  rankdir=LR;\n  node [shape=box];\n");
+
+        if self.package_level {
+            for id in self.nodes.keys() {
+                out.push_str(&format!("  {};\n", dot_quote(id)));
+            }
+        } else {
+            for (index, (package, ids)) in self.nodes_by_package().into_iter().enumerate() {
+                out.push_str(&format!("  subgraph cluster_{index} {{\n"));
+                out.push_str(&format!("    label={};\n", dot_quote(package)));
+                for id in ids {
+                    out.push_str(&format!(
+                        "    {} [label={}];\n",
+                        dot_quote(id),
+                        dot_quote(&self.label(id))
+                    ));
+                }
+                out.push_str("  }\n");
+            }
+        }
+
+        for (from, to) in &self.edges {
+            out.push_str(&format!("  {} -> {};\n", dot_quote(from), dot_quote(to)));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        // Mermaid ids can't contain most punctuation, so number the nodes and put the real
+        // names in labels.
+        let ids: BTreeMap<&str, String> = self
+            .nodes
+            .keys()
+            .enumerate()
+            .map(|(index, id)| (id.as_str(), format!("n{index}")))
+            .collect();
+
+        let mut out = String::from("flowchart LR\n");
+        if self.package_level {
+            for id in self.nodes.keys() {
+                out.push_str(&format!("  {}[{}]\n", ids[id.as_str()], mermaid_quote(id)));
+            }
+        } else {
+            for (index, (package, nodes)) in self.nodes_by_package().into_iter().enumerate() {
+                out.push_str(&format!("  subgraph p{index}[{}]\n", mermaid_quote(package)));
+                for id in nodes {
+                    out.push_str(&format!("    {}[{}]\n", ids[id], mermaid_quote(&self.label(id))));
+                }
+                out.push_str("  end\n");
+            }
+        }
+
+        for (from, to) in &self.edges {
+            out.push_str(&format!("  {} --> {}\n", ids[from.as_str()], ids[to.as_str()]));
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let graph = JsonGraph {
+            package_level: self.package_level,
+            nodes: self
+                .nodes
+                .iter()
+                .map(|(id, node)| JsonNode {
+                    id,
+                    name: self.label(id),
+                    package: &node.package,
+                    path: node.path.as_deref(),
+                })
+                .collect(),
+            edges: self
+                .edges
+                .iter()
+                .map(|(from, to)| JsonEdge { from, to })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&graph).expect("Graph serialization should not fail")
+    }
+
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Mermaid => self.to_mermaid(),
+            GraphFormat::Json => self.to_json() + "\n",
+        }
+    }
+}
+
+/// Find a module by the name users write in source (`Ns.Module` or `Module`) or by its
+/// internal `Module-Ns` name.
+pub fn resolve_module_name<'a>(module_names: impl Iterator<Item = &'a String>, name: &str) -> Result<String> {
+    let module_names: Vec<&String> = module_names.collect();
+    if module_names.iter().any(|id| *id == name) {
+        return Ok(name.to_string());
+    }
+
+    let mut candidates: Vec<&String> = module_names
+        .iter()
+        .filter(|id| helpers::format_namespaced_module_name(id) == name)
+        .copied()
+        .collect();
+    if candidates.is_empty() {
+        candidates = module_names
+            .iter()
+            .filter(|id| id.split('-').next() == Some(name))
+            .copied()
+            .collect();
+    }
+
+    match candidates.as_slice() {
+        [] => Err(anyhow!("Module '{name}' not found")),
+        [id] => Ok(id.to_string()),
+        _ => Err(anyhow!(
+            "Module name '{name}' is ambiguous, use one of: {}",
+            candidates
+                .iter()
+                .map(|id| helpers::format_namespaced_module_name(id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+fn dot_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn mermaid_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "#quot;"))
+}
+
+#[instrument(name = "graph.graph", skip_all, fields(working_dir = %path.display()))]
+pub fn graph(
+    path: &Path,
+    format: GraphFormat,
+    package: Option<&str>,
+    package_level: bool,
+    root: Option<&str>,
+    depth: Option<usize>,
+    prod: bool,
+) -> Result<()> {
+    let build_state = build::load_module_graph(&None, path, prod, None)?;
+    let mut graph = ModuleGraph::from_modules(&build_state.modules);
+
+    if let Some(root) = root {
+        let root = graph.resolve_module(root)?;
+        graph = graph.from_root(&root, depth);
+    }
+    if let Some(package) = package {
+        if build_state.get_package(package).is_none() {
+            return Err(anyhow!("Package '{package}' not found"));
+        }
+        graph = graph.restrict_to_package(package);
+    }
+    if package_level {
+        graph = graph.collapse_to_packages();
+    }
+
+    print!("{}", graph.render(format));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(nodes: &[(&str, &str)], edges: &[(&str, &str)]) -> ModuleGraph {
+        ModuleGraph {
+            nodes: nodes
+                .iter()
+                .map(|(id, package)| {
+                    (
+                        id.to_string(),
+                        Node {
+                            package: package.to_string(),
+                            path: Some(format!("src/{id}.res")),
+                        },
+                    )
+                })
+                .collect(),
+            edges: edges
+                .iter()
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+                .collect(),
+            package_level: false,
+        }
+    }
+
+    fn sample() -> ModuleGraph {
+        graph(
+            &[
+                ("App", "app"),
+                ("Router", "app"),
+                ("Button-Ui", "ui"),
+                ("Icon-Ui", "ui"),
+                ("Belt", "belt"),
+            ],
+            &[
+                ("App", "Router"),
+                ("App", "Button-Ui"),
+                ("Router", "Belt"),
+                ("Button-Ui", "Icon-Ui"),
+                ("Icon-Ui", "Belt"),
+            ],
+        )
+    }
+
+    fn edge_list(graph: &ModuleGraph) -> Vec<(&str, &str)> {
+        graph
+            .edges
+            .iter()
+            .map(|(from, to)| (from.as_str(), to.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn resolves_namespaced_and_plain_names() {
+        let graph = sample();
+        assert_eq!(graph.resolve_module("App").unwrap(), "App");
+        assert_eq!(graph.resolve_module("Ui.Button").unwrap(), "Button-Ui");
+        assert_eq!(graph.resolve_module("Button").unwrap(), "Button-Ui");
+        assert_eq!(graph.resolve_module("Button-Ui").unwrap(), "Button-Ui");
+        assert!(graph.resolve_module("Missing").is_err());
+    }
+
+    #[test]
+    fn shortest_path_follows_dependency_edges() {
+        let graph = sample();
+
+        assert_eq!(
+            graph.shortest_path("App", "Belt").unwrap(),
+            vec!["App", "Router", "Belt"]
+        );
+        assert_eq!(
+            graph.shortest_path("Button-Ui", "Belt").unwrap(),
+            vec!["Button-Ui", "Icon-Ui", "Belt"]
+        );
+        assert_eq!(graph.shortest_path("App", "App").unwrap(), vec!["App"]);
+        assert!(graph.shortest_path("Belt", "App").is_none());
+    }
+
+    #[test]
+    fn root_with_depth_keeps_only_walked_edges() {
+        let graph = sample().from_root("App", Some(1));
+
+        assert_eq!(
+            graph.nodes.keys().map(String::as_str).collect::<Vec<_>>(),
+            vec!["App", "Button-Ui", "Router"]
+        );
+        assert_eq!(edge_list(&graph), vec![("App", "Button-Ui"), ("App", "Router")]);
+    }
+
+    #[test]
+    fn root_without_depth_keeps_transitive_deps() {
+        let graph = sample().from_root("Button-Ui", None);
+
+        assert_eq!(
+            graph.nodes.keys().map(String::as_str).collect::<Vec<_>>(),
+            vec!["Belt", "Button-Ui", "Icon-Ui"]
+        );
+        assert_eq!(
+            edge_list(&graph),
+            vec![("Button-Ui", "Icon-Ui"), ("Icon-Ui", "Belt")]
+        );
+    }
+
+    #[test]
+    fn restricting_to_a_package_drops_outside_edges() {
+        let graph = sample().restrict_to_package("ui");
+
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(edge_list(&graph), vec![("Button-Ui", "Icon-Ui")]);
+    }
+
+    #[test]
+    fn collapsing_yields_package_edges() {
+        let graph = sample().collapse_to_packages();
+
+        assert!(graph.package_level);
+        assert_eq!(
+            graph.nodes.keys().map(String::as_str).collect::<Vec<_>>(),
+            vec!["app", "belt", "ui"]
+        );
+        assert_eq!(
+            edge_list(&graph),
+            vec![("app", "belt"), ("app", "ui"), ("ui", "belt")]
+        );
+    }
+
+    #[test]
+    fn renders_dot_with_package_clusters() {
+        let dot = graph(&[("A", "pkg"), ("B-Ns", "pkg")], &[("A", "B-Ns")]).to_dot();
+
+        assert_eq!(
+            dot,
+            "digraph rescript {\n  rankdir=LR;\n  node [shape=box];\n  subgraph cluster_0 {\n    label=\"pkg\";\n    \"A\" [label=\"A\"];\n    \"B-Ns\" [label=\"Ns.B\"];\n  }\n  \"A\" -> \"B-Ns\";\n}\n"
+        );
+    }
+
+    #[test]
+    fn renders_mermaid_with_numbered_ids() {
+        let mermaid = graph(&[("A", "@scope/pkg"), ("B", "@scope/pkg")], &[("A", "B")]).to_mermaid();
+
+        assert_eq!(
+            mermaid,
+            "flowchart LR\n  subgraph p0[\"@scope/pkg\"]\n    n0[\"A\"]\n    n1[\"B\"]\n  end\n  n0 --> n1\n"
+        );
+    }
+
+    #[test]
+    fn renders_json_nodes_and_edges() {
+        let json = graph(&[("A", "pkg"), ("B-Ns", "pkg")], &[("A", "B-Ns")]).to_json();
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!({
+                "package_level": false,
+                "nodes": [
+                    {"id": "A", "name": "A", "package": "pkg", "path": "src/A.res"},
+                    {"id": "B-Ns", "name": "Ns.B", "package": "pkg", "path": "src/B-Ns.res"},
+                ],
+                "edges": [{"from": "A", "to": "B-Ns"}],
+            })
+        );
+    }
+}
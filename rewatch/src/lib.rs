@@ -2,7 +2,9 @@ pub mod build;
 pub mod cli;
 pub mod cmd;
 pub mod config;
+pub mod daemon;
 pub mod format;
+pub mod graph;
 pub mod helpers;
 pub mod lock;
 pub mod project_context;
@@ -10,3 +12,4 @@ pub mod queue;
 pub mod sourcedirs;
 pub mod telemetry;
 pub mod watcher;
+pub mod why;
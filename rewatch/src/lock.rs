@@ -1,5 +1,5 @@
 use anyhow::Result;
-use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::{Event, EventKind, RecursiveMode};
 use std::fs;
 use std::fs::OpenOptions;
 use std::io::Write;
@@ -11,6 +11,7 @@ use sysinfo::{PidExt, ProcessExt, System, SystemExt};
 
 use crate::queue::FifoQueue;
 use crate::queue::*;
+use crate::watcher;
 
 /* This locking mechanism is meant to never be deleted. Instead, it stores the PID of the process
  * that's running, when trying to aquire a lock, it checks wether that process is still running. If
@@ -122,7 +123,7 @@ pub fn await_lock_deletion(location: &Path, kind: LockKind) -> Result<(), Error>
     let queue = Arc::new(FifoQueue::<Result<Event, notify::Error>>::new());
     let producer = queue.clone();
 
-    let mut watcher = RecommendedWatcher::new(move |res| producer.push(res), Config::default())
+    let mut watcher = watcher::new_watcher(move |res| producer.push(res))
         .map_err(|e| Error::AwaitingLockFile(AwaitLockError::Watcher(e)))?;
 
     watcher
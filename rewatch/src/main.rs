@@ -4,9 +4,9 @@ use log::LevelFilter;
 use std::{io::Write, path::Path};
 
 use rescript::{
-    build, cli, cmd, format,
+    build, cli, cmd, daemon, format, graph,
     lock::{LockKind, drop_lock, get_lock_or_exit},
-    telemetry, watcher,
+    telemetry, watcher, why,
 };
 
 fn main() {
@@ -58,27 +58,108 @@ fn run_main(otel_enabled: bool) -> i32 {
     // or more verbose. This way `-v` and `-vv` add debug output without suppressing progress.
     let show_progress = log_level_filter >= LevelFilter::Info;
 
+    // bsc processes are started from rayon's global pool, so its size caps how many run at once.
+    let jobs = match &cli.command {
+        cli::Command::Build(cli::BuildArgs { jobs, .. })
+        | cli::Command::Watch(cli::WatchArgs { jobs, .. })
+        | cli::Command::Daemon(cli::DaemonArgs { jobs, .. })
+        | cli::Command::Format { jobs, .. } => jobs.resolve(),
+        _ => Ok(None),
+    };
+    let jobs = match jobs {
+        Ok(jobs) => jobs,
+        Err(err) => return exit_code(Err(err)),
+    };
+    if let Some(jobs) = jobs
+        && let Err(err) = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs.get())
+            .build_global()
+    {
+        log::warn!("Could not limit the number of jobs: {err}");
+    }
+
+    if let cli::Command::Watch(cli::WatchArgs { watch_backend, .. })
+    | cli::Command::Daemon(cli::DaemonArgs { watch_backend, .. }) = &cli.command
+    {
+        watcher::set_backend(watch_backend.watch_backend, watch_backend.poll_interval());
+    }
+
     match cli.command {
         cli::Command::CompilerArgs { path } => {
             exit_code(build::get_compiler_args(Path::new(&path)).map(|args| println!("{}", args)))
         }
-        cli::Command::Build(build_args) => {
-            let features = build_args.features.parsed();
-            let result = build::build(
+        cli::Command::Build(build_args) if build_args.dry_run => exit_code(
+            build::dry_run::dry_run(
                 &build_args.filter,
                 build_args.folder.as_ref(),
-                show_progress,
-                build_args.no_timing,
-                true, // create_sourcedirs is now always enabled
-                plain_output,
                 (*build_args.warn_error).clone(),
                 build_args.prod,
-                features,
-            );
-            if result.is_ok()
-                && let Some(args_after_build) = (*build_args.after_build).clone()
+                build_args.features.parsed(),
+                &build_args.targets,
+                build_args.since.as_deref(),
+            )
+            .map(|report| println!("{}", report.format())),
+        ),
+        cli::Command::Build(build_args) => {
+            let features = build_args.features.parsed();
+            // Keep stdout machine-readable when diagnostics are emitted as JSON.
+            let show_progress = show_progress && *build_args.message_format == cli::MessageFormat::Human;
+            // The daemon always writes JS and builds the whole project with the jobs it was started
+            // with. A type-check, a build of some targets or of the changes since a revision, or a
+            // build with a job limit runs in this process.
+            let daemon_result = if build_args.no_daemon
+                || jobs.is_some()
+                || build_args.check
+                || !build_args.targets.is_empty()
+                || build_args.since.is_some()
             {
-                cmd::run(args_after_build);
+                None
+            } else {
+                daemon::build(
+                    build_args.folder.as_ref(),
+                    &daemon::BuildRequest {
+                        version: env!("CARGO_PKG_VERSION").to_string(),
+                        filter: build_args.filter.as_ref().map(|re| re.as_str().to_string()),
+                        warn_error: (*build_args.warn_error).clone(),
+                        prod: build_args.prod,
+                        features: features.clone(),
+                        message_format: *build_args.message_format,
+                        explain: *build_args.explain,
+                        keep_going: *build_args.keep_going,
+                        timings_format: *build_args.timings,
+                        show_progress,
+                        no_timing: build_args.no_timing,
+                        plain_output,
+                        stdout_colors: console::colors_enabled(),
+                        stderr_colors: console::colors_enabled_stderr(),
+                    },
+                )
+            };
+            let result = daemon_result.unwrap_or_else(|| {
+                build::build(
+                    &build_args.filter,
+                    build_args.folder.as_ref(),
+                    show_progress,
+                    build_args.no_timing,
+                    true, // create_sourcedirs is now always enabled
+                    plain_output,
+                    (*build_args.warn_error).clone(),
+                    build_args.prod,
+                    features,
+                    *build_args.message_format,
+                    *build_args.explain,
+                    *build_args.keep_going,
+                    *build_args.timings,
+                    build_args.check,
+                    &build_args.targets,
+                    build_args.since.as_deref(),
+                )
+            });
+            if let Err(err) = cmd::run_after_build(&build_args.after_build, result.as_ref().ok(), None) {
+                if build_args.after_build_fail_build && result.is_ok() {
+                    return exit_code(Err(err));
+                }
+                eprintln!("{err:#}");
             }
             exit_code(result.map(|_| ()))
         }
@@ -86,17 +167,46 @@ fn run_main(otel_enabled: bool) -> i32 {
             let _lock = get_lock_or_exit(LockKind::Watch, &watch_args.folder);
 
             let features = watch_args.features.parsed();
+            // Keep stdout machine-readable when diagnostics or events are emitted as JSON.
+            let show_progress = show_progress
+                && *watch_args.message_format == cli::MessageFormat::Human
+                && watch_args.events.events.is_none();
+            let events = match build::events::EventSink::new(
+                watch_args.events.events,
+                watch_args.events.events_socket.as_deref(),
+            ) {
+                Ok(events) => events,
+                Err(err) => return exit_code(Err(err)),
+            };
             exit_code(watcher::start(
                 &watch_args.filter,
                 show_progress,
                 &watch_args.folder,
-                (*watch_args.after_build).clone(),
+                watch_args.after_build.clone(),
                 true, // create_sourcedirs is now always enabled
                 plain_output,
                 (*watch_args.warn_error).clone(),
                 watch_args.clear_screen,
                 watch_args.prod,
                 features,
+                *watch_args.message_format,
+                *watch_args.explain,
+                *watch_args.keep_going,
+                *watch_args.timings,
+                events,
+            ))
+        }
+        cli::Command::Daemon(daemon_args) => {
+            let _lock = get_lock_or_exit(LockKind::Watch, &daemon_args.folder);
+
+            exit_code(daemon::start(
+                &daemon_args.filter,
+                show_progress,
+                &daemon_args.folder,
+                plain_output,
+                (*daemon_args.warn_error).clone(),
+                daemon_args.prod,
+                daemon_args.features.parsed(),
             ))
         }
         cli::Command::Clean { folder, prod } => {
@@ -111,7 +221,35 @@ fn run_main(otel_enabled: bool) -> i32 {
 
             code
         }
-        cli::Command::Format { stdin, check, files } => exit_code(format::format(stdin, check, files)),
+        cli::Command::Graph {
+            folder,
+            format,
+            package,
+            package_level,
+            root,
+            depth,
+            prod,
+        } => exit_code(graph::graph(
+            folder.as_ref(),
+            format,
+            package.as_deref(),
+            package_level,
+            root.as_deref(),
+            depth,
+            prod,
+        )),
+        cli::Command::Why {
+            module,
+            dependency,
+            folder,
+            prod,
+        } => exit_code(why::why(folder.as_ref(), &module, dependency.as_deref(), prod)),
+        cli::Command::Affected { since, folder, prod } => {
+            exit_code(build::affected::affected(Path::new(&folder), &since, prod))
+        }
+        cli::Command::Format {
+            stdin, check, files, ..
+        } => exit_code(format::format(stdin, check, files)),
     }
 }
 
@@ -1,12 +1,18 @@
 //! OpenTelemetry setup for rewatch.
 //!
-//! Provides optional tracing export via OTLP HTTP when OTEL_EXPORTER_OTLP_ENDPOINT is set.
-//! When the environment variable is not set, tracing is disabled (no-op).
+//! Provides optional tracing export via OTLP HTTP when OTEL_EXPORTER_OTLP_ENDPOINT is set, and/or
+//! to a local JSON-lines file when RESCRIPT_TRACE_FILE is set.
+//! When neither environment variable is set, tracing is disabled (no-op).
+
+mod trace_file;
 
 use opentelemetry::trace::TracerProvider as _;
 use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
 use opentelemetry_sdk::Resource;
 use opentelemetry_sdk::trace::SdkTracerProvider;
+use std::path::PathBuf;
+use std::sync::Arc;
+use trace_file::{TraceFile, TraceFileLayer};
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::fmt;
 use tracing_subscriber::layer::SubscriberExt;
@@ -16,6 +22,8 @@ use tracing_subscriber::util::SubscriberInitExt;
 /// Drop this at the end of main to flush all spans.
 pub struct TelemetryGuard {
     provider: Option<SdkTracerProvider>,
+    /// The RESCRIPT_TRACE_FILE spans are written to, flushed on drop.
+    trace_file: Option<Arc<TraceFile>>,
     /// Whether OTEL tracing was initialized (affects logger setup in main).
     otel_enabled: bool,
 }
@@ -29,6 +37,9 @@ impl TelemetryGuard {
 
 impl Drop for TelemetryGuard {
     fn drop(&mut self) {
+        if let Some(trace_file) = self.trace_file.take() {
+            trace_file.flush();
+        }
         if let Some(provider) = self.provider.take() {
             // Force flush - this exports any buffered spans
             if let Err(e) = provider.force_flush() {
@@ -42,31 +53,53 @@ impl Drop for TelemetryGuard {
     }
 }
 
-/// Initialize OpenTelemetry tracing if OTEL_EXPORTER_OTLP_ENDPOINT is set.
+/// Initialize OpenTelemetry tracing if OTEL_EXPORTER_OTLP_ENDPOINT is set, and the trace file
+/// exporter if RESCRIPT_TRACE_FILE is set.
 ///
 /// Returns a guard that must be kept alive for the duration of the program.
 /// When the guard is dropped, it flushes all pending spans.
 ///
-/// If OTEL_EXPORTER_OTLP_ENDPOINT is not set, returns a no-op guard and
-/// tracing calls become no-ops.
+/// If neither is set, returns a no-op guard and tracing calls become no-ops.
 pub fn init_telemetry() -> TelemetryGuard {
     // Presence of either the general or trace-specific endpoint env var enables OTLP export.
-    init_telemetry_for_endpoint_presence(
+    init_telemetry_from_env(
         std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_some(),
         std::env::var_os("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_some(),
+        std::env::var_os("RESCRIPT_TRACE_FILE")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from),
     )
 }
 
-fn init_telemetry_for_endpoint_presence(endpoint_set: bool, traces_endpoint_set: bool) -> TelemetryGuard {
+fn init_telemetry_from_env(
+    endpoint_set: bool,
+    traces_endpoint_set: bool,
+    trace_file_path: Option<PathBuf>,
+) -> TelemetryGuard {
+    // The logger isn't set up yet, so report a trace file that can't be created directly.
+    let trace_file = trace_file_path.and_then(|path| match TraceFile::create(&path) {
+        Ok(trace_file) => Some(trace_file),
+        Err(e) => {
+            eprintln!(
+                "Could not create RESCRIPT_TRACE_FILE {}: {}. Spans won't be written.",
+                path.display(),
+                e
+            );
+            None
+        }
+    });
+
     if endpoint_set || traces_endpoint_set {
-        init_with_otlp()
+        init_with_otlp(trace_file)
+    } else if let Some(trace_file) = trace_file {
+        init_with_trace_file(trace_file)
     } else {
         init_noop()
     }
 }
 
-/// Initialize with OTLP exporter.
-fn init_with_otlp() -> TelemetryGuard {
+/// Initialize with OTLP exporter, and the trace file exporter when one is given.
+fn init_with_otlp(trace_file: Option<Arc<TraceFile>>) -> TelemetryGuard {
     // Build a blocking reqwest-backed HTTP exporter. Blocking is fine here
     // because the batch span processor runs in its own background thread.
     //
@@ -81,7 +114,10 @@ fn init_with_otlp() -> TelemetryGuard {
         Ok(exp) => exp,
         Err(e) => {
             log::warn!("Failed to create OTLP exporter: {}. Falling back to no-op.", e);
-            return init_noop();
+            return match trace_file {
+                Some(trace_file) => init_with_trace_file(trace_file),
+                None => init_noop(),
+            };
         }
     };
 
@@ -116,20 +152,44 @@ fn init_with_otlp() -> TelemetryGuard {
     tracing_subscriber::registry()
         .with(filter)
         .with(telemetry_layer)
+        .with(trace_file.clone().map(TraceFileLayer::new))
         .with(fmt_layer)
         .init();
 
     TelemetryGuard {
         provider: Some(provider),
+        trace_file,
         otel_enabled: true,
     }
 }
 
+/// Initialize with only the trace file exporter.
+fn init_with_trace_file(trace_file: Arc<TraceFile>) -> TelemetryGuard {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("debug"));
+    let subscriber = tracing_subscriber::registry()
+        .with(filter)
+        .with(TraceFileLayer::new(trace_file.clone()));
+
+    // Unlike `.init()`, this doesn't install a `log` bridge, so main.rs still sets up its own
+    // logger and output stays the same as without tracing.
+    if let Err(e) = tracing::subscriber::set_global_default(subscriber) {
+        eprintln!("Could not enable RESCRIPT_TRACE_FILE: {}", e);
+        return init_noop();
+    }
+
+    TelemetryGuard {
+        provider: None,
+        trace_file: Some(trace_file),
+        otel_enabled: false,
+    }
+}
+
 /// Initialize with no-op tracing (no export).
 fn init_noop() -> TelemetryGuard {
     // Don't set up any subscriber - let main.rs handle logging normally
     TelemetryGuard {
         provider: None,
+        trace_file: None,
         otel_enabled: false,
     }
 }
@@ -154,7 +214,7 @@ mod tests {
 
     #[test]
     fn init_telemetry_without_env_is_noop() {
-        let guard = init_telemetry_for_endpoint_presence(false, false);
+        let guard = init_telemetry_from_env(false, false, None);
         assert!(
             !guard.otel_enabled(),
             "expected no-op guard when OTEL endpoint env vars are unset"
@@ -0,0 +1,204 @@
+//! Writes `tracing` spans to a local file as JSON lines, one object per span, written when the
+//! span closes. Enabled with `RESCRIPT_TRACE_FILE`, so a trace can be attached to a bug report
+//! without running an OTLP collector.
+
+use serde_json::{Map, Value, json};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Subscriber, span};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+/// The file spans are written to. Shared between the layer and the `TelemetryGuard`, which
+/// flushes it on shutdown.
+pub struct TraceFile {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl TraceFile {
+    pub fn create(path: &Path) -> std::io::Result<Arc<Self>> {
+        let file = File::create(path)?;
+        Ok(Self::new(Box::new(BufWriter::new(file))))
+    }
+
+    fn new(writer: Box<dyn Write + Send>) -> Arc<Self> {
+        Arc::new(Self {
+            writer: Mutex::new(writer),
+        })
+    }
+
+    fn write_line(&self, line: &Value) {
+        let mut writer = self.writer.lock().unwrap();
+        // A trace is a debugging aid, failing to write one must not fail the build.
+        let _ = writeln!(writer, "{line}");
+    }
+
+    pub fn flush(&self) {
+        let _ = self.writer.lock().unwrap().flush();
+    }
+}
+
+/// Per-span data, stored in the span's extensions until it closes.
+struct SpanData {
+    id: u64,
+    start: Instant,
+    start_unix: SystemTime,
+    attributes: Map<String, Value>,
+}
+
+struct AttributeVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for AttributeVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), json!(format!("{value:?}")));
+    }
+}
+
+pub struct TraceFileLayer {
+    file: Arc<TraceFile>,
+    // `tracing` reuses span ids once a span is closed, so the trace gets its own.
+    next_id: AtomicU64,
+}
+
+impl TraceFileLayer {
+    pub fn new(file: Arc<TraceFile>) -> Self {
+        Self {
+            file,
+            next_id: AtomicU64::new(1),
+        }
+    }
+}
+
+impl<S> Layer<S> for TraceFileLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut attributes = Map::new();
+        attrs.record(&mut AttributeVisitor(&mut attributes));
+        span.extensions_mut().insert(SpanData {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            start: Instant::now(),
+            start_unix: SystemTime::now(),
+            attributes,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+            values.record(&mut AttributeVisitor(&mut data.attributes));
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let parent_id = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<SpanData>().map(|data| data.id));
+        let mut extensions = span.extensions_mut();
+        let Some(data) = extensions.remove::<SpanData>() else {
+            return;
+        };
+        let metadata = span.metadata();
+        self.file.write_line(&json!({
+            "name": metadata.name(),
+            "target": metadata.target(),
+            "level": metadata.level().as_str(),
+            "span_id": data.id,
+            "parent_id": parent_id,
+            "thread": format!("{:?}", std::thread::current().id()),
+            "start_unix_us": data
+                .start_unix
+                .duration_since(UNIX_EPOCH)
+                .map(|since_epoch| since_epoch.as_micros() as u64)
+                .unwrap_or_default(),
+            "duration_us": data.start.elapsed().as_micros() as u64,
+            "attributes": data.attributes,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::info_span;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_closed_spans_as_json_lines() {
+        let buffer = SharedBuffer::default();
+        let file = TraceFile::new(Box::new(buffer.clone()));
+        let subscriber = tracing_subscriber::registry().with(TraceFileLayer::new(file));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = info_span!("build.incremental", initial_build = true);
+            let _outer = outer.enter();
+            let inner = info_span!("build.parse", dirty_modules = 3, module = %"Main");
+            inner.record("dirty_modules", 4);
+            drop(inner);
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines = output
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<Value>>();
+        assert_eq!(lines.len(), 2);
+
+        let (inner, outer) = (&lines[0], &lines[1]);
+        assert_eq!(inner["name"], "build.parse");
+        assert_eq!(inner["level"], "INFO");
+        assert_eq!(
+            inner["attributes"],
+            json!({ "dirty_modules": 4, "module": "Main" })
+        );
+        assert_eq!(inner["parent_id"], outer["span_id"]);
+        assert_eq!(outer["name"], "build.incremental");
+        assert_eq!(outer["attributes"], json!({ "initial_build": true }));
+        assert_eq!(outer["parent_id"], Value::Null);
+    }
+}
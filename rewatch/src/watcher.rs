@@ -1,6 +1,13 @@
+pub mod keys;
+pub mod settings;
+
 use crate::build;
 use crate::build::build_types::{BuildCommandState, SourceType};
 use crate::build::clean;
+use crate::build::events::{self, BuildKind, EventSink};
+use crate::build::source_hashes;
+use crate::build::summary::BuildSummary;
+use crate::cli::{AfterBuildArg, MessageFormat, TimingsFormat, WatchBackend};
 use crate::cmd;
 use crate::config::{self, SourceMapCommand};
 use crate::helpers;
@@ -11,25 +18,66 @@ use crate::queue::*;
 use anyhow::{Context, Result};
 use console::Term;
 use futures_timer::Delay;
-use notify::event::ModifyKind;
-use notify::{Config, Error, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use keys::{KeyCommand, Keys};
+use notify::event::{DataChange, MetadataKind, ModifyKind};
+use notify::{
+    Config, Error, Event, EventHandler, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use settings::WatchSettings;
+use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
 use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
-enum CompileType {
+pub(crate) enum CompileType {
     Incremental,
     Full,
     None,
 }
 
-type WatchPaths = Vec<(PathBuf, RecursiveMode)>;
+pub(crate) type WatchPaths = Vec<(PathBuf, RecursiveMode)>;
+
+static BACKEND: OnceLock<(WatchBackend, Duration)> = OnceLock::new();
+
+/// Selects how file changes are detected for the rest of the process, by the watcher and while
+/// waiting for another build's lock.
+pub fn set_backend(backend: WatchBackend, poll_interval: Duration) {
+    let _ = BACKEND.set((backend, poll_interval));
+}
+
+/// A file watcher for the backend selected with [`set_backend`], the native one by default.
+pub(crate) fn new_watcher(mut handler: impl EventHandler) -> notify::Result<Box<dyn Watcher + Send>> {
+    match BACKEND.get() {
+        Some((WatchBackend::Poll, poll_interval)) => {
+            let watcher = PollWatcher::new(
+                move |res: notify::Result<Event>| handler.handle_event(res.map(normalize_polled_event)),
+                Config::default().with_poll_interval(*poll_interval),
+            )?;
+            Ok(Box::new(watcher))
+        }
+        Some((WatchBackend::Native, _)) | None => {
+            Ok(Box::new(RecommendedWatcher::new(handler, Config::default())?))
+        }
+    }
+}
+
+/// The poll watcher reports a newer modification time as a metadata change, where the native
+/// watchers report the content change we react to.
+fn normalize_polled_event(mut event: Event) -> Event {
+    if event.kind == EventKind::Modify(ModifyKind::Metadata(MetadataKind::WriteTime)) {
+        event.kind = EventKind::Modify(ModifyKind::Data(DataChange::Any));
+    }
+    event
+}
 type StartupBuildResult = (
     BuildCommandState,
     WatchPaths,
-    Option<(Instant, build::CompilationOutcome)>,
+    (
+        Instant,
+        Result<build::CompilationOutcome, build::IncrementalBuildError>,
+    ),
 );
 
 fn is_rescript_file(path_buf: &Path) -> bool {
@@ -65,8 +113,102 @@ fn matches_filter(path_buf: &Path, filter: &Option<regex::Regex>) -> bool {
     filter.as_ref().map(|re| !re.is_match(&name)).unwrap_or(true)
 }
 
+/// The sources and configs an event changed. Reported in the `files-changed` event, and they
+/// cancel a build that's running.
+fn changed_paths(event: &Event, filter: &Option<regex::Regex>, settings: &WatchSettings) -> Vec<PathBuf> {
+    if matches!(
+        event.kind,
+        EventKind::Access(_) | EventKind::Other | EventKind::Modify(ModifyKind::Metadata(_))
+    ) {
+        return Vec::new();
+    }
+    event
+        .paths
+        .iter()
+        .filter(|path| {
+            let is_config = path
+                .file_name()
+                .is_some_and(|name| name == "rescript.json" || name == "bsconfig.json");
+            is_config
+                || (is_rescript_file(path)
+                    && !is_in_build_path(path)
+                    && matches_filter(path, filter)
+                    && !settings.is_ignored(path))
+        })
+        .cloned()
+        .collect()
+}
+
+/// The current `--filter` and watch settings, shared with the file watcher's thread so it can
+/// cancel builds.
+#[derive(Default)]
+struct ChangeFilter {
+    filter: Option<regex::Regex>,
+    settings: WatchSettings,
+}
+
+fn share_change_filter(
+    change_filter: &RwLock<ChangeFilter>,
+    filter: &Option<regex::Regex>,
+    build_state: &BuildCommandState,
+) {
+    *change_filter.write().unwrap() = ChangeFilter {
+        filter: filter.clone(),
+        settings: build_state.watch.clone(),
+    };
+}
+
+fn is_cancelled(result: &Result<build::CompilationOutcome, build::IncrementalBuildError>) -> bool {
+    matches!(
+        result,
+        Err(build::IncrementalBuildError {
+            kind: build::IncrementalBuildErrorKind::Cancelled,
+            ..
+        })
+    )
+}
+
+/// Emits the diagnostics of a finished build and the `build-finished` event.
+fn emit_build_finished(
+    build_state: &BuildCommandState,
+    kind: BuildKind,
+    timing_total: Instant,
+    result: &Result<build::CompilationOutcome, build::IncrementalBuildError>,
+) {
+    build_state.emit(&events::Event::Diagnostics {
+        diagnostics: &build_state.diagnostics,
+    });
+    build_state.emit(&events::Event::BuildFinished {
+        kind,
+        success: result.is_ok(),
+        cancelled: is_cancelled(result),
+        outcome: result.as_ref().ok().copied(),
+        duration_ms: timing_total.elapsed().as_millis(),
+    });
+}
+
+/// A cancelled build left modules to compile, they're compiled along with the changes that
+/// cancelled it.
+fn next_compile_type(
+    result: &Result<build::CompilationOutcome, build::IncrementalBuildError>,
+) -> CompileType {
+    if is_cancelled(result) {
+        CompileType::Incremental
+    } else {
+        CompileType::None
+    }
+}
+
+fn print_build_cancelled(error: &build::IncrementalBuildError, show_progress: bool) {
+    if show_progress {
+        println!("{error}, restarting...");
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn finish_successful_watch_compile(
-    after_build: Option<String>,
+    after_build: &AfterBuildArg,
+    build_state: &BuildCommandState,
     timing_total: Instant,
     show_progress: bool,
     plain_output: bool,
@@ -74,9 +216,11 @@ fn finish_successful_watch_compile(
     compilation_kind: Option<&str>,
     outcome: build::CompilationOutcome,
 ) {
-    if let Some(a) = after_build {
-        cmd::run(a)
-    }
+    report_after_build(cmd::run_after_build(
+        after_build,
+        Some(&BuildSummary::new(build_state, outcome)),
+        build_state.events.as_deref(),
+    ));
     let timing_total_elapsed = timing_total.elapsed();
     if show_progress {
         if plain_output {
@@ -90,9 +234,36 @@ fn finish_successful_watch_compile(
     }
 }
 
+fn finish_failed_watch_compile(
+    after_build: &AfterBuildArg,
+    build_state: &BuildCommandState,
+    clear_screen: bool,
+    show_progress: bool,
+    plain_output: bool,
+) {
+    if should_clear_screen(clear_screen, show_progress, plain_output) {
+        print_build_failed_footer();
+    }
+    report_after_build(cmd::run_after_build(
+        after_build,
+        None,
+        build_state.events.as_deref(),
+    ));
+}
+
+/// A failing after-build command doesn't stop the watcher.
+fn report_after_build(result: Result<()>) {
+    if let Err(err) = result {
+        eprintln!("{err:#}");
+    }
+}
+
 /// Computes the list of paths to watch based on the build state.
 /// Returns tuples of (path, recursive_mode) for each watch target.
-fn compute_watch_paths(build_state: &BuildCommandState, root: &Path) -> Vec<(PathBuf, RecursiveMode)> {
+pub(crate) fn compute_watch_paths(
+    build_state: &BuildCommandState,
+    root: &Path,
+) -> Vec<(PathBuf, RecursiveMode)> {
     // Use a HashMap to deduplicate paths, giving precedence to Recursive mode
     // when the same path appears with different modes (e.g. package root watched
     // NonRecursively for rescript.json changes, but also as a source folder with
@@ -151,7 +322,7 @@ fn compute_watch_paths(build_state: &BuildCommandState, root: &Path) -> Vec<(Pat
 }
 
 /// Registers all watch paths with the given watcher.
-fn register_watches(watcher: &mut RecommendedWatcher, watch_paths: &[(PathBuf, RecursiveMode)]) {
+pub(crate) fn register_watches(watcher: &mut dyn Watcher, watch_paths: &[(PathBuf, RecursiveMode)]) {
     for (path, mode) in watch_paths {
         let mode_str = if *mode == RecursiveMode::Recursive {
             "recursive"
@@ -166,12 +337,25 @@ fn register_watches(watcher: &mut RecommendedWatcher, watch_paths: &[(PathBuf, R
 }
 
 /// Unregisters all watch paths from the given watcher.
-fn unregister_watches(watcher: &mut RecommendedWatcher, watch_paths: &[(PathBuf, RecursiveMode)]) {
+fn unregister_watches(watcher: &mut dyn Watcher, watch_paths: &[(PathBuf, RecursiveMode)]) {
     for (path, _) in watch_paths {
         let _ = watcher.unwatch(path);
     }
 }
 
+/// Command-line options and toggles of the watch session outlive the build state. The watch
+/// settings are read from the new config.
+fn carry_forward_settings(previous: &BuildCommandState, next: &mut BuildCommandState) {
+    next.message_format = previous.message_format;
+    next.explain = previous.explain;
+    next.keep_going = previous.keep_going;
+    next.timings_format = previous.timings_format;
+    next.events = previous.events.clone();
+    next.cancel = previous.cancel.clone();
+    next.hide_warnings = previous.hide_warnings;
+    next.watch = WatchSettings::new(next);
+}
+
 fn carry_forward_compile_warnings(previous: &BuildCommandState, next: &mut BuildCommandState) {
     for (module_name, next_module) in next.build_state.modules.iter_mut() {
         let Some(previous_module) = previous.build_state.modules.get(module_name) else {
@@ -210,6 +394,15 @@ fn carry_forward_compile_warnings(previous: &BuildCommandState, next: &mut Build
     }
 }
 
+/// A `--filter` typed with `f`. Empty means no filter.
+fn parse_filter(pattern: &str) -> Result<Option<regex::Regex>, regex::Error> {
+    if pattern.is_empty() {
+        Ok(None)
+    } else {
+        regex::Regex::new(pattern).map(Some)
+    }
+}
+
 fn should_clear_screen(clear_screen: bool, show_progress: bool, plain_output: bool) -> bool {
     clear_screen && show_progress && !plain_output
 }
@@ -230,7 +423,7 @@ fn print_build_failed_footer() {
     println!("\nBuild failed. Watching for changes...");
 }
 
-fn cleanup_before_watch_exit(
+pub(crate) fn cleanup_before_watch_exit(
     path: &Path,
     build_state: &BuildCommandState,
     show_progress: bool,
@@ -239,19 +432,236 @@ fn cleanup_before_watch_exit(
     if show_progress {
         println!("{message}");
     }
-    build::with_build_lock(path, || clean::cleanup_after_build(build_state));
+    build::with_build_lock(path, || {
+        clean::cleanup_after_build(build_state);
+        build::saved_state::write(build_state);
+    });
+}
+
+pub(crate) fn removes_watch_lock(event: &Event) -> bool {
+    matches!(event.kind, EventKind::Remove(_))
+        && event
+            .paths
+            .iter()
+            .any(|path| path.ends_with(LockKind::Watch.file_name()))
+}
+
+/// Mark the module whose implementation or interface is `path` (canonicalized) for parsing, unless
+/// its content is what it was when it was last parsed.
+pub(crate) fn mark_source_changed(build_state: &mut BuildCommandState, path: &Path) {
+    // Collect package names first to avoid borrow checker issues
+    let module_package_pairs = build_state.module_name_package_pairs();
+
+    for (module_name, package_name) in module_package_pairs {
+        let package = build_state
+            .build_state
+            .packages
+            .get(&package_name)
+            .expect("Package not found");
+
+        if let Some(module) = build_state.build_state.modules.get_mut(&module_name) {
+            match module.source_type {
+                SourceType::SourceFile(ref mut source_file) => {
+                    let canonicalized_implementation_file =
+                        package.path.join(&source_file.implementation.path);
+                    if path == canonicalized_implementation_file {
+                        if let Ok(modified) = path.metadata().and_then(|x| x.modified()) {
+                            source_file.implementation.last_modified = modified;
+                        };
+                        // Rewrites that leave the content as it was last parsed
+                        // (formatters, checkouts) don't need another parse.
+                        if !source_hashes::is_unchanged(&build_state.build_state.source_hashes, path, None) {
+                            source_file.implementation.parse_dirty = true;
+                        }
+                        break;
+                    }
+
+                    // mark the interface file dirty
+                    if let Some(ref mut interface) = source_file.interface {
+                        let canonicalized_interface_file = package.path.join(&interface.path);
+                        if path == canonicalized_interface_file {
+                            if let Ok(modified) = path.metadata().and_then(|x| x.modified()) {
+                                interface.last_modified = modified;
+                            }
+                            if !source_hashes::is_unchanged(
+                                &build_state.build_state.source_hashes,
+                                path,
+                                None,
+                            ) {
+                                interface.parse_dirty = true;
+                            }
+                            break;
+                        }
+                    }
+                }
+                SourceType::MlMap(_) => (),
+            }
+        }
+    }
+}
+
+/// Record what a file system event means for the next build: changed sources are marked dirty,
+/// while added, removed or renamed sources and config changes need a full rebuild.
+pub(crate) fn apply_event(
+    event: &Event,
+    build_state: &mut BuildCommandState,
+    filter: &Option<regex::Regex>,
+    needs_compile_type: &mut CompileType,
+) {
+    // Detect config-file changes and trigger a full rebuild.
+    // Legacy bsconfig.json is accepted for backward compatibility.
+    if event.paths.iter().any(|p| {
+        p.file_name()
+            .map(|name| name == "rescript.json" || name == "bsconfig.json")
+            .unwrap_or(false)
+    }) && matches!(
+        event.kind,
+        EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_)
+    ) {
+        log::debug!("config file changed -> full compile");
+        tracing::debug!(
+            reason = "config file changed",
+            event_kind = ?event.kind,
+            paths = ?event.paths,
+            "watcher.full_compile_triggered"
+        );
+        *needs_compile_type = CompileType::Full;
+        return;
+    }
+
+    let paths: Vec<&PathBuf> = event
+        .paths
+        .iter()
+        .filter(|path| is_rescript_file(path))
+        .filter(|path| !is_in_build_path(path))
+        .filter(|path| matches_filter(path, filter))
+        .filter(|path| !build_state.watch.is_ignored(path))
+        .collect();
+    for path in paths {
+        match (*needs_compile_type, event.kind) {
+            (
+                CompileType::Incremental | CompileType::None,
+                // when we have a name change, create or remove event we need to do a full compile
+                EventKind::Remove(_)
+                | EventKind::Any
+                | EventKind::Create(_)
+                | EventKind::Modify(ModifyKind::Name(_)),
+            ) => {
+                // if we are going to do a full compile, we don't need to bother marking
+                // files dirty because we do a full scan anyway
+                log::debug!(
+                    "received {:?} while needs_compile_type was {needs_compile_type:?} -> full compile",
+                    event.kind
+                );
+                *needs_compile_type = CompileType::Full;
+            }
+
+            (
+                CompileType::None | CompileType::Incremental,
+                // when we have a data change event, we can do an incremental compile
+                EventKind::Modify(ModifyKind::Data(_)) |
+                // windows sends ModifyKind::Any on file content changes
+                EventKind::Modify(ModifyKind::Any),
+            ) => {
+                // if we are going to compile incrementally, we need to mark the exact files
+                // dirty
+                log::debug!(
+                    "received {:?} while needs_compile_type was {needs_compile_type:?} -> incremental compile",
+                    event.kind
+                );
+                if let Ok(canonicalized_path_buf) = path
+                    .canonicalize()
+                    .map(StrippedVerbatimPath::to_stripped_verbatim_path)
+                {
+                    mark_source_changed(build_state, &canonicalized_path_buf);
+                    *needs_compile_type = CompileType::Incremental;
+                }
+            }
+
+            (
+                CompileType::None | CompileType::Incremental,
+                // these are not relevant events for compilation
+                EventKind::Access(_)
+                | EventKind::Other
+                | EventKind::Modify(ModifyKind::Metadata(_))
+                | EventKind::Modify(ModifyKind::Other),
+            ) => (),
+            // if we already need a full compile, we don't need to check for other events
+            (CompileType::Full, _) => (),
+        }
+    }
+}
+
+/// Rediscover packages and modules, then build. Needed when sources were added, removed or
+/// renamed, or a config changed. The options of `build_state`, and the warnings of modules that
+/// didn't change, carry over to the new state. Reinitialization cleans up previous build
+/// artifacts, so callers need to hold the build lock. When the new state can't be initialized,
+/// e.g. because `rescript.json` is invalid, `build_state` is left as it was.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn full_rebuild_without_lock(
+    build_state: &mut BuildCommandState,
+    watcher: &mut dyn Watcher,
+    current_watch_paths: &mut WatchPaths,
+    path: &Path,
+    filter: &Option<regex::Regex>,
+    show_progress: bool,
+    plain_output: bool,
+    prod: bool,
+    features: Option<Vec<String>>,
+    create_sourcedirs: bool,
+    source_map_command: SourceMapCommand,
+) -> Result<build::CompilationOutcome, build::IncrementalBuildError> {
+    let mut next_build_state = build::initialize_build(
+        None,
+        filter,
+        show_progress,
+        path,
+        plain_output,
+        build_state.get_warn_error_override(),
+        prod,
+        features,
+        source_map_command,
+    )
+    .map_err(|err| build::IncrementalBuildError {
+        plain_output,
+        kind: build::IncrementalBuildErrorKind::InitializationError(format!("{err:#}")),
+    })?;
+
+    // Full rebuilds can be triggered by editor atomic saves that surface as rename events.
+    // Preserve warning state for unchanged modules so their warnings are re-emitted after the
+    // fresh build state replaces the previous one.
+    carry_forward_compile_warnings(build_state, &mut next_build_state);
+    carry_forward_settings(build_state, &mut next_build_state);
+    *build_state = next_build_state;
+
+    // Re-register watches based on the new build state
+    unregister_watches(watcher, current_watch_paths);
+    *current_watch_paths = compute_watch_paths(build_state, path);
+    register_watches(watcher, current_watch_paths);
+
+    build::incremental_build_without_lock(
+        build_state,
+        None,
+        false,
+        show_progress,
+        false,
+        create_sourcedirs,
+        plain_output,
+    )
 }
 
 struct AsyncWatchArgs<'a> {
-    watcher: &'a mut RecommendedWatcher,
+    watcher: &'a mut dyn Watcher,
     current_watch_paths: Vec<(PathBuf, RecursiveMode)>,
     initial_build_state: BuildCommandState,
     q: Arc<FifoQueue<Result<Event, Error>>>,
+    change_filter: Arc<RwLock<ChangeFilter>>,
+    keys: Option<Keys>,
     ctrlc_pressed: Arc<AtomicBool>,
     path: &'a Path,
     show_progress: bool,
     filter: &'a Option<regex::Regex>,
-    after_build: Option<String>,
+    after_build: AfterBuildArg,
     create_sourcedirs: bool,
     plain_output: bool,
     clear_screen: bool,
@@ -265,6 +675,8 @@ async fn async_watch(
         mut current_watch_paths,
         initial_build_state,
         q,
+        change_filter,
+        keys,
         ctrlc_pressed,
         path,
         show_progress,
@@ -278,31 +690,63 @@ async fn async_watch(
     }: AsyncWatchArgs<'_>,
 ) -> Result<()> {
     let mut build_state = initial_build_state;
+    let mut filter = filter.clone();
     let mut needs_compile_type = CompileType::None;
+    let mut paused = false;
+    let mut pending_changed_paths: BTreeSet<PathBuf> = BTreeSet::new();
     loop {
         if ctrlc_pressed.load(Ordering::SeqCst) {
             cleanup_before_watch_exit(path, &build_state, show_progress, "\nExiting...");
             break Ok(());
         }
-        let mut events: Vec<Event> = vec![];
-        if !q.is_empty() {
-            // Wait for events to settle
-            Delay::new(Duration::from_millis(50)).await;
+        while let Some(command) = keys.as_ref().and_then(Keys::next_command) {
+            match command {
+                KeyCommand::Rebuild => needs_compile_type = CompileType::Full,
+                KeyCommand::ClearScreen => clear_terminal_screen(),
+                KeyCommand::ToggleWarnings => {
+                    build_state.hide_warnings = !build_state.hide_warnings;
+                    if build_state.hide_warnings {
+                        println!("Warnings hidden. Press w to show them.");
+                    } else {
+                        println!("Warnings shown.");
+                    }
+                }
+                KeyCommand::SetFilter(pattern) => match parse_filter(&pattern) {
+                    Ok(new_filter) => {
+                        filter = new_filter;
+                        needs_compile_type = CompileType::Full;
+                    }
+                    Err(err) => println!("Invalid filter, keeping the current one: {err}"),
+                },
+                KeyCommand::TogglePause => {
+                    paused = !paused;
+                    if paused {
+                        println!("Paused. Changes are recorded, press p to build them.");
+                    } else {
+                        println!("Resumed.");
+                    }
+                }
+                KeyCommand::Quit => {
+                    cleanup_before_watch_exit(path, &build_state, show_progress, "\nExiting...");
+                    return Ok(());
+                }
+            }
         }
+        let mut events: Vec<Event> = vec![];
+        // Wait for events to settle: a burst of writes, e.g. from a code generator, only
+        // starts a build once no new events arrived for the debounce duration.
         while !q.is_empty() {
-            if let Ok(event) = q.pop() {
-                events.push(event)
+            while !q.is_empty() {
+                if let Ok(event) = q.pop() {
+                    events.push(event)
+                }
             }
+            Delay::new(build_state.watch.debounce).await;
         }
 
         for event in events {
             // If watch.lock is removed, we can quit the watcher.
-            if event
-                .paths
-                .iter()
-                .any(|path| path.ends_with(LockKind::Watch.file_name()))
-                && let EventKind::Remove(_) = event.kind
-            {
+            if removes_watch_lock(&event) {
                 cleanup_before_watch_exit(
                     path,
                     &build_state,
@@ -312,133 +756,31 @@ async fn async_watch(
                 return Ok(());
             }
 
-            // Detect config-file changes and trigger a full rebuild.
-            // Legacy bsconfig.json is accepted for backward compatibility.
-            if event.paths.iter().any(|p| {
-                p.file_name()
-                    .map(|name| name == "rescript.json" || name == "bsconfig.json")
-                    .unwrap_or(false)
-            }) && matches!(
-                event.kind,
-                EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_)
-            ) {
-                log::debug!("config file changed -> full compile");
-                tracing::debug!(
-                    reason = "config file changed",
-                    event_kind = ?event.kind,
-                    paths = ?event.paths,
-                    "watcher.full_compile_triggered"
-                );
-                needs_compile_type = CompileType::Full;
-                continue;
-            }
-
-            let paths = event
-                .paths
-                .iter()
-                .filter(|path| is_rescript_file(path))
-                .filter(|path| !is_in_build_path(path))
-                .filter(|path| matches_filter(path, filter));
-            for path in paths {
-                let path_buf = path.to_path_buf();
-
-                match (needs_compile_type, event.kind) {
-                    (
-                        CompileType::Incremental | CompileType::None,
-                        // when we have a name change, create or remove event we need to do a full compile
-                        EventKind::Remove(_)
-                        | EventKind::Any
-                        | EventKind::Create(_)
-                        | EventKind::Modify(ModifyKind::Name(_)),
-                    ) => {
-                        // if we are going to do a full compile, we don't need to bother marking
-                        // files dirty because we do a full scan anyway
-                        log::debug!("received {:?} while needs_compile_type was {needs_compile_type:?} -> full compile", event.kind);
-                        needs_compile_type = CompileType::Full;
-                    }
-
-                    (
-                        CompileType::None | CompileType::Incremental,
-                        // when we have a data change event, we can do an incremental compile
-                        EventKind::Modify(ModifyKind::Data(_)) |
-                        // windows sends ModifyKind::Any on file content changes
-                        EventKind::Modify(ModifyKind::Any),
-                    ) => {
-                        // if we are going to compile incrementally, we need to mark the exact files
-                        // dirty
-                        log::debug!("received {:?} while needs_compile_type was {needs_compile_type:?} -> incremental compile", event.kind);
-                        if let Ok(canonicalized_path_buf) = path_buf
-                            .canonicalize()
-                            .map(StrippedVerbatimPath::to_stripped_verbatim_path)
-                        {
-                            // Collect package names first to avoid borrow checker issues
-                            let module_package_pairs = build_state.module_name_package_pairs();
-
-                            for (module_name, package_name) in module_package_pairs {
-                                let package = build_state
-                                    .build_state
-                                    .packages
-                                    .get(&package_name)
-                                    .expect("Package not found");
-
-                                if let Some(module) = build_state.build_state.modules.get_mut(&module_name) {
-                                    match module.source_type {
-                                        SourceType::SourceFile(ref mut source_file) => {
-                                        let canonicalized_implementation_file =
-                                            package.path.join(&source_file.implementation.path);
-                                        if canonicalized_path_buf == canonicalized_implementation_file {
-                                            if let Ok(modified) =
-                                                canonicalized_path_buf.metadata().and_then(|x| x.modified())
-                                            {
-                                                source_file.implementation.last_modified = modified;
-                                            };
-                                            source_file.implementation.parse_dirty = true;
-                                            break;
-                                        }
-
-                                        // mark the interface file dirty
-                                        if let Some(ref mut interface) = source_file.interface {
-                                            let canonicalized_interface_file =
-                                                package.path.join(&interface.path);
-                                            if canonicalized_path_buf == canonicalized_interface_file {
-                                                if let Ok(modified) = canonicalized_path_buf
-                                                    .metadata()
-                                                    .and_then(|x| x.modified())
-                                                {
-                                                    interface.last_modified = modified;
-                                                }
-                                                interface.parse_dirty = true;
-                                                break;
-                                            }
-                                        }
-                                        }
-                                        SourceType::MlMap(_) => (),
-                                    }
-                                }
-                            }
-                            needs_compile_type = CompileType::Incremental;
-                        }
-                    }
-
-                    (
-                        CompileType::None | CompileType::Incremental,
-                        // these are not relevant events for compilation
-                        EventKind::Access(_)
-                        | EventKind::Other
-                        | EventKind::Modify(ModifyKind::Metadata(_))
-                        | EventKind::Modify(ModifyKind::Other),
-                    ) => (),
-                    // if we already need a full compile, we don't need to check for other events
-                    (CompileType::Full, _) => (),
-                }
+            if build_state.events.is_some() {
+                pending_changed_paths.extend(changed_paths(&event, &filter, &build_state.watch));
             }
+            apply_event(&event, &mut build_state, &filter, &mut needs_compile_type);
         }
 
-        if needs_compile_type != CompileType::None {
+        // While paused, changes keep accumulating in `needs_compile_type`.
+        let compile_type = if paused {
+            CompileType::None
+        } else {
+            needs_compile_type
+        };
+        if compile_type != CompileType::None {
             log::debug!("doing {needs_compile_type:?}");
+            let paths: Vec<PathBuf> = std::mem::take(&mut pending_changed_paths).into_iter().collect();
+            build_state.emit(&events::Event::FilesChanged { paths: &paths });
+            build_state.emit(&events::Event::BuildStarted {
+                kind: match compile_type {
+                    CompileType::Full => BuildKind::Full,
+                    _ => BuildKind::Incremental,
+                },
+            });
         }
 
-        match needs_compile_type {
+        match compile_type {
             CompileType::Incremental => {
                 if should_clear_screen(clear_screen, show_progress, plain_output) {
                     clear_terminal_screen();
@@ -455,11 +797,13 @@ async fn async_watch(
                     create_sourcedirs,
                     plain_output,
                 );
+                emit_build_finished(&build_state, BuildKind::Incremental, timing_total, &result);
 
                 match result {
                     Ok(result) => {
                         finish_successful_watch_compile(
-                            after_build.clone(),
+                            &after_build,
+                            &build_state,
                             timing_total,
                             show_progress,
                             plain_output,
@@ -468,14 +812,17 @@ async fn async_watch(
                             result,
                         );
                     }
-                    Err(_) => {
-                        if should_clear_screen(clear_screen, show_progress, plain_output) {
-                            print_build_failed_footer();
-                        }
-                    }
+                    Err(ref e) if is_cancelled(&result) => print_build_cancelled(e, show_progress),
+                    Err(_) => finish_failed_watch_compile(
+                        &after_build,
+                        &build_state,
+                        clear_screen,
+                        show_progress,
+                        plain_output,
+                    ),
                 }
 
-                needs_compile_type = CompileType::None;
+                needs_compile_type = next_compile_type(&result);
             }
             CompileType::Full => {
                 if should_clear_screen(clear_screen, show_progress, plain_output) {
@@ -487,46 +834,29 @@ async fn async_watch(
                 // Reinitialization runs cleanup for previous build artifacts, so full rebuilds need
                 // the same build lock boundary as regular `rescript build`.
                 let result = build::with_build_lock(path, || {
-                    let mut next_build_state = build::initialize_build(
-                        None,
-                        filter,
-                        show_progress,
+                    let result = full_rebuild_without_lock(
+                        &mut build_state,
+                        watcher,
+                        &mut current_watch_paths,
                         path,
+                        &filter,
+                        show_progress,
                         plain_output,
-                        build_state.get_warn_error_override(),
                         prod,
                         features.clone(),
-                        SourceMapCommand::Watch,
-                    )
-                    .expect("Could not initialize build");
-
-                    // Full rebuilds can be triggered by editor atomic saves that surface as rename events.
-                    // Preserve warning state for unchanged modules so their warnings are re-emitted after the
-                    // fresh build state replaces the previous one.
-                    carry_forward_compile_warnings(&build_state, &mut next_build_state);
-                    build_state = next_build_state;
-
-                    // Re-register watches based on the new build state
-                    unregister_watches(watcher, &current_watch_paths);
-                    current_watch_paths = compute_watch_paths(&build_state, path);
-                    register_watches(watcher, &current_watch_paths);
-
-                    let result = build::incremental_build_without_lock(
-                        &mut build_state,
-                        None,
-                        false,
-                        show_progress,
-                        false,
                         create_sourcedirs,
-                        plain_output,
+                        SourceMapCommand::Watch,
                     );
                     build::write_build_ninja(&build_state);
                     result
                 });
+                share_change_filter(&change_filter, &filter, &build_state);
+                emit_build_finished(&build_state, BuildKind::Full, timing_total, &result);
                 match result {
                     Ok(result) => {
                         finish_successful_watch_compile(
-                            after_build.clone(),
+                            &after_build,
+                            &build_state,
                             timing_total,
                             show_progress,
                             plain_output,
@@ -535,13 +865,16 @@ async fn async_watch(
                             result,
                         );
                     }
-                    Err(_) => {
-                        if should_clear_screen(clear_screen, show_progress, plain_output) {
-                            print_build_failed_footer();
-                        }
-                    }
+                    Err(ref e) if is_cancelled(&result) => print_build_cancelled(e, show_progress),
+                    Err(_) => finish_failed_watch_compile(
+                        &after_build,
+                        &build_state,
+                        clear_screen,
+                        show_progress,
+                        plain_output,
+                    ),
                 }
-                needs_compile_type = CompileType::None;
+                needs_compile_type = next_compile_type(&result);
             }
             CompileType::None => {
                 // We want to sleep for a little while so the CPU can schedule other work. That way we end
@@ -557,21 +890,40 @@ pub fn start(
     filter: &Option<regex::Regex>,
     show_progress: bool,
     folder: &str,
-    after_build: Option<String>,
+    after_build: AfterBuildArg,
     create_sourcedirs: bool,
     plain_output: bool,
     warn_error: Option<String>,
     clear_screen: bool,
     prod: bool,
     features: Option<Vec<String>>,
+    message_format: MessageFormat,
+    explain: bool,
+    keep_going: bool,
+    timings_format: Option<TimingsFormat>,
+    events: Option<EventSink>,
 ) -> Result<()> {
     futures::executor::block_on(async {
         let queue = Arc::new(FifoQueue::<Result<Event, Error>>::new());
         let producer = queue.clone();
         let consumer = queue.clone();
 
-        let mut watcher = RecommendedWatcher::new(move |res| producer.push(res), Config::default())
-            .expect("Could not create watcher");
+        // Set as soon as a source changes, so a running build stops dispatching modules and the
+        // watcher restarts it with the new changes.
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cancel_for_watcher = Arc::clone(&cancel);
+        let change_filter = Arc::new(RwLock::new(ChangeFilter::default()));
+        let change_filter_for_watcher = Arc::clone(&change_filter);
+        let mut watcher = new_watcher(move |res: Result<Event, Error>| {
+            if let Ok(event) = &res {
+                let change_filter = change_filter_for_watcher.read().unwrap();
+                if !changed_paths(event, &change_filter.filter, &change_filter.settings).is_empty() {
+                    cancel_for_watcher.store(true, Ordering::SeqCst);
+                }
+            }
+            producer.push(res)
+        })
+        .expect("Could not create watcher");
 
         let path = Path::new(folder);
 
@@ -598,12 +950,23 @@ pub fn start(
                     SourceMapCommand::Watch,
                 )
                 .with_context(|| "Could not initialize build")?;
+                build_state.message_format = message_format;
+                build_state.explain = explain;
+                build_state.keep_going = keep_going;
+                build_state.timings_format = timings_format;
+                build_state.events = events.map(Arc::new);
+                build_state.watch = WatchSettings::new(&build_state);
+                share_change_filter(&change_filter, filter, &build_state);
+                build_state.cancel = Some(cancel);
 
                 // Compute and register targeted watches based on source folders.
                 let current_watch_paths = compute_watch_paths(&build_state, path);
-                register_watches(&mut watcher, &current_watch_paths);
+                register_watches(watcher.as_mut(), &current_watch_paths);
 
                 let timing_total = Instant::now();
+                build_state.emit(&events::Event::BuildStarted {
+                    kind: BuildKind::Initial,
+                });
                 let initial_compile_result = build::incremental_build_without_lock(
                     &mut build_state,
                     None,
@@ -612,14 +975,17 @@ pub fn start(
                     false,
                     create_sourcedirs,
                     plain_output,
-                )
-                .ok()
-                .map(|result| (timing_total, result));
-
+                );
+                emit_build_finished(
+                    &build_state,
+                    BuildKind::Initial,
+                    timing_total,
+                    &initial_compile_result,
+                );
                 Ok::<StartupBuildResult, anyhow::Error>((
                     build_state,
                     current_watch_paths,
-                    initial_compile_result,
+                    (timing_total, initial_compile_result),
                 ))
             })?;
 
@@ -629,23 +995,39 @@ pub fn start(
         }
 
         // Run after-build outside build.lock. Hooks may invoke ReScript commands that need the same lock.
-        if let Some((timing_total, result)) = initial_compile_result {
-            finish_successful_watch_compile(
-                after_build.clone(),
+        let (timing_total, initial_compile_result) = initial_compile_result;
+        match initial_compile_result {
+            Ok(result) => finish_successful_watch_compile(
+                &after_build,
+                &build_state,
                 timing_total,
                 show_progress,
                 plain_output,
                 "Finished initial compilation",
                 Some("initial"),
                 result,
-            );
+            ),
+            Err(_) if is_cancelled(&initial_compile_result) => (),
+            Err(_) => report_after_build(cmd::run_after_build(
+                &after_build,
+                None,
+                build_state.events.as_deref(),
+            )),
         }
 
+        // Single-key commands need a terminal, and their messages would mix with JSON output.
+        let keys = (show_progress && !plain_output).then(|| {
+            println!("{}", keys::HELP);
+            Keys::listen()
+        });
+
         async_watch(AsyncWatchArgs {
-            watcher: &mut watcher,
+            watcher: watcher.as_mut(),
             current_watch_paths,
             initial_build_state: build_state,
             q: consumer,
+            change_filter,
+            keys,
             ctrlc_pressed,
             path,
             show_progress,
@@ -796,6 +1178,65 @@ mod tests {
         assert!(!should_clear_screen(false, true, false));
     }
 
+    #[test]
+    fn polled_write_time_changes_are_content_changes() {
+        let polled = Event::new(EventKind::Modify(ModifyKind::Metadata(MetadataKind::WriteTime)))
+            .add_path(PathBuf::from("/project/src/App.res"));
+        let event = normalize_polled_event(polled);
+        assert_eq!(event.kind, EventKind::Modify(ModifyKind::Data(DataChange::Any)));
+        assert_eq!(
+            changed_paths(&event, &None, &WatchSettings::default()),
+            vec![PathBuf::from("/project/src/App.res")]
+        );
+
+        let removed = Event::new(EventKind::Remove(notify::event::RemoveKind::Any));
+        assert_eq!(normalize_polled_event(removed.clone()).kind, removed.kind);
+    }
+
+    #[test]
+    fn full_rebuild_reports_an_invalid_config_instead_of_panicking() {
+        let temp_dir = tempfile::TempDir::new().expect("temp dir should be created");
+        std::fs::write(temp_dir.path().join("rescript.json"), "{ not json")
+            .expect("config should be written");
+        let mut build_state = test_build_state("ModuleA", test_module("src/ModuleA.res", None, None, None));
+
+        let result = full_rebuild_without_lock(
+            &mut build_state,
+            &mut notify::NullWatcher,
+            &mut Vec::new(),
+            temp_dir.path(),
+            &None,
+            false,
+            true,
+            false,
+            None,
+            true,
+            SourceMapCommand::Watch,
+        );
+
+        assert!(matches!(
+            result,
+            Err(build::IncrementalBuildError {
+                kind: build::IncrementalBuildErrorKind::InitializationError(_),
+                ..
+            })
+        ));
+        assert!(build_state.get_module("ModuleA").is_some());
+    }
+
+    #[test]
+    fn hidden_warnings_stay_hidden_after_a_full_rebuild() {
+        let mut previous = test_build_state("ModuleA", test_module("src/ModuleA.res", None, None, None));
+        previous.hide_warnings = true;
+        previous.keep_going = true;
+        let mut next = test_build_state("ModuleA", test_module("src/ModuleA.res", None, None, None));
+
+        carry_forward_settings(&previous, &mut next);
+
+        assert!(next.hide_warnings);
+        assert!(next.keep_going);
+    }
+
     #[test]
     fn carries_forward_implementation_warnings_for_matching_module_paths() {
         let previous = test_build_state(
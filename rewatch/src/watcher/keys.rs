@@ -0,0 +1,129 @@
+//! Single-key commands of `rescript watch` when it runs in a terminal.
+
+use console::{Key, Term};
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+pub const HELP: &str = "Press r to rebuild, c to clear the screen, w to toggle warnings, f to change the filter, p to pause, q to quit";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyCommand {
+    /// `r`: rediscover packages and sources and rebuild.
+    Rebuild,
+    /// `c`
+    ClearScreen,
+    /// `w`: show or hide compiler warnings.
+    ToggleWarnings,
+    /// `f`: the new `--filter` regex, empty to compile all sources.
+    SetFilter(String),
+    /// `p`: stop or resume building on changes.
+    TogglePause,
+    /// `q`
+    Quit,
+}
+
+/// Reads commands from the terminal on a background thread.
+pub struct Keys {
+    commands: Receiver<KeyCommand>,
+    /// Reading a key switches the terminal to raw mode until the key arrives. The reader thread
+    /// is still waiting for one when the watcher exits, so the mode is restored on drop.
+    #[cfg(unix)]
+    terminal_mode: Option<libc::termios>,
+}
+
+impl Keys {
+    pub fn listen() -> Self {
+        #[cfg(unix)]
+        let terminal_mode = unix::terminal_mode();
+        let (sender, commands) = mpsc::channel();
+        thread::spawn(move || {
+            let term = Term::stdout();
+            loop {
+                let command = match term.read_key() {
+                    Ok(Key::Char('f')) => {
+                        print!("Filter regex (empty for all sources): ");
+                        let _ = io::stdout().flush();
+                        match term.read_line() {
+                            Ok(pattern) => Some(KeyCommand::SetFilter(pattern.trim().to_string())),
+                            Err(_) => None,
+                        }
+                    }
+                    Ok(key) => command_for_key(&key),
+                    // Ctrl-C, which console turns into a SIGINT for the Ctrl-C handler.
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => None,
+                    Err(_) => return,
+                };
+                if let Some(command) = command
+                    && sender.send(command).is_err()
+                {
+                    return;
+                }
+            }
+        });
+        Self {
+            commands,
+            #[cfg(unix)]
+            terminal_mode,
+        }
+    }
+
+    /// The next command typed since the last call, if any.
+    pub fn next_command(&self) -> Option<KeyCommand> {
+        self.commands.try_recv().ok()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Keys {
+    fn drop(&mut self) {
+        if let Some(mode) = &self.terminal_mode {
+            unix::restore_terminal_mode(mode);
+        }
+    }
+}
+
+fn command_for_key(key: &Key) -> Option<KeyCommand> {
+    match key {
+        Key::Char('r') => Some(KeyCommand::Rebuild),
+        Key::Char('c') => Some(KeyCommand::ClearScreen),
+        Key::Char('w') => Some(KeyCommand::ToggleWarnings),
+        Key::Char('p') => Some(KeyCommand::TogglePause),
+        Key::Char('q') => Some(KeyCommand::Quit),
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    pub fn terminal_mode() -> Option<libc::termios> {
+        let mut mode = std::mem::MaybeUninit::uninit();
+        // SAFETY: tcgetattr only writes the termios struct it's given.
+        match unsafe { libc::tcgetattr(libc::STDIN_FILENO, mode.as_mut_ptr()) } {
+            // SAFETY: tcgetattr succeeded, so it initialized the struct.
+            0 => Some(unsafe { mode.assume_init() }),
+            _ => None,
+        }
+    }
+
+    pub fn restore_terminal_mode(mode: &libc::termios) {
+        // SAFETY: the mode was read from the same file descriptor with tcgetattr.
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, mode) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_keys_to_commands() {
+        assert_eq!(command_for_key(&Key::Char('r')), Some(KeyCommand::Rebuild));
+        assert_eq!(command_for_key(&Key::Char('c')), Some(KeyCommand::ClearScreen));
+        assert_eq!(command_for_key(&Key::Char('w')), Some(KeyCommand::ToggleWarnings));
+        assert_eq!(command_for_key(&Key::Char('p')), Some(KeyCommand::TogglePause));
+        assert_eq!(command_for_key(&Key::Char('q')), Some(KeyCommand::Quit));
+        assert_eq!(command_for_key(&Key::Char('x')), None);
+        assert_eq!(command_for_key(&Key::Enter), None);
+    }
+}
@@ -0,0 +1,159 @@
+//! The `watch` settings of the root `rescript.json`, and the `.gitignore` files the watcher
+//! honours.
+
+use crate::build::build_types::BuildCommandState;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long the watcher waits for file changes to settle when `watch.debounce` isn't set.
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone)]
+pub struct WatchSettings {
+    /// Changes are collected until none arrived for this long, then one build handles all of them.
+    pub debounce: Duration,
+    /// Each matcher applies to the files under its own root.
+    ignores: Vec<Gitignore>,
+}
+
+impl Default for WatchSettings {
+    fn default() -> Self {
+        Self {
+            debounce: DEFAULT_DEBOUNCE,
+            ignores: Vec::new(),
+        }
+    }
+}
+
+impl WatchSettings {
+    /// Reads the `watch` section of the root config, the `.gitignore` files of the local packages,
+    /// and those of the folders between the project root and the root of its git repository.
+    pub fn new(build_state: &BuildCommandState) -> Self {
+        let root = build_state.project_context.get_root_path();
+        let watch_config = build_state.get_root_config().watch.as_ref();
+
+        let mut ignores: Vec<Gitignore> = enclosing_gitignores(root)
+            .into_iter()
+            .filter_map(|file| gitignore(&file, &[]))
+            .collect();
+        let root_patterns = watch_config
+            .and_then(|watch| watch.ignore.as_deref())
+            .unwrap_or_default();
+        ignores.extend(gitignore(&root.join(".gitignore"), root_patterns));
+        for package in build_state.packages.values() {
+            if package.is_local_dep && package.path != root {
+                ignores.extend(gitignore(&package.path.join(".gitignore"), &[]));
+            }
+        }
+
+        Self {
+            debounce: watch_config
+                .and_then(|watch| watch.debounce)
+                .map_or(DEFAULT_DEBOUNCE, Duration::from_millis),
+            ignores,
+        }
+    }
+
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        self.ignores.iter().any(|ignore| {
+            path.starts_with(ignore.path()) && ignore.matched_path_or_any_parents(path, false).is_ignore()
+        })
+    }
+}
+
+/// A matcher for the patterns of a `.gitignore` file, if it exists, followed by `patterns`. Patterns
+/// are relative to the folder of the `.gitignore` file. `None` when there is nothing to match.
+fn gitignore(file: &Path, patterns: &[String]) -> Option<Gitignore> {
+    let root = file.parent()?;
+    let mut builder = GitignoreBuilder::new(root);
+    if file.exists()
+        && let Some(err) = builder.add(file)
+    {
+        log::warn!("Could not read {}: {err}", file.to_string_lossy());
+    }
+    for pattern in patterns {
+        if let Err(err) = builder.add_line(None, pattern) {
+            log::warn!("Invalid watch.ignore pattern {pattern:?}: {err}");
+        }
+    }
+    match builder.build() {
+        Ok(gitignore) if !gitignore.is_empty() => Some(gitignore),
+        Ok(_) => None,
+        Err(err) => {
+            log::warn!("Could not read {}: {err}", file.to_string_lossy());
+            None
+        }
+    }
+}
+
+/// The `.gitignore` files of the folders above `root`, up to the root of the git repository.
+/// Empty when `root` isn't inside a git repository, or is its root.
+fn enclosing_gitignores(root: &Path) -> Vec<PathBuf> {
+    if root.join(".git").exists() {
+        return Vec::new();
+    }
+    let mut files = Vec::new();
+    for folder in root.ancestors().skip(1) {
+        files.push(folder.join(".gitignore"));
+        if folder.join(".git").exists() {
+            return files;
+        }
+    }
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn settings(ignores: Vec<Gitignore>) -> WatchSettings {
+        WatchSettings {
+            ignores,
+            ..WatchSettings::default()
+        }
+    }
+
+    #[test]
+    fn ignores_gitignored_files_and_patterns() {
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        let root = temp_dir.path();
+        fs::write(root.join(".gitignore"), "src/generated/\n").unwrap();
+        let patterns = vec!["**/*.gen.res".to_string(), "!src/Keep.gen.res".to_string()];
+        let settings = settings(
+            gitignore(&root.join(".gitignore"), &patterns)
+                .into_iter()
+                .collect(),
+        );
+
+        assert!(settings.is_ignored(&root.join("src/generated/Api.res")));
+        assert!(settings.is_ignored(&root.join("src/Types.gen.res")));
+        assert!(!settings.is_ignored(&root.join("src/Keep.gen.res")));
+        assert!(!settings.is_ignored(&root.join("src/App.res")));
+        assert!(!settings.is_ignored(Path::new("/elsewhere/src/generated/Api.res")));
+    }
+
+    #[test]
+    fn no_matcher_without_patterns() {
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        assert!(gitignore(&temp_dir.path().join(".gitignore"), &[]).is_none());
+    }
+
+    #[test]
+    fn collects_gitignores_up_to_the_repository_root() {
+        let temp_dir = TempDir::new().expect("temp dir should be created");
+        let repo = temp_dir.path().join("repo");
+        let project = repo.join("apps/web");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(&project).unwrap();
+
+        assert_eq!(
+            enclosing_gitignores(&project),
+            vec![repo.join("apps/.gitignore"), repo.join(".gitignore")]
+        );
+        assert!(enclosing_gitignores(&repo).is_empty());
+        assert!(enclosing_gitignores(temp_dir.path()).is_empty());
+    }
+}
@@ -0,0 +1,138 @@
+//! `rescript why`: explain how one module depends on another, and why a module was part of
+//! the compile universe of the last build.
+
+use crate::build::{
+    self,
+    compile_universe::{self, CompileReason},
+};
+use crate::graph::{self, ModuleGraph};
+use crate::helpers;
+use crate::project_context::ProjectContext;
+use anyhow::{Context, Result, anyhow};
+use std::path::Path;
+use tracing::instrument;
+
+#[instrument(name = "why.why", skip_all, fields(working_dir = %path.display()))]
+pub fn why(path: &Path, module: &str, dependency: Option<&str>, prod: bool) -> Result<()> {
+    match dependency {
+        Some(dependency) => explain_dependency(path, module, dependency, prod),
+        None => explain_last_build(path, module),
+    }
+}
+
+fn explain_dependency(path: &Path, module: &str, dependency: &str, prod: bool) -> Result<()> {
+    let build_state = build::load_module_graph(&None, path, prod, None)?;
+    let graph = ModuleGraph::from_modules(&build_state.modules);
+    let from = graph.resolve_module(module)?;
+    let to = graph.resolve_module(dependency)?;
+
+    let chain = graph.shortest_path(&from, &to).ok_or_else(|| {
+        anyhow!(
+            "{} does not depend on {}",
+            helpers::format_namespaced_module_name(&from),
+            helpers::format_namespaced_module_name(&to)
+        )
+    })?;
+    println!("{}", format_dependency_chain(&chain, &graph));
+    Ok(())
+}
+
+fn format_dependency_chain(chain: &[String], graph: &ModuleGraph) -> String {
+    chain
+        .iter()
+        .map(|id| {
+            let display_name = helpers::format_namespaced_module_name(id);
+            match graph.nodes.get(id) {
+                Some(graph::Node {
+                    package,
+                    path: Some(path),
+                }) => format!("{display_name} ({path} in {package})"),
+                Some(graph::Node { package, path: None }) => format!("{display_name} ({package})"),
+                None => display_name,
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n → ")
+}
+
+fn explain_last_build(path: &Path, module: &str) -> Result<()> {
+    let project_context = ProjectContext::new(path)?;
+    let universe = compile_universe::read(project_context.get_root_path())?;
+
+    let module_name = graph::resolve_module_name(universe.modules.keys(), module)
+        .with_context(|| format!("Could not look up {module} among the modules of the last build"))?;
+    let chain = universe
+        .chain(&module_name)
+        .ok_or_else(|| anyhow!("Could not trace why {module} was compiled in the last build"))?;
+    println!("{}", format_compile_chain(&chain));
+    Ok(())
+}
+
+fn format_compile_chain(chain: &[(&str, &CompileReason)]) -> String {
+    let Some((module, _)) = chain.first() else {
+        return String::new();
+    };
+    let reasons = chain
+        .iter()
+        .map(|(module, reason)| {
+            format!(
+                "{}: {}",
+                helpers::format_namespaced_module_name(module),
+                reason.describe()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n → ");
+    format!(
+        "{} was compiled in the last build:\n{reasons}",
+        helpers::format_namespaced_module_name(module)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[test]
+    fn formats_dependency_chain_with_paths() {
+        let graph = ModuleGraph {
+            nodes: BTreeMap::from([
+                (
+                    "App".to_string(),
+                    graph::Node {
+                        package: "app".to_string(),
+                        path: Some("src/App.res".to_string()),
+                    },
+                ),
+                (
+                    "Button-Ui".to_string(),
+                    graph::Node {
+                        package: "ui".to_string(),
+                        path: Some("src/Button.res".to_string()),
+                    },
+                ),
+            ]),
+            edges: BTreeSet::from([("App".to_string(), "Button-Ui".to_string())]),
+            package_level: false,
+        };
+
+        assert_eq!(
+            format_dependency_chain(&["App".to_string(), "Button-Ui".to_string()], &graph),
+            "App (src/App.res in app)\n → Ui.Button (src/Button.res in ui)"
+        );
+    }
+
+    #[test]
+    fn formats_compile_chain_back_to_the_dirty_module() {
+        let dependency = CompileReason::DirtyDependency {
+            module: "Button-Ui".to_string(),
+        };
+        let chain = vec![("App", &dependency), ("Button-Ui", &CompileReason::ParseDirty)];
+
+        assert_eq!(
+            format_compile_chain(&chain),
+            "App was compiled in the last build:\nApp: depends on Ui.Button\n → Ui.Button: source changed"
+        );
+    }
+}
@@ -0,0 +1,31 @@
+// @ts-check
+// A stand-in for a remote cache: keeps the bodies of PUT requests in memory and serves them to GET
+// requests. Prints its URL once it's listening.
+import * as http from "node:http";
+
+/** @type {Map<string, Buffer>} */
+const entries = new Map();
+
+const server = http.createServer((request, response) => {
+  const url = request.url ?? "";
+  if (request.method === "PUT") {
+    /** @type {Buffer[]} */
+    const chunks = [];
+    request.on("data", chunk => chunks.push(chunk));
+    request.on("end", () => {
+      entries.set(url, Buffer.concat(chunks));
+      response.writeHead(200).end();
+    });
+  } else if (request.method === "GET" && entries.has(url)) {
+    response.writeHead(200).end(entries.get(url));
+  } else {
+    response.writeHead(404).end();
+  }
+});
+
+server.listen(0, "127.0.0.1", () => {
+  const address = server.address();
+  if (address && typeof address === "object") {
+    console.log(`http://127.0.0.1:${address.port}/cache`);
+  }
+});
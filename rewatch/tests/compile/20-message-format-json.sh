@@ -0,0 +1,73 @@
+#!/bin/bash
+
+cd $(dirname $0)
+source "../utils.sh"
+
+bold "Test: --message-format json prints one diagnostic per line on stdout"
+
+fixture=$(mktemp -d 2>/dev/null || mktemp -d -t rewatch-message-format-json)
+trap "rm -rf '$fixture'" EXIT
+
+mkdir -p "$fixture/src"
+
+cat > "$fixture/package.json" <<'EOF'
+{
+  "name": "rewatch-message-format-json",
+  "version": "0.0.1"
+}
+EOF
+
+cat > "$fixture/rescript.json" <<'EOF'
+{
+  "name": "rewatch-message-format-json",
+  "sources": { "dir": "src" },
+  "warnings": { "number": "+26" }
+}
+EOF
+
+cat > "$fixture/src/Main.res" <<'EOF'
+let f = () => {
+  let unused = 1
+  ()
+}
+EOF
+
+cd "$fixture"
+stdout=$(rewatch build --message-format json 2>/dev/null)
+build_status=$?
+
+if [ $build_status -ne 0 ]; then
+  error "Build failed"
+  printf "%s\n" "$stdout" >&2
+  exit 1
+fi
+
+if ! echo "$stdout" | grep -q '"severity":"warning","warning_number":26'; then
+  error "Expected a JSON warning diagnostic for warning 26"
+  printf "%s\n" "$stdout" >&2
+  exit 1
+fi
+
+if echo "$stdout" | grep -v '^{' | grep -q .; then
+  error "stdout contains non-JSON lines"
+  printf "%s\n" "$stdout" >&2
+  exit 1
+fi
+
+cat > "$fixture/src/Main.res" <<'EOF'
+let x: string = 1
+EOF
+
+stdout=$(rewatch build --message-format json 2>/dev/null)
+if [ $? -eq 0 ]; then
+  error "Build with a type error should fail"
+  exit 1
+fi
+
+if ! echo "$stdout" | grep -q '"range":{"start":{"line":1,"column":17}'; then
+  error "Expected a JSON error diagnostic with a range"
+  printf "%s\n" "$stdout" >&2
+  exit 1
+fi
+
+success "--message-format json reports warnings and errors as JSON lines"
@@ -0,0 +1,51 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: build --dry-run lists the planned work without touching lib/"
+
+rewatch build &> /dev/null
+
+lib_state() {
+  find . -path ./node_modules -prune -o -path '*/lib/*' -type f -print0 | sort -z | xargs -0 ls -l --time-style=full-iso
+}
+
+echo '// change' >> ./packages/main/src/InternalDep.res
+before=$(lib_state)
+output=$(rewatch build --dry-run 2>&1)
+dry_run_status=$?
+after=$(lib_state)
+git checkout -- ./packages/main/src/InternalDep.res
+rewatch build &> /dev/null
+
+if [ $dry_run_status -ne 0 ]; then
+  error "build --dry-run failed"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+if [ "$before" != "$after" ]; then
+  error "build --dry-run changed files in lib/"
+  diff <(echo "$before") <(echo "$after") >&2
+  exit 1
+fi
+
+if echo "$output" | grep -q 'Would parse 1 files' \
+  && echo "$output" | grep -q '  packages/main/src/InternalDep.res' \
+  && echo "$output" | grep -q '  InternalDep: source changed' \
+  && echo "$output" | grep -q '  Main: depends on InternalDep'; then
+  success "build --dry-run lists the files to parse and the modules to compile"
+else
+  error "build --dry-run did not list the expected work"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+if [ "$(echo "$output" | grep -n 'InternalDep: source changed' | cut -d: -f1)" -lt "$(echo "$output" | grep -n 'Main: depends on InternalDep' | cut -d: -f1)" ]; then
+  success "build --dry-run lists dependencies before their dependents"
+else
+  error "build --dry-run should list InternalDep before Main"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
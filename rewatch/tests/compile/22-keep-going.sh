@@ -0,0 +1,88 @@
+#!/bin/bash
+
+cd $(dirname $0)
+source "../utils.sh"
+
+bold "Test: --keep-going compiles everything that isn't blocked by an error"
+
+fixture=$(mktemp -d 2>/dev/null || mktemp -d -t rewatch-keep-going)
+trap "rm -rf '$fixture'" EXIT
+
+mkdir -p "$fixture/src"
+
+cat > "$fixture/package.json" <<'EOF'
+{
+  "name": "rewatch-keep-going",
+  "version": "0.0.1"
+}
+EOF
+
+cat > "$fixture/rescript.json" <<'EOF'
+{
+  "name": "rewatch-keep-going",
+  "sources": { "dir": "src" },
+  "package-specs": { "module": "esmodule", "in-source": true },
+  "suffix": ".res.js"
+}
+EOF
+
+# Broken <- Blocked, and an unrelated chain Base <- AlsoBroken, Base <- Late.
+echo 'let value: string = 1' > "$fixture/src/Broken.res"
+echo 'let value = Broken.value' > "$fixture/src/Blocked.res"
+echo 'let value = 1' > "$fixture/src/Base.res"
+echo 'let value: string = Base.value' > "$fixture/src/AlsoBroken.res"
+echo 'let value = Base.value + 1' > "$fixture/src/Late.res"
+
+cd "$fixture"
+output=$(rewatch build --keep-going 2>&1)
+if [ $? -eq 0 ]; then
+  error "Build with type errors should fail"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+if echo "$output" | grep -q 'Broken.res' && echo "$output" | grep -q 'AlsoBroken.res'; then
+  success "--keep-going reports the errors of all failing modules"
+else
+  error "--keep-going should report the errors of both failing modules"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+if echo "$output" | grep -q 'Skipped 1 modules that depend on a module with errors: Blocked'; then
+  success "--keep-going lists the modules blocked by an error"
+else
+  error "--keep-going should list Blocked as skipped"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+if [ -f src/Late.res.js ] && [ ! -f src/Blocked.res.js ]; then
+  success "--keep-going compiles modules that don't depend on a failed module"
+else
+  error "Expected Late to be compiled and Blocked to be skipped"
+  ls src >&2
+  exit 1
+fi
+
+stdout=$(rewatch build --keep-going --message-format json 2>/dev/null)
+if echo "$stdout" | grep -q '"message":"Skipped 1 modules that depend on a module with errors: Blocked"'; then
+  success "--message-format json reports the skipped modules"
+else
+  error "--message-format json should report Blocked as skipped"
+  printf "%s\n" "$stdout" >&2
+  exit 1
+fi
+
+# A cycle next to an unrelated error is reported as a cycle, not as skipped modules.
+echo 'let value = Loop.value' > src/Cycle.res
+echo 'let value = Cycle.value' > src/Loop.res
+output=$(rewatch build --keep-going 2>&1)
+if echo "$output" | grep -q 'Found a circular dependency' \
+  && ! echo "$output" | grep -q 'Skipped .*Cycle' && ! echo "$output" | grep -q 'Skipped .*Loop'; then
+  success "--keep-going reports a cycle next to an unrelated error"
+else
+  error "--keep-going should report the cycle between Cycle and Loop"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
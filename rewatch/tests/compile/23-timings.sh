@@ -0,0 +1,48 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: build writes per-module timings and an optional Chrome trace"
+
+echo '// change' >> ./packages/main/src/InternalDep.res
+output=$(rewatch build --timings chrome 2>&1)
+build_status=$?
+git checkout -- ./packages/main/src/InternalDep.res
+
+if [ $build_status -ne 0 ]; then
+  error "build --timings chrome failed"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+timings=lib/bs/build-timings.json
+if [ -f "$timings" ] \
+  && grep -q '"module": "InternalDep"' "$timings" \
+  && grep -q '"slowest_modules"' "$timings" \
+  && grep -q '"critical_path"' "$timings" \
+  && grep -q '"parallelism"' "$timings"; then
+  success "Build timings are written to $timings"
+else
+  error "Expected $timings with the timings of InternalDep"
+  cat "$timings" >&2
+  exit 1
+fi
+
+trace=lib/bs/build-trace.json
+if [ -f "$trace" ] && grep -q '"traceEvents"' "$trace" && grep -q '"ph": "X"' "$trace"; then
+  success "--timings chrome writes a Chrome trace to $trace"
+else
+  error "Expected a Chrome trace in $trace"
+  cat "$trace" >&2
+  exit 1
+fi
+
+rm -f "$trace"
+rewatch build &> /dev/null
+if [ ! -f "$trace" ]; then
+  success "The Chrome trace is only written with --timings chrome"
+else
+  error "The Chrome trace should not be written without --timings chrome"
+  exit 1
+fi
@@ -0,0 +1,40 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: touched but unchanged sources are not parsed again"
+
+rewatch build &> /dev/null
+
+# Give the file a newer modification time than its AST without changing its content.
+sleep 1
+touch ./packages/main/src/InternalDep.res
+
+output=$(rewatch build --explain 2>&1)
+if [ $? -ne 0 ]; then
+  error "Build after touching a file failed"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+if echo "$output" | grep -q '  InternalDep:'; then
+  error "InternalDep was compiled again although its content didn't change"
+  printf "%s\n" "$output" >&2
+  exit 1
+else
+  success "A touched but unchanged source is not compiled again"
+fi
+
+echo '// change' >> ./packages/main/src/InternalDep.res
+output=$(rewatch build --explain 2>&1)
+git checkout -- ./packages/main/src/InternalDep.res
+rewatch build &> /dev/null
+
+if echo "$output" | grep -q 'InternalDep: source changed'; then
+  success "A changed source is still compiled again"
+else
+  error "InternalDep should be compiled after its content changed"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
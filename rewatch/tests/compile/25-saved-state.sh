@@ -0,0 +1,39 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: the build state is saved and reused by the next build"
+
+rewatch build &> /dev/null
+
+state=lib/bs/build-state.json
+if [ -f "$state" ]; then
+  success "The build state is saved to $state"
+else
+  error "Expected the build to save its state to $state"
+  exit 1
+fi
+
+# Modules with warnings are always compiled again to show them, InternalDep has none.
+output=$(rewatch build --explain 2>&1)
+if [ $? -eq 0 ] && ! echo "$output" | grep -q '  InternalDep:'; then
+  success "A no-op build from the saved state doesn't compile unchanged modules"
+else
+  error "A no-op build from the saved state should not compile InternalDep"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+echo '// change' >> ./packages/main/src/InternalDep.res
+output=$(rewatch build --explain 2>&1)
+git checkout -- ./packages/main/src/InternalDep.res
+rewatch build &> /dev/null
+
+if echo "$output" | grep -q 'InternalDep: source changed' && echo "$output" | grep -q 'Main: depends on InternalDep'; then
+  success "Changes made since the state was saved are still picked up"
+else
+  error "InternalDep and its dependents should be compiled after a change"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
@@ -0,0 +1,57 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: compiler output is restored from RESCRIPT_CACHE_DIR"
+
+cache_dir=$(mktemp -d)
+count_entries() { find "$cache_dir" -mindepth 2 -maxdepth 2 -type d -not -path "$cache_dir/tmp/*" | wc -l; }
+
+rewatch clean &> /dev/null
+RESCRIPT_CACHE_DIR="$cache_dir" rewatch build &> /dev/null
+entries=$(count_entries)
+if [ "$entries" -gt 0 ]; then
+  success "The build stored $entries cache entries"
+else
+  error "Expected the build to store entries in $cache_dir"
+  rm -rf "$cache_dir"
+  exit 1
+fi
+
+rewatch clean &> /dev/null
+RESCRIPT_CACHE_DIR="$cache_dir" rewatch build &> /dev/null
+if [ $? -eq 0 ] && [ "$(count_entries)" -eq "$entries" ]; then
+  success "A clean build restores its output from the cache"
+else
+  error "A clean build with a warm cache should not store new entries"
+  rm -rf "$cache_dir"
+  exit 1
+fi
+
+# A sibling, so the relative paths to the runtime and the portals in package.json still resolve.
+copy_dir=../testrepo-copy
+rm -rf "$copy_dir"
+cp -R . "$copy_dir"
+pushd "$copy_dir" > /dev/null
+rewatch clean &> /dev/null
+RESCRIPT_CACHE_DIR="$cache_dir" rewatch build &> /dev/null
+copy_status=$?
+popd > /dev/null
+rm -rf "$copy_dir"
+if [ $copy_status -eq 0 ] && [ "$(count_entries)" -eq "$entries" ]; then
+  success "A copy of the project at another path restores its output from the cache"
+else
+  error "A build of a copy at another path should not store new entries"
+  rm -rf "$cache_dir"
+  exit 1
+fi
+rm -rf "$cache_dir"
+
+if git diff --exit-code . &> /dev/null && [ -f ./packages/main/lib/ocaml/InternalDep.cmj ]; then
+  success "The restored output matches a regular build"
+else
+  error "The output restored from the cache differs from a regular build"
+  git diff .
+  exit 1
+fi
@@ -0,0 +1,66 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: compiler output is shared through a remote cache"
+
+server_output=$(mktemp)
+node ../tests/cache-server.mjs > "$server_output" &
+server_pid=$!
+trap 'kill $server_pid 2> /dev/null; rm -f "$server_output"' EXIT
+for _ in $(seq 1 50); do
+  [ -s "$server_output" ] && break
+  sleep 0.1
+done
+export RESCRIPT_REMOTE_CACHE_URL=$(cat "$server_output")
+
+# A read-write build fills the remote cache.
+rewatch clean &> /dev/null
+RESCRIPT_REMOTE_CACHE_MODE=read-write rewatch build &> /dev/null
+
+# A read-only build of a copy of the project at another path, with an empty local cache, restores
+# its output from the remote cache and keeps a copy in the local one. The copy is a sibling, so the
+# relative paths to the runtime and the portals in package.json still resolve.
+cache_dir=$(mktemp -d)
+copy_dir=../testrepo-copy
+rm -rf "$copy_dir"
+cp -R . "$copy_dir"
+pushd "$copy_dir" > /dev/null
+rewatch clean &> /dev/null
+RESCRIPT_CACHE_DIR="$cache_dir" rewatch build &> /dev/null
+build_status=$?
+popd > /dev/null
+entries=$(find "$cache_dir" -mindepth 2 -maxdepth 2 -type d -not -path "$cache_dir/tmp/*" | wc -l)
+rm -rf "$cache_dir"
+
+if [ $build_status -eq 0 ] && [ "$entries" -gt 0 ]; then
+  success "A copy at another path restores output from the remote cache"
+else
+  error "Expected the build of a copy at another path to restore entries from the remote cache"
+  rm -rf "$copy_dir"
+  exit 1
+fi
+
+# Build directories hold logs and paths of the copy, compare everything else.
+if git diff --exit-code . &> /dev/null && diff -r -x lib -x node_modules . "$copy_dir" &> /dev/null \
+  && [ -f "$copy_dir/packages/main/lib/ocaml/InternalDep.cmj" ]; then
+  success "The output restored from the remote cache matches a regular build"
+else
+  error "The output restored from the remote cache differs from a regular build"
+  diff -r -x lib -x node_modules . "$copy_dir"
+  rm -rf "$copy_dir"
+  exit 1
+fi
+rm -rf "$copy_dir"
+
+# An unreachable cache doesn't fail the build.
+kill $server_pid 2> /dev/null
+wait $server_pid 2> /dev/null
+rewatch clean &> /dev/null
+if rewatch build &> /dev/null; then
+  success "The build succeeds when the remote cache can't be reached"
+else
+  error "The build should not fail because the remote cache can't be reached"
+  exit 1
+fi
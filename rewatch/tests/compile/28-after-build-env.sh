@@ -0,0 +1,42 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: --after-build gets quoted arguments and the build summary"
+
+rewatch build &> /dev/null
+
+echo '// change' >> ./packages/main/src/InternalDep.res
+# Called directly, the rewatch helper would split the quoted command again.
+output=$(RUST_BACKTRACE=1 $REWATCH_EXECUTABLE build --after-build \
+  "sh -c 'printf \"arg=%s\n\" \"\$1\"; printf \"changed=%s\n\" \$RESCRIPT_CHANGED_FILES; printf \"module=%s\n\" \$RESCRIPT_COMPILED_MODULES; printf \"outcome=%s\n\" \"\$RESCRIPT_OUTCOME\"' after-build 'two words'" 2>&1)
+status=$?
+git checkout -- ./packages/main/src/InternalDep.res
+rewatch build &> /dev/null
+
+if [ $status -ne 0 ]; then
+  error "build --after-build failed"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+if echo "$output" | grep -q '^\[out\] arg=two words$'; then
+  success "Quoted arguments are passed as one argument"
+else
+  error "Quoted arguments were split"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+# A comment doesn't change the interface of InternalDep, so Main, which depends on it, is skipped.
+if echo "$output" | grep -q '^\[out\] changed=.*packages/main/src/InternalDep.res$' \
+  && echo "$output" | grep -q '^\[out\] module=InternalDep$' \
+  && ! echo "$output" | grep -q '^\[out\] module=Main$' \
+  && echo "$output" | grep -qE '^\[out\] outcome=(clean|warnings)$'; then
+  success "The after-build command gets the changed files, compiled modules and outcome"
+else
+  error "The after-build command did not get the build summary"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
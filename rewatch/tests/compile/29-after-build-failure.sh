@@ -0,0 +1,56 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: --after-build-failure runs on failed builds and hook failures are reported"
+
+rewatch build &> /dev/null
+
+echo 'let broken =' >> ./packages/main/src/InternalDep.res
+# Called directly, the rewatch helper would split the quoted commands again.
+output=$(RUST_BACKTRACE=1 $REWATCH_EXECUTABLE build \
+  --after-build "sh -c 'echo after-build ran'" \
+  --after-build-failure "sh -c 'echo after-build-failure ran with \$RESCRIPT_OUTCOME'" 2>&1)
+status=$?
+git checkout -- ./packages/main/src/InternalDep.res
+rewatch build &> /dev/null
+
+if [ $status -ne 0 ] \
+  && echo "$output" | grep -q '^\[out\] after-build-failure ran with failed$' \
+  && ! echo "$output" | grep -q '^\[out\] after-build ran$'; then
+  success "Only the failure command runs when the build fails"
+else
+  error "The failure command did not run on a failed build"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+output=$(RUST_BACKTRACE=1 $REWATCH_EXECUTABLE build --after-build "sh -c 'exit 3'" 2>&1)
+status=$?
+if [ $status -eq 0 ] && echo "$output" | grep -q 'failed with exit status: 3'; then
+  success "A failing after-build command is reported without failing the build"
+else
+  error "A failing after-build command should be reported and not fail the build"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+output=$(RUST_BACKTRACE=1 $REWATCH_EXECUTABLE build --after-build "sh -c 'exit 3'" --after-build-fail-build 2>&1)
+if [ $? -ne 0 ]; then
+  success "--after-build-fail-build fails the build when the after-build command fails"
+else
+  error "--after-build-fail-build should make the build exit non-zero"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+output=$(RUST_BACKTRACE=1 $REWATCH_EXECUTABLE build --after-build "rescript-no-such-command" 2>&1)
+if echo "$output" | grep -q 'Could not run the after-build command `rescript-no-such-command`' \
+  && ! echo "$output" | grep -q 'panicked'; then
+  success "A missing after-build command is reported without panicking"
+else
+  error "A missing after-build command should be reported cleanly"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
@@ -0,0 +1,63 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: build --check type-checks without writing JS"
+
+rewatch build &> /dev/null
+
+restore() {
+  git checkout -- ./packages/main/src/InternalDep.res
+  rewatch build &> /dev/null
+}
+
+echo 'let value = 2' > ./packages/main/src/InternalDep.res
+error_output=$(rewatch build --check 2>&1)
+if [ $? -ne 0 ]; then
+  error "build --check failed on valid code"
+  printf "%s\n" "$error_output" >&2
+  restore
+  exit 1
+fi
+
+if git diff --exit-code ./packages/main/src/InternalDep.mjs &> /dev/null; then
+  success "build --check did not write JS"
+else
+  error "build --check changed the JS output"
+  git diff ./packages/main/src/InternalDep.mjs
+  restore
+  exit 1
+fi
+
+if [ -f ./packages/main/lib/ocaml/InternalDep.cmt ] && [ ! -f ./packages/main/lib/ocaml/InternalDep.cmj ]; then
+  success "build --check wrote the .cmt without a .cmj"
+else
+  error "build --check should write the .cmt and remove the outdated .cmj"
+  ls ./packages/main/lib/ocaml/InternalDep.*
+  restore
+  exit 1
+fi
+
+rewatch build &> /dev/null
+if grep -q 'value = 2' ./packages/main/src/InternalDep.mjs; then
+  success "The next build writes the JS of the checked module"
+else
+  error "The next build did not write the JS of the checked module"
+  cat ./packages/main/src/InternalDep.mjs
+  restore
+  exit 1
+fi
+
+echo 'let value: string = 2' > ./packages/main/src/InternalDep.res
+error_output=$(rewatch build --check 2>&1)
+if [ $? -ne 0 ]; then
+  success "build --check fails on a type error"
+else
+  error "build --check should fail on a type error"
+  printf "%s\n" "$error_output" >&2
+  restore
+  exit 1
+fi
+
+restore
@@ -0,0 +1,55 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: build --target only compiles the target and its dependencies"
+
+rewatch build &> /dev/null
+
+echo '// change' >> ./packages/main/src/InternalDep.res
+output=$(rewatch build --dry-run --target packages/main/src/InternalDep.res 2>&1)
+if echo "$output" | grep -q '  InternalDep: source changed' \
+  && ! echo "$output" | grep -q '  Main: depends on InternalDep'; then
+  success "build --dry-run --target leaves out the dependents of the target"
+else
+  error "build --dry-run --target should only list InternalDep"
+  printf "%s\n" "$output" >&2
+  git checkout -- ./packages/main/src/InternalDep.res
+  rewatch build &> /dev/null
+  exit 1
+fi
+
+output=$(rewatch build --target packages/main/src/InternalDep.res 2>&1)
+if [ $? -eq 0 ] && echo "$output" | grep -q 'Compiled 1 modules'; then
+  success "build --target compiled only the target"
+else
+  error "build --target should compile only InternalDep"
+  printf "%s\n" "$output" >&2
+  git checkout -- ./packages/main/src/InternalDep.res
+  rewatch build &> /dev/null
+  exit 1
+fi
+
+output=$(rewatch build 2>&1)
+if echo "$output" | grep -q 'Compiled 1 modules'; then
+  success "The next build compiles the dependents left out by --target"
+else
+  error "The next build should compile Main, which depends on InternalDep"
+  printf "%s\n" "$output" >&2
+  git checkout -- ./packages/main/src/InternalDep.res
+  rewatch build &> /dev/null
+  exit 1
+fi
+
+git checkout -- ./packages/main/src/InternalDep.res
+rewatch build &> /dev/null
+
+output=$(rewatch build --target NoSuchModule 2>&1)
+if [ $? -ne 0 ] && echo "$output" | grep -q 'Could not find a module for target NoSuchModule'; then
+  success "An unknown target is reported"
+else
+  error "An unknown target should fail the build"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
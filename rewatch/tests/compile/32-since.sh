@@ -0,0 +1,89 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: affected and build --since follow the changes since a git revision"
+
+rewatch build &> /dev/null
+
+restore() {
+  git checkout -- ./packages/main/src/InternalDep.res ./packages/main/rescript.json
+  rewatch build &> /dev/null
+}
+
+output=$(rewatch affected --since HEAD 2>&1)
+if [ $? -eq 0 ] && [ -z "$output" ]; then
+  success "Nothing is affected without changes"
+else
+  error "affected should print nothing without changes"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+echo '// change' >> ./packages/main/src/InternalDep.res
+output=$(rewatch affected --since HEAD 2>&1)
+if echo "$output" | grep -q '^InternalDep$' && echo "$output" | grep -q '^Main$'; then
+  success "affected lists the changed module and its dependents"
+else
+  error "affected should list InternalDep and Main"
+  printf "%s\n" "$output" >&2
+  restore
+  exit 1
+fi
+
+output=$(rewatch build --since HEAD 2>&1)
+if [ $? -eq 0 ] && echo "$output" | grep -q 'Compiled 2 modules'; then
+  success "build --since compiled the affected modules"
+else
+  error "build --since should compile InternalDep and Main"
+  printf "%s\n" "$output" >&2
+  restore
+  exit 1
+fi
+
+git checkout -- ./packages/main/src/InternalDep.res
+rewatch build &> /dev/null
+
+echo '' >> ./packages/main/rescript.json
+output=$(rewatch affected --since HEAD 2>&1)
+if echo "$output" | grep -q '^InternalDep$' && echo "$output" | grep -q '^Main$'; then
+  success "A changed rescript.json affects every module of its package"
+else
+  error "A changed rescript.json should affect InternalDep and Main"
+  printf "%s\n" "$output" >&2
+  restore
+  exit 1
+fi
+restore
+
+rm ./packages/main/src/InternalDep.res
+output=$(rewatch affected --since HEAD 2>&1)
+if echo "$output" | grep -q '^Main$' && ! echo "$output" | grep -q '^InternalDep$'; then
+  success "affected lists the modules importing a deleted module"
+else
+  error "affected should list Main, which imports the deleted InternalDep"
+  printf "%s\n" "$output" >&2
+  restore
+  exit 1
+fi
+
+output=$(rewatch build --since HEAD 2>&1)
+if [ $? -ne 0 ]; then
+  success "build --since fails when a deleted module is still imported"
+else
+  error "build --since should compile Main and fail on the deleted InternalDep"
+  printf "%s\n" "$output" >&2
+  restore
+  exit 1
+fi
+restore
+
+output=$(rewatch affected --since no-such-revision 2>&1)
+if [ $? -ne 0 ] && echo "$output" | grep -q 'git diff --name-only -z no-such-revision -- failed'; then
+  success "An unknown revision is reported"
+else
+  error "An unknown revision should fail"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
@@ -0,0 +1,121 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: rescript build is served by a running daemon"
+
+cleanup() {
+  exit_watcher
+  rm -f rewatch.log
+}
+
+trap cleanup EXIT
+
+error_output=$(rewatch clean 2>&1)
+if [ $? -eq 0 ];
+then
+  success "Repo Cleaned"
+else
+  error "Error Cleaning Repo"
+  printf "%s\n" "$error_output" >&2
+  exit 1
+fi
+
+clear_locks
+
+rewatch_bg daemon > rewatch.log 2>&1 &
+
+wait_for_socket() {
+  local timeout=20
+  while [ "$timeout" -gt 0 ]; do
+    [ -S lib/daemon.sock ] && return 0
+    sleep 1
+    timeout=$((timeout - 1))
+  done
+  return 1
+}
+
+if wait_for_socket; then
+  success "Daemon started"
+else
+  error "Daemon did not create lib/daemon.sock"
+  cat rewatch.log
+  exit 1
+fi
+
+if rewatch watch > /dev/null 2>&1; then
+  error "A watcher should not start while the daemon holds watch.lock"
+  exit 1
+else
+  success "The daemon holds watch.lock"
+fi
+
+echo 'Console.log("added-by-daemon-test")' >> ./packages/main/src/Main.res
+
+build_output=$(rewatch build 2>&1)
+if [ $? -eq 0 ] && node ./packages/main/src/Main.mjs | grep 'added-by-daemon-test' &> /dev/null; then
+  success "The daemon picked up a change made right before the build"
+else
+  error "The daemon's build did not include the change"
+  printf "%s\n" "$build_output"
+  exit 1
+fi
+
+if echo "$build_output" | grep 'Compiled' &> /dev/null; then
+  success "The build output is printed by the client"
+else
+  error "Expected the build output on the client"
+  printf "%s\n" "$build_output"
+  exit 1
+fi
+
+replace '/Console.log("added-by-daemon-test")/d' ./packages/main/src/Main.res
+
+if rewatch -v build --prod 2>&1 | grep 'Building without the daemon' &> /dev/null; then
+  success "A build with another package selection falls back to an in-process build"
+else
+  error "Expected a --prod build to be declined by the daemon"
+  exit 1
+fi
+
+if rewatch build &> /dev/null && git diff --exit-code ./ &> /dev/null; then
+  success "Adding and removing changes nothing"
+else
+  error "Adding and removing changes left some artifacts"
+  git diff ./
+  exit 1
+fi
+
+cp rescript.json rescript.json.bak
+echo '{ not json' > rescript.json
+sleep 1
+build_output=$(rewatch build 2>&1)
+build_status=$?
+mv rescript.json.bak rescript.json
+sleep 1
+if [ $build_status -ne 0 ] && [ -S lib/daemon.sock ] && echo "$build_output" | grep -q 'Could not initialize build'; then
+  success "An invalid rescript.json fails the build without stopping the daemon"
+else
+  error "An invalid rescript.json should fail the build and keep the daemon running"
+  printf "%s\n" "$build_output"
+  cat rewatch.log
+  exit 1
+fi
+
+if rewatch build &> /dev/null; then
+  success "The daemon builds again once rescript.json is fixed"
+else
+  error "The daemon should build again once rescript.json is fixed"
+  cat rewatch.log
+  exit 1
+fi
+
+exit_watcher
+
+if wait_for_file_gone "lib/daemon.sock" 10; then
+  success "The daemon stops and removes its socket when watch.lock is removed"
+else
+  error "lib/daemon.sock was not removed"
+  exit 1
+fi
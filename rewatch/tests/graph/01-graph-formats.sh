@@ -0,0 +1,49 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: rescript graph exports the module dependency graph"
+
+rewatch build &> /dev/null
+
+dot=$(rewatch graph 2>&1)
+if [ $? -ne 0 ]; then
+  error "rescript graph failed"
+  printf "%s\n" "$dot" >&2
+  exit 1
+fi
+
+if echo "$dot" | grep -q '"Main" -> "InternalDep";'; then
+  success "DOT output contains the Main -> InternalDep edge"
+else
+  error "DOT output is missing the Main -> InternalDep edge"
+  printf "%s\n" "$dot" >&2
+  exit 1
+fi
+
+json=$(rewatch graph --format json --root Main --depth 1 2>/dev/null)
+if echo "$json" | grep -q '"to": "Dep01"' && ! echo "$json" | grep -q '"from": "Dep01"'; then
+  success "--root with --depth 1 only follows direct dependencies"
+else
+  error "--root with --depth 1 returned an unexpected graph"
+  printf "%s\n" "$json" >&2
+  exit 1
+fi
+
+mermaid=$(rewatch graph --format mermaid --package-level 2>/dev/null)
+if echo "$mermaid" | grep -q '^flowchart LR' && echo "$mermaid" | grep -qF '["@testrepo/main"]'; then
+  success "Package-level Mermaid output lists packages"
+else
+  error "Package-level Mermaid output is missing packages"
+  printf "%s\n" "$mermaid" >&2
+  exit 1
+fi
+
+if git diff --exit-code . > /dev/null; then
+  success "rescript graph left the testrepo untouched"
+else
+  error "rescript graph changed files in the testrepo"
+  git diff --stat .
+  exit 1
+fi
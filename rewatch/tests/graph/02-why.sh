@@ -0,0 +1,48 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: rescript why explains dependency chains and recompiles"
+
+rewatch build &> /dev/null
+
+chain=$(rewatch why Main InternalDep 2>&1)
+if [ $? -eq 0 ] && echo "$chain" | grep -q '^Main (' && echo "$chain" | grep -q '→ InternalDep ('; then
+  success "why prints the chain from Main to InternalDep"
+else
+  error "why did not print the chain from Main to InternalDep"
+  printf "%s\n" "$chain" >&2
+  exit 1
+fi
+
+rewatch why InternalDep Main &> /dev/null
+if [ $? -ne 0 ]; then
+  success "why fails when there is no dependency chain"
+else
+  error "why should fail when InternalDep does not depend on Main"
+  exit 1
+fi
+
+echo '// change' >> ./packages/main/src/InternalDep.res
+rewatch build &> /dev/null
+explanation=$(rewatch why Main 2>&1)
+git checkout -- ./packages/main/src/InternalDep.res
+rewatch build &> /dev/null
+
+if echo "$explanation" | grep -q 'Main: depends on InternalDep' && echo "$explanation" | grep -q 'InternalDep: source changed'; then
+  success "why explains why Main was compiled in the last build"
+else
+  error "why did not explain why Main was compiled"
+  printf "%s\n" "$explanation" >&2
+  exit 1
+fi
+
+output=$(rewatch why NoSuchModule 2>&1)
+if [ $? -ne 0 ] && echo "$output" | grep -q "Module 'NoSuchModule' not found"; then
+  success "why fails for a module that wasn't in the last build"
+else
+  error "why should fail for a module that wasn't in the last build"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
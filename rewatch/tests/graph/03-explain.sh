@@ -0,0 +1,30 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: --explain reports why modules are compiled"
+
+rewatch build &> /dev/null
+
+output=$(rewatch build --explain 2>&1)
+if echo "$output" | grep -q 'Explain: no modules needed compiling'; then
+  success "A no-op build explains that nothing needed compiling"
+else
+  error "A no-op build should explain that nothing needed compiling"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
+
+echo '// change' >> ./packages/main/src/InternalDep.res
+output=$(rewatch build --explain 2>&1)
+git checkout -- ./packages/main/src/InternalDep.res
+rewatch build &> /dev/null
+
+if echo "$output" | grep -q '  InternalDep: source changed' && echo "$output" | grep -q '  Main: depends on InternalDep'; then
+  success "--explain lists the changed source and its dependents"
+else
+  error "--explain did not list the expected reasons"
+  printf "%s\n" "$output" >&2
+  exit 1
+fi
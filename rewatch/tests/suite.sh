@@ -132,6 +132,19 @@ fi
 ./compile/17-prod-flag.sh &&
 ./compile/18-external-dep-uncurried-dot.sh &&
 ./compile/19-utf8-warning.sh &&
+./compile/20-message-format-json.sh &&
+./compile/21-dry-run.sh &&
+./compile/22-keep-going.sh &&
+./compile/23-timings.sh &&
+./compile/24-content-hash.sh &&
+./compile/25-saved-state.sh &&
+./compile/26-artifact-cache.sh &&
+./compile/27-remote-cache.sh &&
+./compile/28-after-build-env.sh &&
+./compile/29-after-build-failure.sh &&
+./compile/30-check.sh &&
+./compile/31-target.sh &&
+./compile/32-since.sh &&
 ./compile/14-no-testrepo-changes.sh &&
 ./compile/15-no-new-files.sh &&
 ./compile/16-snapshots-unchanged.sh &&
@@ -144,10 +157,17 @@ fi
 ./watch/04-watch-config-change.sh &&
 ./watch/05-watch-ignores-non-source.sh &&
 ./watch/06-watch-missing-source-folder.sh &&
+./watch/07-watch-events.sh &&
+./watch/08-watch-cancel-restart.sh &&
+./watch/09-watch-poll-backend.sh &&
+./watch/10-watch-ignore-patterns.sh &&
 
 # Lock tests
 ./lock/01-lock-when-watching.sh &&
 
+# Daemon tests
+./daemon/01-daemon-build.sh &&
+
 # Suffix tests
 ./suffix/01-custom-suffix.sh &&
 
@@ -179,6 +199,11 @@ fi
 ./features/05-features-cycle-errors.sh &&
 ./features/06-features-empty-flag-rejected.sh &&
 
+# Graph tests
+./graph/01-graph-formats.sh &&
+./graph/02-why.sh &&
+./graph/03-explain.sh &&
+
 # Compiler-args tests
 ./compiler-args/01-compiler-args-cwd-invariant.sh &&
 ./compiler-args/02-warnings-in-parser-and-compiler.sh
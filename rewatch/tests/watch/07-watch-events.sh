@@ -0,0 +1,104 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: watch --events stdout-json streams build events"
+
+error_output=$(rewatch clean 2>&1)
+if [ $? -eq 0 ];
+then
+  success "Repo Cleaned"
+else
+  error "Error Cleaning Repo"
+  printf "%s\n" "$error_output" >&2
+  exit 1
+fi
+
+rewatch_bg watch --events stdout-json --explain --after-build pwd > events.log 2> rewatch.log &
+success "Watcher Started"
+
+wait_for_event() {
+  local pattern="$1"; local timeout=20
+  while [ "$timeout" -gt 0 ]; do
+    grep -E "$pattern" events.log &> /dev/null && return 0
+    sleep 1
+    timeout=$((timeout - 1))
+  done
+  return 1
+}
+
+if wait_for_event '"event":"build-finished","kind":"initial","success":true'; then
+  success "The initial build is reported"
+else
+  error "Expected a build-finished event for the initial build"
+  cat events.log rewatch.log
+  exit_watcher
+  exit 1
+fi
+
+if grep -v '^{"event":' events.log &> /dev/null; then
+  error "stdout should only contain events"
+  cat events.log
+  exit_watcher
+  exit 1
+else
+  success "stdout only contains events"
+fi
+
+if grep -q 'Explain: compiling' rewatch.log; then
+  success "--explain goes to stderr next to the events"
+else
+  error "Expected the --explain output on stderr"
+  cat rewatch.log
+  exit_watcher
+  exit 1
+fi
+
+echo 'Console.log("added-by-events-test")' >> ./packages/main/src/Main.res
+
+if wait_for_event '"event":"build-finished","kind":"incremental","success":true'; then
+  success "The incremental build is reported"
+else
+  error "Expected a build-finished event for the incremental build"
+  cat events.log rewatch.log
+  exit_watcher
+  exit 1
+fi
+
+for pattern in \
+  '"event":"files-changed","paths":\[[^]]*Main\.res"' \
+  '"event":"build-started","kind":"incremental"' \
+  '"event":"parse-finished","parsed":1,"success":true' \
+  '"event":"module-compiled","module":"Main","package":"main","success":true' \
+  '"event":"diagnostics"'; do
+  if ! grep -E "$pattern" events.log &> /dev/null; then
+    error "Missing event matching $pattern"
+    cat events.log
+    exit_watcher
+    exit 1
+  fi
+done
+success "The incremental build reports changed files, parsing and compiled modules"
+
+if wait_for_event '"event":"after-build-output","stream":"stdout","line":"[^"]*testrepo"'; then
+  success "The output of the after-build command is forwarded as events"
+else
+  error "Expected an after-build-output event"
+  cat events.log rewatch.log
+  exit_watcher
+  exit 1
+fi
+
+replace '/Console.log("added-by-events-test")/d' ./packages/main/src/Main.res
+sleep 2
+exit_watcher
+rm -f events.log
+
+if git diff --exit-code ./ &> /dev/null; then
+  success "Adding and removing changes nothing"
+else
+  error "Adding and removing changes left some artifacts"
+  git diff ./
+  exit 1
+fi
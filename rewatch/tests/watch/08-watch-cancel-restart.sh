@@ -0,0 +1,80 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: changes during a watch build restart it"
+
+error_output=$(rewatch clean 2>&1)
+if [ $? -eq 0 ];
+then
+  success "Repo Cleaned"
+else
+  error "Error Cleaning Repo"
+  printf "%s\n" "$error_output" >&2
+  exit 1
+fi
+
+rewatch_bg watch --events stdout-json > events.log 2> rewatch.log &
+success "Watcher Started"
+
+wait_for_event() {
+  local pattern="$1"; local timeout=20
+  while [ "$timeout" -gt 0 ]; do
+    grep -E "$pattern" events.log &> /dev/null && return 0
+    sleep 1
+    timeout=$((timeout - 1))
+  done
+  return 1
+}
+
+if ! wait_for_event '"event":"build-finished","kind":"initial"'; then
+  error "Expected the initial build to finish"
+  cat events.log rewatch.log
+  exit_watcher
+  exit 1
+fi
+
+# Save twice in a row, the second save usually lands while the first build is compiling.
+echo 'Console.log("first-save")' >> ./packages/main/src/Main.res
+sleep 0.3
+echo 'Console.log("second-save")' >> ./packages/main/src/Main.res
+
+if ! wait_for_event '"event":"build-finished","kind":"incremental","success":true,"cancelled":false'; then
+  error "Expected an incremental build to finish"
+  cat events.log rewatch.log
+  exit_watcher
+  exit 1
+fi
+sleep 2
+
+if [ "$(tail -n 1 events.log | grep -c '"cancelled":true')" -ne 0 ]; then
+  error "A cancelled build should be followed by another build"
+  cat events.log
+  exit_watcher
+  exit 1
+fi
+
+if node ./packages/main/src/Main.mjs | grep 'second-save' &> /dev/null;
+then
+  success "The output contains the latest save"
+else
+  error "The output is missing the latest save"
+  cat events.log rewatch.log
+  exit_watcher
+  exit 1
+fi
+
+replace '/Console.log("first-save")/d' ./packages/main/src/Main.res
+replace '/Console.log("second-save")/d' ./packages/main/src/Main.res
+sleep 2
+exit_watcher
+rm -f events.log
+
+if git diff --exit-code ./ &> /dev/null; then
+  success "Adding and removing changes nothing"
+else
+  error "Adding and removing changes left some artifacts"
+  git diff ./
+  exit 1
+fi
@@ -0,0 +1,59 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: It should watch with the poll backend"
+
+error_output=$(rewatch clean 2>&1)
+if [ $? -eq 0 ];
+then
+  success "Repo Cleaned"
+else
+  error "Error Cleaning Repo"
+  printf "%s\n" "$error_output" >&2
+  exit 1
+fi
+
+# Start watcher and capture logs for debugging
+rewatch_bg watch --watch-backend poll --poll-interval 200 > rewatch.log 2>&1 &
+success "Watcher Started"
+
+# Trigger a recompilation
+echo 'Console.log("added-by-poll-test")' >> ./packages/main/src/Main.res
+
+# Wait for the compiled JS to show up (can be slow in CI)
+target=./packages/main/src/Main.mjs
+if ! wait_for_file "$target" 20; then
+  error "Expected output not found: $target"
+  ls -la ./packages/main/src || true
+  tail -n 200 rewatch.log || true
+  exit_watcher
+  exit 1
+fi
+
+if node ./packages/main/src/Main.mjs | grep 'added-by-poll-test' &> /dev/null;
+then
+  success "Output is correct"
+else
+  error "Output is incorrect"
+  exit_watcher
+  exit 1
+fi
+
+sleep 1
+
+replace '/Console.log("added-by-poll-test")/d' ./packages/main/src/Main.res;
+
+sleep 5
+
+if git diff --exit-code ./
+then
+  success "Adding and removing changes nothing"
+else
+  error "Adding and removing changes left some artifacts"
+  exit_watcher
+  exit 1
+fi
+
+exit_watcher
@@ -0,0 +1,85 @@
+#!/bin/bash
+cd $(dirname $0)
+source "../utils.sh"
+cd ../../testrepo
+
+bold "Test: Watcher ignores files matching watch.ignore"
+
+error_output=$(rewatch clean 2>&1)
+if [ $? -eq 0 ];
+then
+  success "Repo Cleaned"
+else
+  error "Error Cleaning Repo"
+  printf "%s\n" "$error_output" >&2
+  exit 1
+fi
+
+restore_config() {
+  git checkout ./rescript.json &> /dev/null
+}
+
+node -e '
+  const fs = require("fs");
+  const config = JSON.parse(fs.readFileSync("rescript.json", "utf8"));
+  config.watch = { debounce: 100, ignore: ["src/WatchIgnored*.res"] };
+  fs.writeFileSync("rescript.json", `${JSON.stringify(config, null, 2)}\n`);
+'
+
+rewatch_bg watch > rewatch.log 2>&1 &
+success "Watcher Started"
+
+if ! wait_for_file "./src/Test.mjs" 20; then
+  error "Initial build did not complete"
+  cat rewatch.log
+  exit_watcher
+  restore_config
+  exit 1
+fi
+success "Initial build completed"
+
+echo 'let ignored = true' > ./src/WatchIgnored.res
+echo 'let probe = "watcher-is-alive"' > ./src/WatchProbe.res
+
+if ! wait_for_file "./src/WatchProbe.mjs" 20; then
+  error "Watcher did not respond to source change (probe file not compiled)"
+  cat rewatch.log
+  rm -f ./src/WatchIgnored.res ./src/WatchProbe.res
+  exit_watcher
+  restore_config
+  exit 1
+fi
+success "Watcher responded to source change"
+
+# The probe's full rebuild compiles every source, an ignored file is still part of the build.
+sleep 1
+rm -f ./src/WatchIgnored.mjs
+echo 'let changed = true' >> ./src/WatchIgnored.res
+sleep 3
+
+if [ -f ./src/WatchIgnored.mjs ]; then
+  error "A change to an ignored file triggered a rebuild"
+  cat rewatch.log
+  rm -f ./src/WatchIgnored.res ./src/WatchIgnored.mjs ./src/WatchProbe.res ./src/WatchProbe.mjs
+  exit_watcher
+  restore_config
+  exit 1
+fi
+success "A change to an ignored file was correctly ignored"
+
+exit_watcher
+restore_config
+
+sleep 2
+rm -f ./src/WatchIgnored.res ./src/WatchIgnored.mjs ./src/WatchProbe.res ./src/WatchProbe.mjs
+rm -f rewatch.log
+
+if git diff --exit-code . > /dev/null 2>&1 && [ -z "$(git ls-files --others --exclude-standard .)" ];
+then
+  success "No leftover changes"
+else
+  error "Leftover changes detected"
+  git diff .
+  git ls-files --others --exclude-standard .
+  exit 1
+fi
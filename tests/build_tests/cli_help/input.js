@@ -15,8 +15,12 @@ const cliHelp =
   "Commands:\n" +
   "  build          Build the project (default command)\n" +
   "  watch          Build, then start a watcher\n" +
+  "  daemon         Build, then keep the build state in memory and serve `rescript build` from it\n" +
   "  clean          Clean the build artifacts\n" +
   "  format         Format ReScript files\n" +
+  "  graph          Export the module dependency graph\n" +
+  "  why            Explain why a module depends on another one, or why it was compiled in the last build\n" +
+  "  affected       List the modules affected by the changes since a git revision\n" +
   "  compiler-args  Print the compiler arguments for a ReScript source file\n" +
   "  help           Print this message or the help of the given subcommand(s)\n" +
   "\n" +
@@ -39,15 +43,48 @@ const buildHelp =
   "  [FOLDER]  Path to the project or subproject. This folder must contain a rescript.json file [default: .]\n" +
   "\n" +
   "Options:\n" +
-  "  -f, --filter <FILTER>            Filter source files by regex. E.g., filter out test files for compilation while doing feature work\n" +
-  "  -v, --verbose...                 Increase logging verbosity\n" +
-  "  -a, --after-build <AFTER_BUILD>  Run an additional command after build. E.g., play a sound or run a test suite when done compiling\n" +
-  "  -q, --quiet...                   Decrease logging verbosity\n" +
-  '      --warn-error <WARN_ERROR>    Override warning configuration from rescript.json. Example: --warn-error "+3+8+11+12+26+27+31+32+33+34+35+39+44+45+110"\n' +
-  "      --features <FEATURES>        Restrict the current package to a comma-separated set of features. Only source directories tagged with one of these features (plus untagged ones, and features they transitively imply through the top-level `features` map) are compiled. Omit the flag to build with all features active. Example: --features native,experimental\n" +
-  "  -n, --no-timing [<NO_TIMING>]    Disable output timing [default: false] [possible values: true, false]\n" +
-  '      --prod                       Skip dev-dependencies and dev sources (type: "dev")\n' +
-  "  -h, --help                       Print help\n";
+  "  -f, --filter <FILTER>\n" +
+  "          Filter source files by regex. E.g., filter out test files for compilation while doing feature work\n" +
+  "  -v, --verbose...\n" +
+  "          Increase logging verbosity\n" +
+  "  -a, --after-build <AFTER_BUILD>\n" +
+  "          Run an additional command after build. E.g., play a sound or run a test suite when done compiling\n" +
+  "  -q, --quiet...\n" +
+  "          Decrease logging verbosity\n" +
+  "      --after-build-failure <AFTER_BUILD_FAILURE>\n" +
+  "          Run an additional command when the build fails. E.g., send a notification when compiling breaks\n" +
+  "      --warn-error <WARN_ERROR>\n" +
+  '          Override warning configuration from rescript.json. Example: --warn-error "+3+8+11+12+26+27+31+32+33+34+35+39+44+45+110"\n' +
+  "      --features <FEATURES>\n" +
+  "          Restrict the current package to a comma-separated set of features. Only source directories tagged with one of these features (plus untagged ones, and features they transitively imply through the top-level `features` map) are compiled. Omit the flag to build with all features active. Example: --features native,experimental\n" +
+  "      --message-format <FORMAT>\n" +
+  "          How to report compiler errors and warnings. `json` prints one JSON object per diagnostic (file, range, severity, warning number, package and message) on stdout and disables the progress output [default: human] [possible values: human, json]\n" +
+  "      --explain\n" +
+  "          Print why each module is compiled: changed source, dirty/deleted dependency, expired .cmi of a dependency, or a compiler/config change since the last build\n" +
+  "      --keep-going\n" +
+  "          When a module fails to compile, keep compiling every module that doesn't depend on it and report all errors at the end, instead of stopping at the first failing dependency wave\n" +
+  "      --timings <FORMAT>\n" +
+  "          Per-module build timings are always written to `lib/bs/build-timings.json`. With `chrome`, a Chrome trace-event file is also written to `lib/bs/build-trace.json` [possible values: chrome]\n" +
+  "  -j, --jobs <JOBS>\n" +
+  "          Maximum number of compiler processes to run at the same time while parsing, compiling and formatting. Falls back to the `RESCRIPT_JOBS` environment variable, and to the number of CPUs when neither is set\n" +
+  "      --dry-run\n" +
+  "          Print the files that would be parsed, the modules that would be compiled (in scheduler order) and the stale artifacts that would be deleted, without running the compiler or changing anything in `lib/`\n" +
+  "      --check\n" +
+  "          Only type-check: write `.cmi` and `.cmt` files, but no JS, and skip `js-post-build`. The next build without `--check` compiles the checked modules again to write their JS\n" +
+  "      --target <FILE_OR_MODULE>\n" +
+  "          Only compile this module and the modules it depends on. A source file, relative to the working directory, or a module name. Can be repeated. Other changed modules are compiled by the next build without `--target`\n" +
+  "      --since <GIT_REF>\n" +
+  "          Only compile the modules affected by the changes since this git revision: modules whose source or package `rescript.json` differs from it (per `git diff --name-only`) and their dependents, plus the modules they depend on. Other changed modules are compiled by the next build without `--since`\n" +
+  "      --after-build-fail-build\n" +
+  "          Exit with a non-zero code when the `--after-build` command fails\n" +
+  "  -n, --no-timing [<NO_TIMING>]\n" +
+  "          Disable output timing [default: false] [possible values: true, false]\n" +
+  "      --no-daemon\n" +
+  "          Build in this process even when a `rescript daemon` is running for the project\n" +
+  "      --prod\n" +
+  '          Skip dev-dependencies and dev sources (type: "dev")\n' +
+  "  -h, --help\n" +
+  "          Print help\n";
 
 const cleanHelp =
   "Clean the build artifacts\n" +
@@ -76,6 +113,7 @@ const formatHelp =
   "  -v, --verbose...     Increase logging verbosity\n" +
   "  -q, --quiet...       Decrease logging verbosity\n" +
   "  -s, --stdin <STDIN>  Read the code from stdin and print the formatted code to stdout [possible values: .res, .resi]\n" +
+  "  -j, --jobs <JOBS>    Maximum number of compiler processes to run at the same time while parsing, compiling and formatting. Falls back to the `RESCRIPT_JOBS` environment variable, and to the number of CPUs when neither is set\n" +
   "  -h, --help           Print help\n";
 
 const compilerArgsHelp =